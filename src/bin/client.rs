@@ -1,15 +1,11 @@
-use std::collections::HashMap;
 use std::io::{self, Write};
-use std::net::UdpSocket;
-use std::str;
-use rand::Rng;
+use std::process;
+use std::str::FromStr;
 use chrono::NaiveDateTime;
-use std::time::{Duration, Instant};
 
 // 导入自定义模块
 #[path = "../serialization.rs"]
 mod serialization;
-use serialization::{Serializer, Deserializer, ByteOrder};
 
 #[path = "../controller.rs"]
 mod controller;
@@ -19,190 +15,212 @@ use controller::{Request, Response};
 mod config;
 use config::Config;
 
-/// 生成随机的请求ID
-fn gen_request_id() -> String {
-    rand::thread_rng().gen_range(0..100000000).to_string()
+#[path = "../response_cache.rs"]
+mod response_cache;
+
+#[path = "../flight_client.rs"]
+mod flight_client;
+use flight_client::{FlightClient, MonitorEnd};
+
+#[path = "../client_args.rs"]
+mod client_args;
+use client_args::parse_command;
+
+/// 脚本模式的用法说明
+const USAGE: &str = "Usage: client [--verbose] [COMMAND [--OPTION VALUE]...]
+
+Without a command the interactive menu is started. Commands:
+  query          --source S --destination D [--max-fare CENTS]
+  query-by-date  --source S --destination D --from \"YYYY-MM-DD HH:MM:SS\" --to \"YYYY-MM-DD HH:MM:SS\"
+  details        --flight-id ID
+  details-batch  --flight-ids ID[,ID...]
+  reserve        --flight-id ID --seats N [--idempotency-key KEY]
+  reserve-multi  --legs FLIGHT_ID:SEATS[,FLIGHT_ID:SEATS...]
+  cancel         --flight-id ID --seats N
+  monitor        --flight-id ID --interval SECONDS [--threshold SEATS]
+  unmonitor      --flight-id ID
+  stats
+  reset          --flight-id ID";
+
+/// 转义JSON字符串
+fn json_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
 }
 
-
-
-fn send_request_and_receive_response(map: HashMap<String, String>, socket: &UdpSocket) -> Result<HashMap<String, String>, io::Error> {
-    let config = Config::load().expect("Failed to load config");
-    let retry = config.client.retry;
-    let timeout = config.client.timeout;
-
-    let mut serializer = Serializer::new(ByteOrder::Little);
-    let timeout_duration = Duration::new(timeout.into(), 0); // 设置超时时间为10秒
-    socket.set_read_timeout(Some(timeout_duration))?;
-    let mut attempt = 0;
-
-    // println!("request_id: {:?}", map.get("request_id").unwrap());
-    // println!("timestamp: {:?}", chrono::Utc::now().timestamp());
-
-    println!("Request: {:?}", map);
-
-    serializer.serialize_map(&map)?;
-    let send_buffer = serializer.get_buffer();
-    socket.send(&send_buffer)?;
-
-    let mut received_result = None;
-
-
-    loop {
-        let start_time = Instant::now();
-        let mut buffer = [0u8; 1024];
-
-        // 设置超时
-        while start_time.elapsed() < timeout_duration {
-            match socket.recv_from(&mut buffer) {
-                Ok((amt, _)) => {
-                    let received = &buffer[..amt];
-                    let mut deserializer = Deserializer::new(received, ByteOrder::Little);
-                    let value = deserializer.deserialize_next().unwrap();
-                    let result: HashMap<String, String> = value.as_map().unwrap().iter()
-                        .map(|(k, v)| (k.to_string(), v.as_string().unwrap().to_string()))
-                        .collect();
-
-                    println!("Received: {:?}", result);
-                    received_result = Some(result);
-                    break; // 成功接收到响应，退出循环
-                }
-                Err(ref e) if e.kind() == std::io::ErrorKind::WouldBlock => {
-                    // 如果没有数据可用，继续等待
-                    continue;
-                }
-                Err(e) => {
-                    return Err(e);
-                }
-            }
+/// 把Response转换为一行JSON,并返回请求是否成功
+fn response_json(response: &Response) -> (String, bool) {
+    let status = |result: &Result<(), String>| match result {
+        Ok(()) => (r#"{"ok":true}"#.to_string(), true),
+        Err(e) => (format!(r#"{{"ok":false,"error":{}}}"#, json_string(e)), false),
+    };
+    let optional = |value: Option<String>| value.unwrap_or_else(|| "null".to_string());
+
+    match response {
+        Response::FlightIds(flight_ids) => {
+            let ids = flight_ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(",");
+            (format!(r#"{{"ok":true,"flight_ids":[{}]}}"#, ids), true)
         }
-
-        if received_result.is_some() {
-            break; // 收到响应，退出尝试循环
-        } else {
-            attempt += 1;
-            if attempt < retry {
-                println!("No response received, resending request...");
-                socket.send(&send_buffer)?; // 重新发送请求
-            }
+        Response::FlightDetails { departure_time, airfare, seats_available } => (
+            format!(
+                r#"{{"ok":true,"departure_time":{},"airfare":{},"seats_available":{}}}"#,
+                optional(departure_time.map(|time| json_string(&time.format("%Y-%m-%d %H:%M:%S").to_string()))),
+                optional(airfare.map(|airfare| airfare.to_string())),
+                optional(seats_available.map(|seats| seats.to_string())),
+            ),
+            true,
+        ),
+        Response::FlightDetailsBatch(details) => {
+            let flights = details
+                .iter()
+                .map(|(flight_id, record)| match record {
+                    Ok(record) => format!(
+                        r#"{{"flight_id":{},"departure_time":{},"airfare":{},"seats_available":{}}}"#,
+                        flight_id,
+                        json_string(&record.departure_time.format("%Y-%m-%d %H:%M:%S").to_string()),
+                        record.airfare,
+                        record.seats_available,
+                    ),
+                    Err(e) => format!(r#"{{"flight_id":{},"error":{}}}"#, flight_id, json_string(e)),
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            (format!(r#"{{"ok":true,"flights":[{}]}}"#, flights), true)
+        }
+        Response::Reservation(Ok(overbooked)) => (format!(r#"{{"ok":true,"overbooked":{}}}"#, overbooked), true),
+        Response::Reservation(Err(e)) => (format!(r#"{{"ok":false,"error":{}}}"#, json_string(e)), false),
+        Response::Cancellation(result)
+        | Response::MonitoringStarted(result)
+        | Response::MonitoringStopped(result) => status(result),
+        Response::MultiReservation(Ok(())) => (r#"{"ok":true}"#.to_string(), true),
+        Response::MultiReservation(Err((leg, e))) => (format!(r#"{{"ok":false,"failed_leg":{},"error":{}}}"#, leg, json_string(e)), false),
+        Response::MyMonitors(monitors) => {
+            let monitors = monitors
+                .iter()
+                .map(|(flight_id, remaining)| format!(r#"{{"flight_id":{},"remaining":{}}}"#, flight_id, remaining))
+                .collect::<Vec<_>>()
+                .join(",");
+            (format!(r#"{{"ok":true,"monitors":[{}]}}"#, monitors), true)
         }
+        Response::Stats(stats) => (
+            format!(
+                r#"{{"ok":true,"flights":{},"total_seats":{},"seats_sold":{},"average_fare":{}}}"#,
+                stats.flights, stats.total_seats, stats.seats_sold, stats.average_fare
+            ),
+            true,
+        ),
+        Response::FlightReset(Ok(seats_available)) => (format!(r#"{{"ok":true,"seats_available":{}}}"#, seats_available), true),
+        Response::FlightReset(Err(e)) | Response::Error(e) => (format!(r#"{{"ok":false,"error":{}}}"#, json_string(e)), false),
     }
-
-    received_result.ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "No response received after 2 attempts"))
 }
 
-/// 发送请求并处理响应
-fn send_request(request: Request, socket: &UdpSocket) -> Result<Response, io::Error> {
-    let request_id = gen_request_id();
-    let mut map = HashMap::new();
-
-    let config = Config::load().expect("Failed to load config");
-    let invocation_semantic = config.client.invocation_semantic;
-
-    println!("----------------------------------");
-    match request {
-        Request::QueryFlightIds { source, destination } => {
-            // 构建查询航班ID的请求
-            map.insert("request_id".to_string(), request_id);
-            map.insert("invocation_semantic".to_string(), invocation_semantic);
-            map.insert("action".to_string(), 1.to_string());
-            map.insert("source".to_string(), source);
-            map.insert("destination".to_string(), destination);
-
-            // 序列化并发送请求
-            let result = send_request_and_receive_response(map, socket).unwrap();
-
-            // 处理响应数据
-            if result.get("flight_ids").is_none() {
-                Ok(Response::FlightIds(vec![]))
-            } else {
-                let flight_ids = result.get("flight_ids").unwrap()
-                    .split(",").map(|s| s.parse().unwrap()).collect();
-                Ok(Response::FlightIds(flight_ids))
-            }
+/// 脚本模式:发送一次请求,以JSON打印结果,按是否成功设置退出码
+fn run_command(client: &mut FlightClient, request: Request) -> ! {
+    client.set_verbose(false);
+    match client.send_request(request) {
+        Ok(response) => {
+            let (json, ok) = response_json(&response);
+            println!("{}", json);
+            process::exit(if ok { 0 } else { 1 });
         }
-        Request::QueryFlightDetails { flight_id } => {
-            // 构建查询航班详情的请求
-            map.insert("request_id".to_string(), request_id);
-            map.insert("invocation_semantic".to_string(), invocation_semantic);
-            map.insert("action".to_string(), 2.to_string());
-            map.insert("flight_id".to_string(), flight_id.to_string());
-
-            // 序列化并发送请求
-            let result = send_request_and_receive_response(map, socket).unwrap();
-
-            // 处理响应数据
-            let status = result.get("status").unwrap();
-            if status == "200" {
-                let departure_time = NaiveDateTime::parse_from_str(
-                    result.get("departure_time").unwrap(),
-                    "%Y-%m-%d %H:%M:%S"
-                ).unwrap();
-                let airfare: f32 = result.get("airfare").unwrap().parse().unwrap();
-                let seats_available: i32 = result.get("seats_available").unwrap().parse().unwrap();
-                Ok(Response::FlightDetails {
-                    departure_time: Some(departure_time),
-                    airfare: Some(airfare),
-                    seats_available: Some(seats_available)
-                })
-            } else {
-                Ok(Response::FlightDetails {
-                    departure_time: None,
-                    airfare: None,
-                    seats_available: None
-                })
-            }
+        Err(e) => {
+            println!(r#"{{"ok":false,"error":{}}}"#, json_string(&e.to_string()));
+            process::exit(1);
         }
-        Request::ReserveSeats { flight_id, seats } => {
-            // 构建预订座位的请求
-            map.insert("request_id".to_string(), request_id);
-            map.insert("invocation_semantic".to_string(), invocation_semantic);
-            map.insert("action".to_string(), 3.to_string());
-            map.insert("flight_id".to_string(), flight_id.to_string());
-            map.insert("seats".to_string(), seats.to_string());
-
-            // 序列化并发送请求
-            let result = send_request_and_receive_response(map, socket).unwrap();
+    }
+}
 
-            // 处理响应数据
-            let status = result.get("status").unwrap();
-            if status == "200" {
-                Ok(Response::Reservation(Ok(())))
-            } else {
-                Ok(Response::Reservation(Err(result.get("message").unwrap().to_owned())))
-            }
+/// 发送请求并打印结果;请求失败时打印错误并返回None,交互循环继续运行
+fn send_and_print(client: &mut FlightClient, request: Request) -> Option<Response> {
+    match client.send_request(request) {
+        Ok(response) => {
+            println!("{}", response);
+            Some(response)
         }
-        Request::MonitorFlight { flight_id, monitor_interval } => {
-            // 构建监控航班的请求
-            map.insert("request_id".to_string(), request_id);
-            map.insert("invocation_semantic".to_string(), invocation_semantic);
-            map.insert("action".to_string(), 4.to_string());
-            map.insert("flight_id".to_string(), flight_id.to_string());
-            map.insert("monitor_interval".to_string(), monitor_interval.to_string());
+        Err(e) => {
+            println!("Error: {}", e);
+            None
+        }
+    }
+}
 
-            // 序列化并发送请求
-            let result = send_request_and_receive_response(map, socket).unwrap();
+/// 打印提示并读取一行输入,返回去掉首尾空白的内容;标准输入已关闭时返回UnexpectedEof错误
+fn prompt(message: &str) -> io::Result<String> {
+    print!("{}", message);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    if io::stdin().read_line(&mut input)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "stdin closed"));
+    }
+    Ok(input.trim().to_string())
+}
 
-            // 处理响应数据
-            let status = result.get("status").unwrap();
-            if status == "200" {
-                Ok(Response::MonitoringStarted(Ok(())))
-            } else {
-                Ok(Response::MonitoringStarted(Err(result.get("message").unwrap().to_owned())))
-            }
+/// 提示输入并用parse解析,输入不合法时打印错误并重新提示,而不是让客户端崩溃
+fn prompt_with<T>(message: &str, parse: impl Fn(&str) -> Option<T>) -> io::Result<T> {
+    loop {
+        let input = prompt(message)?;
+        match parse(&input) {
+            Some(value) => return Ok(value),
+            None => println!("Invalid input: {:?}, please try again", input),
         }
     }
 }
 
+/// 提示输入一个值,输入无法解析时重新提示
+fn prompt_parse<T: FromStr>(message: &str) -> io::Result<T> {
+    prompt_with(message, |input| input.parse().ok())
+}
+
 fn main() -> io::Result<()> {
+    // 解析命令行参数
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let verbose = args.iter().any(|arg| arg == "--verbose");
+    let args: Vec<String> = args.into_iter().filter(|arg| arg != "--verbose").collect();
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return Ok(());
+    }
+    // 带子命令时进入脚本模式,参数错误以退出码2结束
+    let command = match parse_command(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            process::exit(2);
+        }
+    };
+
     // 加载配置并创建UDP socket
-    let config = Config::load().expect("Failed to load config");
-    let socket = UdpSocket::bind("0.0.0.0:0")?;
-    
-    // println!("Local address: {:?}", socket.local_addr()?);
-    println!("Server address: {:?}", &config.server.address);
-    
-    socket.connect(&config.server.address)?;
+    let config = Config::load_or_default().expect("Failed to load config");
+    if command.is_none() {
+        println!("Server address: {:?}", &config.server.address);
+    }
+
+    let mut client = FlightClient::new(config)?;
+    if verbose {
+        eprintln!("Local address: {}", client.local_addr()?);
+        eprintln!("Resolved server address: {}", client.server_addr());
+    }
+    let rtt = client.ping()?;
+    if verbose {
+        eprintln!("Ping: {:.1}ms", rtt.as_secs_f64() * 1000.0);
+    }
+
+    if let Some(request) = command {
+        run_command(&mut client, request);
+    }
 
     // 主循环，处理用户输入和请求
     loop {
@@ -214,91 +232,96 @@ fn main() -> io::Result<()> {
         println!("  2 - query flight details");
         println!("  3 - reserve seats");
         println!("  4 - monitor flight");
+        println!("  6 - cancel reservation");
+        println!("  7 - stop monitoring flight");
+        println!("  8 - query flight ids by departure date");
+        println!("  stats - show flight capacity and occupancy");
+        println!("  monitors - list this client's active monitoring subscriptions");
+        println!("  reset - reset flight seats (admin)");
         print!("Enter command: ");
         io::stdout().flush()?;
         io::stdin().read_line(&mut input)?;
 
         let message = input.trim();
         if message == "quit" {
+            println!("RTT summary: {}", client.rtt_stats());
             break;
         } else if message == "1" {
             // 查询航班ID
-            let mut input2 = String::new();
-            print!("Enter source: ");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut input2)?;
-            let mut input3 = String::new();
-            let source = input2.trim();
-            print!("Enter destination: ");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut input3)?;
-            let destination = input3.trim();
-            let request = Request::QueryFlightIds {
-                source: source.to_string(),
-                destination: destination.to_string(),
-            };
-            let response = send_request(request, &socket)?;
-            println!("Result: {:?}", response);
+            let source = prompt("Enter source: ")?;
+            let destination = prompt("Enter destination: ")?;
+            // 留空表示不限票价
+            let max_fare = prompt_with("Enter max fare in cents (empty for no limit): ", |input| {
+                if input.is_empty() { Some(None) } else { input.parse().ok().map(Some) }
+            })?;
+            let request = Request::QueryFlightIds { source, destination, max_fare };
+            send_and_print(&mut client, request);
         } else if message == "2" {
             // 查询航班详情
-            let mut input2 = String::new();
-            print!("Enter flight id: ");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut input2)?;
-            let flight_id = input2.trim();
             let request = Request::QueryFlightDetails {
-                flight_id: flight_id.parse().unwrap(),
+                flight_id: prompt_parse("Enter flight id: ")?,
             };
-            let response = send_request(request, &socket)?;
-            println!("Result: {:?}", response);
+            send_and_print(&mut client, request);
         } else if message == "3" {
             // 预订座位
-            let mut input2 = String::new();
-            print!("Enter flight id: ");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut input2)?;
-            let flight_id = input2.trim();
-            let mut input3 = String::new();
-            print!("Enter seats: ");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut input3)?;
-            let seats = input3.trim();
             let request = Request::ReserveSeats {
-                flight_id: flight_id.parse().unwrap(),
-                seats: seats.parse().unwrap(),
+                flight_id: prompt_parse("Enter flight id: ")?,
+                seats: prompt_parse("Enter seats: ")?,
+                idempotency_key: None,
             };
-            let response = send_request(request, &socket)?;
-            println!("Result: {:?}", response);
+            send_and_print(&mut client, request);
         } else if message == "4" {
             // 监控航班
-            let mut input2 = String::new();
-            print!("Enter flight id: ");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut input2)?;
-            let flight_id = input2.trim();
-            let mut input3 = String::new();
-            print!("Enter monitor_interval: ");
-            io::stdout().flush()?;
-            io::stdin().read_line(&mut input3)?;
-            let monitor_interval = input3.trim();
-            let request = Request::MonitorFlight {
-                flight_id: flight_id.parse().unwrap(),
-                monitor_interval: monitor_interval.parse().unwrap(),
-            };
-            let response = send_request(request, &socket)?;
-            println!("Result: {:?}", response);
+            let flight_id: i32 = prompt_parse("Enter flight id: ")?;
+            let monitor_interval: i32 = prompt_parse("Enter monitor_interval: ")?;
+            let request = Request::MonitorFlight { flight_id, monitor_interval, threshold: 0 };
+            if !matches!(send_and_print(&mut client, request), Some(Response::MonitoringStarted(Ok(())))) {
+                continue;
+            }
 
-            // 持续接收监控更新
-            loop {
-                println!("Waiting for monitor update...");
-                let mut buffer = [0u8; 1024];
-                let (amt, _) = socket.recv_from(&mut buffer)?;
-                let received = &buffer[..amt];
-                let mut deserializer = Deserializer::new(received, ByteOrder::Little);
-                let value = deserializer.deserialize_next().unwrap();
-                let result = value.as_map().unwrap();
-                println!("Received: {:?}", result);
+            // 在监控期内持续接收更新,结束后自动取消订阅
+            println!("Waiting for monitor updates...");
+            match client.follow_updates(flight_id, monitor_interval, |update| println!("Received: {:?}", update)) {
+                Ok(MonitorEnd::Elapsed) => println!("Monitor interval elapsed"),
+                Ok(MonitorEnd::Expired) => println!("Monitoring expired"),
+                Ok(MonitorEnd::ServerShutdown) => println!("Server shutting down"),
+                Err(e) => println!("Error: {}", e),
             }
+        } else if message == "6" {
+            // 取消预订
+            let request = Request::CancelReservation {
+                flight_id: prompt_parse("Enter flight id: ")?,
+                seats: prompt_parse("Enter seats: ")?,
+            };
+            send_and_print(&mut client, request);
+        } else if message == "7" {
+            // 停止监控航班
+            let request = Request::Unmonitor {
+                flight_id: prompt_parse("Enter flight id: ")?,
+            };
+            send_and_print(&mut client, request);
+        } else if message == "8" {
+            // 按出发时间范围查询航班ID
+            let parse_time = |input: &str| NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M:%S").ok();
+            let request = Request::QueryFlightsByDate {
+                source: prompt("Enter source: ")?,
+                destination: prompt("Enter destination: ")?,
+                from: prompt_with("Enter earliest departure (YYYY-MM-DD HH:MM:SS): ", parse_time)?,
+                to: prompt_with("Enter latest departure (YYYY-MM-DD HH:MM:SS): ", parse_time)?,
+            };
+            send_and_print(&mut client, request);
+        } else if message == "stats" {
+            // 打印所有航班的统计摘要
+            send_and_print(&mut client, Request::Stats);
+        } else if message == "monitors" {
+            // 列出本客户端当前的监控订阅
+            send_and_print(&mut client, Request::ListMyMonitors);
+        } else if message == "reset" {
+            // 重置航班座位(管理命令)
+            let request = Request::ResetFlight {
+                flight_id: prompt_parse("Enter flight id: ")?,
+            };
+            send_and_print(&mut client, request);
         }
     }
 
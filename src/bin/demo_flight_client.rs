@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Instant;
+use chrono::NaiveDateTime;
+
+#[path = "../config.rs"]
+mod config;
+use config::Config;
+
+#[path = "../controller.rs"]
+mod controller;
+use controller::{Request, Response};
+
+#[path = "../serialization.rs"]
+mod serialization;
+use serialization::Value;
+
+#[path = "../rate_limiter.rs"]
+mod rate_limiter;
+
+#[path = "../flight_server.rs"]
+mod flight_server;
+use flight_server::Server;
+
+#[path = "../response_cache.rs"]
+mod response_cache;
+
+#[path = "../flight_client.rs"]
+mod flight_client;
+use flight_client::{parse_response, FlightClient};
+
+fn main() {
+    // Malformed responses are reported as errors instead of panicking the client
+    let response = |fields: &[(&str, Value)]| -> HashMap<String, Value> {
+        fields.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    };
+    let departure = NaiveDateTime::parse_from_str("2024-08-30 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let details = Request::QueryFlightDetails { flight_id: 0 };
+    let reserve = Request::ReserveSeats { flight_id: 0, seats: 1, idempotency_key: None };
+    let query = Request::QueryFlightIds { source: "A".to_string(), destination: "B".to_string(), max_fare: None };
+    println!("Details, 404: {:?}", parse_response(&details, &response(&[("status", "404".into()), ("message", "Flight not found".into())])));
+    println!("Details, 500: {:?}", parse_response(&details, &response(&[("status", "500".into()), ("message", "Unknown error".into())])));
+    println!("Details, no status: {:?}", parse_response(&details, &response(&[])));
+    println!("Details, missing airfare: {:?}", parse_response(&details, &response(&[
+        ("status", "200".into()), ("departure_time", Value::DateTime(departure)), ("seats_available", Value::Int32(3)),
+    ])));
+    println!("Details, departure_time as a string: {:?}", parse_response(&details, &response(&[
+        ("status", "200".into()), ("departure_time", "2024-08-30 10:00:00".into()), ("airfare", Value::Int64(100)), ("seats_available", Value::Int32(3)),
+    ])));
+    println!("Details, airfare as a string: {:?}", parse_response(&details, &response(&[
+        ("status", "200".into()), ("departure_time", Value::DateTime(departure)), ("airfare", "100".into()), ("seats_available", Value::Int32(3)),
+    ])));
+    println!("Reserve, 400 without message: {:?}", parse_response(&reserve, &response(&[("status", "400".into())])));
+    println!("Reserve, 500: {:?}", parse_response(&reserve, &response(&[("status", "500".into())])));
+    println!("Query, 404: {:?}", parse_response(&query, &response(&[("status", "404".into()), ("message", "No matching flights found".into())])));
+    println!("Query, flight ids as a string: {:?}", parse_response(&query, &response(&[("status", "200".into()), ("flight_ids", "1,2".into())])));
+
+    // Start a server on a loopback port chosen by the OS
+    let mut server_config = Config::default();
+    server_config.server.address = "127.0.0.1:0".to_string();
+    let server = Server::bind(server_config).unwrap();
+    server.controller.add_flight(controller::Flight {
+        flight_id: 0,
+        source: "New York".to_string(),
+        destination: "London".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-08-30 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 20000,
+        seats_available: 50,
+        capacity: 50,
+        overbook_limit: 0,
+    }).unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+
+    thread::scope(|s| {
+        s.spawn(|| server.run().unwrap());
+
+        // Talk to it through the client library instead of the interactive CLI
+        let client_config = || {
+            let mut config = Config::default();
+            config.server.address = server_addr.to_string();
+            config
+        };
+        let mut client = FlightClient::new(client_config()).unwrap();
+        println!("Ping: {:?}", client.ping().unwrap());
+
+        let flight_ids = client.query_flight_ids("New York", "London", None).unwrap();
+        println!("Flight ids: {:?}", flight_ids);
+        if let Response::FlightIds(ids) = flight_ids {
+            for id in ids {
+                println!("Flight {}: {:?}", id, client.query_flight_details(id).unwrap());
+            }
+        }
+        println!("Reserve 2 seats: {:?}", client.reserve_seats(0, 2).unwrap());
+        println!("Reserve 100 seats: {:?}", client.reserve_seats(0, 100).unwrap());
+        println!("Monitor: {:?}", client.monitor_flight(0, 60).unwrap());
+
+        // A reservation from another client triggers a push to the monitoring one
+        let mut other = FlightClient::new(client_config()).unwrap();
+        println!("Other client reserves 1 seat: {:?}", other.reserve_seats(0, 1).unwrap());
+        println!("Update: {:?}", client.receive_update().unwrap());
+        println!("Stop monitoring: {:?}", client.send_request(Request::Unmonitor { flight_id: 0 }).unwrap());
+
+        // A short interval ends the monitor loop cleanly and unsubscribes
+        println!("Monitor for 1s: {:?}", client.monitor_flight(0, 1).unwrap());
+        let started = Instant::now();
+        let end = client.follow_updates(0, 1, |update| println!("Update: {:?}", update)).unwrap();
+        println!("Monitor loop ended after {:?}: {:?}", started.elapsed(), end);
+
+        // With force_duplicate the second reservation reuses the first request_id,
+        // so the server replays the cached response instead of reserving again
+        let mut duplicate_config = client_config();
+        duplicate_config.client.force_duplicate = true;
+        let mut duplicating = FlightClient::new(duplicate_config).unwrap();
+        let first = duplicating.reserve_seats(0, 5).unwrap();
+        let second = duplicating.reserve_seats(0, 5).unwrap();
+        println!("Duplicated reservation: {:?} / {:?}, identical: {}", first, second, format!("{:?}", first) == format!("{:?}", second));
+        println!("Seats after duplicated reservation: {:?}", client.query_flight_details(0).unwrap());
+
+        println!("RTT summary: {}", client.rtt_stats());
+
+        shutdown.store(true, Ordering::SeqCst);
+    });
+}
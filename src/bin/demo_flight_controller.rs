@@ -6,11 +6,14 @@ use chrono::NaiveDateTime;
 mod controller;
 use controller::FlightController;
 
+#[path = "../serialization.rs"]
+mod serialization;
+
 
 fn main() {
     let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
 
-    let mut controller = FlightController::new();
+    let controller = FlightController::new();
 
     // Add some sample flights
     let flight0 = controller::Flight {
@@ -18,30 +21,36 @@ fn main() {
         source: "New York".to_string(),
         destination: "London".to_string(),
         departure_time: NaiveDateTime::parse_from_str("2024-08-30 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
-        airfare: 200.0,
+        airfare: 20000,
         seats_available: 50,
+        capacity: 50,
+        overbook_limit: 0,
     };
-    controller.add_flight(flight0);
+    controller.add_flight(flight0).unwrap();
 
     let flight1 = controller::Flight {
         flight_id: 1,
         source: "New York".to_string(),
         destination: "London".to_string(),
         departure_time: NaiveDateTime::parse_from_str("2024-09-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
-        airfare: 500.0,
+        airfare: 50000,
         seats_available: 100,
+        capacity: 100,
+        overbook_limit: 0,
     };
-    controller.add_flight(flight1);
+    controller.add_flight(flight1).unwrap();
 
     let flight2 = controller::Flight {
         flight_id: 2,
         source: "London".to_string(),
         destination: "Paris".to_string(),
         departure_time: NaiveDateTime::parse_from_str("2024-09-02 14:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
-        airfare: 300.0,
+        airfare: 30000,
         seats_available: 150,
+        capacity: 150,
+        overbook_limit: 0,
     };
-    controller.add_flight(flight2);
+    controller.add_flight(flight2).unwrap();
 
     // Simulate client address
     let client_addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
@@ -50,25 +59,63 @@ fn main() {
     let request = controller::Request::QueryFlightIds {
         source: "New York".to_string(),
         destination: "London".to_string(),
+        max_fare: None,
     };
     let response = controller.handle_request(request, &socket, Some(client_addr));
     println!("QueryFlightIds (New York->London) response: {:?}", response);
 
+    // Test QueryFlightIds with a fare ceiling: flight 0 costs exactly 20000 cents
+    let request = controller::Request::QueryFlightIds {
+        source: "New York".to_string(),
+        destination: "London".to_string(),
+        max_fare: Some(20000),
+    };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("QueryFlightIds (New York->London, max_fare: 20000) response: {:?}", response);
+
+    let request = controller::Request::QueryFlightIds {
+        source: "New York".to_string(),
+        destination: "London".to_string(),
+        max_fare: Some(19999),
+    };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("QueryFlightIds (New York->London, max_fare: 19999) response: {:?}", response);
+
     let request = controller::Request::QueryFlightIds {
         source: "London".to_string(),
         destination: "Paris".to_string(),
+        max_fare: None,
     };
     let response = controller.handle_request(request, &socket, Some(client_addr) );
     println!("QueryFlightIds (London->Paris) response: {:?}", response);
 
 
+    // Test QueryFlightsByDate: both endpoints are inclusive
+    let request = controller::Request::QueryFlightsByDate {
+        source: "New York".to_string(),
+        destination: "London".to_string(),
+        from: NaiveDateTime::parse_from_str("2024-08-30 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        to: NaiveDateTime::parse_from_str("2024-09-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+    };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("QueryFlightsByDate (New York -> London, 2024-08-30 10:00 to 2024-09-01 10:00) response: {:?}", response);
+
+    let request = controller::Request::QueryFlightsByDate {
+        source: "New York".to_string(),
+        destination: "London".to_string(),
+        from: NaiveDateTime::parse_from_str("2024-08-30 10:00:01", "%Y-%m-%d %H:%M:%S").unwrap(),
+        to: NaiveDateTime::parse_from_str("2024-09-01 09:59:59", "%Y-%m-%d %H:%M:%S").unwrap(),
+    };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("QueryFlightsByDate (New York -> London, 2024-08-30 10:00:01 to 2024-09-01 09:59:59) response: {:?}", response);
+
     // Test QueryFlightDetails
     let request = controller::Request::QueryFlightDetails { flight_id: 1 };
     let response = controller.handle_request(request, &socket, Some(client_addr));
     println!("QueryFlightDetails (flight_id: 1) response: {:?}", response);
 
     // Test ReserveSeats
-    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2 };
+    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2, idempotency_key: None };
     let response = controller.handle_request(request, &socket, Some(client_addr) );
     println!("ReserveSeats (flight_id: 1, seats: 2) response: {:?}", response);
 
@@ -78,18 +125,18 @@ fn main() {
     println!("QueryFlightDetails (flight_id: 1) response: {:?}", response);
 
         // Test ReserveSeats
-    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 999 };
+    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 999, idempotency_key: None };
     let response = controller.handle_request(request, &socket, Some(client_addr)     );
     println!("ReserveSeats (flight_id: 1, seats: 999) response: {:?}", response);
 
 
     // Test MonitorFlight
-    let request = controller::Request::MonitorFlight { flight_id: 1, monitor_interval: 1 };
+    let request = controller::Request::MonitorFlight { flight_id: 1, monitor_interval: 1, threshold: 0 };
     let response = controller.handle_request(request, &socket, Some(client_addr));
     println!("MonitorFlight (flight_id: 1, monitor_interval: 60) response: {:?}", response);
 
         // Test ReserveSeats
-    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2 };
+    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2, idempotency_key: None };
     let response = controller.handle_request(request, &socket, Some(client_addr));
     println!("ReserveSeats (flight_id: 1, seats: 2) response: {:?}", response);
 
@@ -97,7 +144,7 @@ fn main() {
     std::thread::sleep(std::time::Duration::from_millis(600));
 
     // Test ReserveSeats
-    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2 };
+    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2, idempotency_key: None };
     let response = controller.handle_request(request, &socket, Some(client_addr));
     println!("ReserveSeats (flight_id: 1, seats: 2) response: {:?}", response);
     
@@ -105,15 +152,151 @@ fn main() {
     std::thread::sleep(std::time::Duration::from_millis(600));
 
     // Test ReserveSeats
-    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2 };
+    let request = controller::Request::ReserveSeats { flight_id: 1, seats: 2, idempotency_key: None };
     let response = controller.handle_request(request, &socket, Some(client_addr) );
     println!("ReserveSeats (flight_id: 1, seats: 2) response: {:?}", response);
+
+    // The server sweeps expired monitors periodically; do it once here so the client gets its expiry message
+    controller.clean_expired_monitors(&socket);
     
+    // Test MonitorFlight with invalid intervals
+    for monitor_interval in [-1, 0, 86401, i32::MAX] {
+        let request = controller::Request::MonitorFlight { flight_id: 1, monitor_interval, threshold: 0 };
+        let response = controller.handle_request(request, &socket, Some(client_addr));
+        println!("MonitorFlight (flight_id: 1, monitor_interval: {}) response: {:?}", monitor_interval, response);
+    }
+
+    // Subscribe to flight 1 again
+    let request = controller::Request::MonitorFlight { flight_id: 1, monitor_interval: 60, threshold: 0 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("MonitorFlight (flight_id: 1, monitor_interval: 60) response: {:?}", response);
+
+    // Test CancelReservation notifies the subscribed client
+    let request = controller::Request::CancelReservation { flight_id: 1, seats: 1 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("CancelReservation (flight_id: 1, seats: 1) response: {:?}", response);
+
+    // Test Unmonitor: the following cancellation should no longer trigger a callback
+    let request = controller::Request::Unmonitor { flight_id: 1 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("Unmonitor (flight_id: 1) response: {:?}", response);
+
+    // Test CancelReservation
+    let request = controller::Request::CancelReservation { flight_id: 1, seats: 2 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("CancelReservation (flight_id: 1, seats: 2) response: {:?}", response);
 
+    // Test ReserveSeats with a negative seat count
+    let request = controller::Request::ReserveSeats { flight_id: 1, seats: -5, idempotency_key: None };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("ReserveSeats (flight_id: 1, seats: -5) response: {:?}", response);
+    if let Some(flight) = controller.flights().get(&1) {
+        println!("Flight 1 seats sold: {} of {}", flight.seats_sold(), flight.capacity);
+    }
+
+    // Test CancelReservation with more seats than were reserved
+    let request = controller::Request::CancelReservation { flight_id: 1, seats: 999 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("CancelReservation (flight_id: 1, seats: 999) response: {:?}", response);
+
+    // Test UpdateFlight: subscribers receive the new airfare and departure time
+    let request = controller::Request::MonitorFlight { flight_id: 0, monitor_interval: 60, threshold: 0 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("MonitorFlight (flight_id: 0, monitor_interval: 60) response: {:?}", response);
+    let new_departure = NaiveDateTime::parse_from_str("2024-08-30 12:30:00", "%Y-%m-%d %H:%M:%S").unwrap();
+    let result = controller.update_flight(0, Some(25000), Some(new_departure), &socket);
+    println!("UpdateFlight (flight_id: 0, airfare: 25000, departure: {}) result: {:?}", new_departure, result);
+    println!("UpdateFlight (flight_id: 42) result: {:?}", controller.update_flight(42, Some(100), None, &socket));
+
+    // Test AddFlight with a duplicate flight_id
+    let duplicate = controller::Flight {
+        flight_id: 0,
+        source: "Tokyo".to_string(),
+        destination: "Seoul".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-09-03 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 15000,
+        seats_available: 80,
+        capacity: 80,
+        overbook_limit: 0,
+    };
+    println!("AddFlight (flight_id: 0) again result: {:?}", controller.add_flight(duplicate));
+
+    // Test RemoveFlight: a monitored flight is removed and can no longer be queried
+    let request = controller::Request::MonitorFlight { flight_id: 2, monitor_interval: 60, threshold: 0 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("MonitorFlight (flight_id: 2, monitor_interval: 60) response: {:?}", response);
+    let removed = controller.remove_flight_and_notify(2, &socket);
+    println!("RemoveFlight (flight_id: 2) removed: {:?}", removed.map(|flight| flight.flight_id));
+    let request = controller::Request::QueryFlightDetails { flight_id: 2 };
+    let response = controller.handle_request(request, &socket, Some(client_addr));
+    println!("QueryFlightDetails (flight_id: 2) response: {:?}", response);
+    println!("RemoveFlight (flight_id: 2) again removed: {:?}", controller.remove_flight(2).map(|flight| flight.flight_id));
+
+    // Test concurrent ReserveSeats: 8 threads compete for 50 seats, none may be oversold
+    let flight3 = controller::Flight {
+        flight_id: 3,
+        source: "Paris".to_string(),
+        destination: "Berlin".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-09-04 09:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 12000,
+        seats_available: 50,
+        capacity: 50,
+        overbook_limit: 0,
+    };
+    controller.add_flight(flight3).unwrap();
+    let reserved: i32 = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                scope.spawn(|| {
+                    (0..10)
+                        .filter(|_| {
+                            let request = controller::Request::ReserveSeats { flight_id: 3, seats: 1, idempotency_key: None };
+                            matches!(controller.handle_request(request, &socket, None), controller::Response::Reservation(Ok(0)))
+                        })
+                        .count() as i32
+                })
+            })
+            .collect();
+        workers.into_iter().map(|worker| worker.join().unwrap()).sum()
+    });
+    println!("Concurrent ReserveSeats (flight_id: 3): {} seats reserved, {} available", reserved, controller.flights()[&3].seats_available);
+
+    // Test ReserveSeats with an idempotency key: repeating the key reserves only once
+    for attempt in 1..=3 {
+        let request = controller::Request::ReserveSeats { flight_id: 0, seats: 4, idempotency_key: Some("booking-42".to_string()) };
+        let response = controller.handle_request(request, &socket, None);
+        println!("ReserveSeats (flight_id: 0, seats: 4, key: booking-42) attempt {} response: {:?}, {} available", attempt, response, controller.flights()[&0].seats_available);
+    }
+    let request = controller::Request::ReserveSeats { flight_id: 0, seats: 4, idempotency_key: Some("booking-43".to_string()) };
+    let response = controller.handle_request(request, &socket, None);
+    println!("ReserveSeats (flight_id: 0, seats: 4, key: booking-43) response: {:?}, {} available", response, controller.flights()[&0].seats_available);
+
+    // Test idempotency key eviction: after the TTL the same key reserves again
+    let mut short_lived = FlightController::new();
+    short_lived.set_idempotency_ttl(std::time::Duration::from_millis(50));
+    short_lived.add_flight(controller::Flight {
+        flight_id: 9,
+        source: "Rome".to_string(),
+        destination: "Madrid".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-09-05 07:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 9000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+    for wait in [0, 0, 100] {
+        std::thread::sleep(std::time::Duration::from_millis(wait));
+        let request = controller::Request::ReserveSeats { flight_id: 9, seats: 1, idempotency_key: Some("booking-7".to_string()) };
+        short_lived.handle_request(request, &socket, None);
+        println!("ReserveSeats (flight_id: 9, key: booking-7) after {}ms: {} available", wait, short_lived.flights()[&9].seats_available);
+    }
 
     // Print final state of flights
     println!("Final state of flights:");
-    for (id, flight) in controller.flights() {
-        println!("Flight {}: {:?}", id, flight);
+    for flight in controller.flights().values() {
+        println!("{}", flight);
     }
-}
\ No newline at end of file
+    if let Some(flight) = controller.flights().get(&1) {
+        println!("{}", flight.detailed());
+    };
+}
@@ -1,9 +1,19 @@
 use std::collections::HashMap;
 #[path = "../serialization.rs"]
 mod serialization;
-use serialization::{Serializer, Deserializer, ByteOrder};
+use serialization::{Serializer, Serialize, Deserializer, ByteOrder, DataType, EnumRegistry, Envelope, InvocationSemantic, SerializationError, Value};
 
 
+/// 零大小的元素,用于在不分配内存的情况下构造超长数组
+#[derive(Clone)]
+struct Empty;
+
+impl Serialize for Empty {
+    fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        serializer.serialize_null()
+    }
+}
+
 fn main() -> std::io::Result<()> {
     let mut serializer = Serializer::new(ByteOrder::Little);
 
@@ -48,9 +58,154 @@ fn main() -> std::io::Result<()> {
     // 反序列化
     let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
 
-    while let Ok(value) = deserializer.deserialize_next() {
+    while let Some(value) = deserializer.try_next()? {
         println!("Deserialized value: {}", value);
     }
 
+    // 读到缓冲区末尾时try_next返回None,值被截断时返回错误而不是当作结束
+    let truncated = &buffer[..buffer.len() - 1];
+    let mut deserializer = Deserializer::new(truncated, ByteOrder::Little);
+    let result = loop {
+        match deserializer.try_next() {
+            Ok(Some(_)) => continue,
+            other => break other,
+        }
+    };
+    assert!(matches!(result, Err(SerializationError::UnexpectedEof)), "{:?}", result);
+    println!("Truncated buffer: {:?}", result);
+
+    // 比较定长和变长(varint)整数编码下,典型航班ID响应的大小
+    let mut response = HashMap::new();
+    response.insert("status".to_string(), 200);
+    response.insert("flight_id".to_string(), 1);
+    response.insert("seats_available".to_string(), 92);
+
+    let mut fixed = Serializer::new(ByteOrder::Little);
+    fixed.serialize_map(&response)?;
+    let mut varint = Serializer::new_varint(ByteOrder::Little);
+    varint.serialize_map(&response)?;
+    let varint_buffer = varint.get_buffer();
+    println!("Fixed-width size: {} bytes, varint size: {} bytes", fixed.get_buffer().len(), varint_buffer.len());
+    println!("Varint decoded: {}", Deserializer::new(&varint_buffer, ByteOrder::Little).deserialize_next()?);
+
+    // 大端序编码:数字按大端写入,用相同字节序解码得到原值,用小端序解码会失败而不是得到错误的数字
+    let mut big = Serializer::new(ByteOrder::Big);
+    big.serialize_map(&response)?;
+    let big_buffer = big.get_buffer();
+    println!("Big-endian decoded: {}", Deserializer::new(&big_buffer, ByteOrder::Big).deserialize_next()?);
+    println!("Big-endian decoded as little-endian: {:?}", Deserializer::new(&big_buffer, ByteOrder::Little).deserialize_next());
+
+    // 类型化数组解码:元素类型一致时直接得到Vec<i32>,中途出现其他类型时报错
+    let mut typed = Serializer::new(ByteOrder::Little);
+    typed.serialize_array(&[3, 1, 4])?;
+    let typed_buffer = typed.get_buffer();
+    let ids = Deserializer::new(&typed_buffer, ByteOrder::Little).deserialize_int32_array()?;
+    assert_eq!(ids, vec![3, 1, 4]);
+    println!("Int32 array: {:?}", ids);
+
+    // 直接序列化迭代器,不需要先收集成Vec;结果与序列化同样元素的切片相同
+    let mut from_iter = Serializer::new(ByteOrder::Little);
+    from_iter.serialize_iter((0..5).map(|i| i * 10))?;
+    let from_iter_buffer = from_iter.get_buffer();
+    let mut from_slice = Serializer::new(ByteOrder::Little);
+    from_slice.serialize_array(&[0, 10, 20, 30, 40])?;
+    assert_eq!(from_iter_buffer, from_slice.get_buffer());
+    println!("Iterator array: {}", Deserializer::new(&from_iter_buffer, ByteOrder::Little).deserialize_next()?);
+
+    let mut mixed = Serializer::new(ByteOrder::Little);
+    mixed.serialize_array(&[Value::Int32(3), Value::String("one".to_string()), Value::Int32(4)])?;
+    let mixed_buffer = mixed.get_buffer();
+    let mismatch = Deserializer::new(&mixed_buffer, ByteOrder::Little).deserialize_int32_array();
+    assert!(matches!(mismatch, Err(SerializationError::UnexpectedType { expected: DataType::Int32, found: DataType::String })), "{:?}", mismatch);
+    println!("Int32 array with a string in the middle: {:?}", mismatch);
+
+    // 混合类型的响应映射:字符串状态码、浮点票价和整数座位数,解码后与原值相等
+    let mut typed_response = HashMap::new();
+    typed_response.insert("status".to_string(), Value::String("200".to_string()));
+    typed_response.insert("airfare".to_string(), Value::Float(199.5));
+    typed_response.insert("seats_available".to_string(), Value::Int32(42));
+    let mut value_map = Serializer::new(ByteOrder::Little);
+    value_map.serialize_value_map(&typed_response)?;
+    let value_map_buffer = value_map.get_buffer();
+    let decoded = Deserializer::new(&value_map_buffer, ByteOrder::Little).deserialize_next()?;
+    assert_eq!(decoded, Value::Map(typed_response));
+    println!("Value map decoded: {}", decoded);
+
+    // 动态构建的Value直接写入,不需要引入Serialize trait;嵌套的数组和映射解码后与原值相等
+    let mut built = HashMap::new();
+    built.insert("status".to_string(), Value::String("200".to_string()));
+    built.insert("flight_ids".to_string(), Value::Array(vec![Value::Int32(1), Value::Int32(2)]));
+    built.insert("stats".to_string(), Value::Map(HashMap::from([("flights".to_string(), Value::Int32(2))])));
+    let built = Value::Map(built);
+    let mut single_value = Serializer::new(ByteOrder::Big);
+    single_value.serialize_value(&built)?;
+    let single_value_buffer = single_value.get_buffer();
+    let decoded = Deserializer::new(&single_value_buffer, ByteOrder::Big).deserialize_next()?;
+    assert_eq!(decoded, built);
+    println!("Value decoded: {}", decoded);
+
+    // 长度超过i32::MAX的数组在写入前被拒绝,而不是写出负数长度;零大小的元素不占内存,可以构造这样的数组
+    let mut oversized = Serializer::new(ByteOrder::Little);
+    let huge = vec![Empty; i32::MAX as usize + 1];
+    let error = oversized.serialize_array(&huge).unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    assert!(oversized.buffer().is_empty(), "nothing should be written for an oversized array");
+    println!("Oversized array: {}", error);
+
+    // 枚举:状态码只写一个字节的判别值,解码时按注册表还原为标签,未定义的判别值报错
+    const STATUS: EnumRegistry = EnumRegistry::new("status", &[(0, "200"), (1, "400"), (2, "404"), (3, "500")]);
+    let mut status = Serializer::new(ByteOrder::Little);
+    status.serialize_enum_label(&STATUS, "404")?;
+    status.serialize_enum(7)?;
+    let status_buffer = status.get_buffer();
+    let mut string_status = Serializer::new(ByteOrder::Little);
+    string_status.serialize_string("404")?;
+    println!("Enum status size: {} bytes, as a string: {} bytes", status_buffer.len() / 2, string_status.get_buffer().len());
+    let mut deserializer = Deserializer::new(&status_buffer, ByteOrder::Little);
+    assert_eq!(deserializer.deserialize_enum_label(&STATUS)?, "404");
+    let unknown = deserializer.deserialize_enum_label(&STATUS);
+    assert!(matches!(unknown, Err(SerializationError::UnknownEnum { name: "status", discriminant: 7 })), "{:?}", unknown);
+    assert_eq!(Deserializer::new(&status_buffer, ByteOrder::Little).deserialize_next()?, Value::Enum(2));
+    assert!(Serializer::new(ByteOrder::Little).serialize_enum_label(&STATUS, "418").is_err());
+    println!("Enum status round trip: 404, unknown discriminant: {}", unknown.unwrap_err());
+
+    // 字符串中的非法UTF-8:错误中带有字符串在缓冲区中的起始位置和第一个非法字节的偏移
+    let mut corrupt = Serializer::new(ByteOrder::Little);
+    corrupt.serialize_string("ab\u{e9}d")?;
+    let mut corrupt_buffer = corrupt.get_buffer();
+    let start = corrupt_buffer.len() - 5;
+    // "é"编码为0xc3 0xa9,把续字节改成'('后就不再是合法的UTF-8
+    corrupt_buffer[start + 3] = b'(';
+    let error = Deserializer::new(&corrupt_buffer, ByteOrder::Little).deserialize_next().unwrap_err();
+    assert!(matches!(error, SerializationError::InvalidUtf8 { position, valid_up_to: 2 } if position == start), "{:?}", error);
+    println!("Invalid UTF-8: {}", error);
+
+    // 请求头与请求体:定长的请求头写在map之前,读出请求头后游标停在请求体开头
+    let envelope = Envelope { request_id: 0x0123_4567_89ab_cdef, invocation_semantic: InvocationSemantic::AtLeastOnce.into(), action: 3 };
+    let mut payload = HashMap::new();
+    payload.insert("flight_id".to_string(), "1".to_string());
+    payload.insert("seats".to_string(), "2".to_string());
+    let mut request = Serializer::new(ByteOrder::Big);
+    envelope.serialize(&mut request)?;
+    request.serialize_map(&payload)?;
+    let request_buffer = request.get_buffer();
+    let mut deserializer = Deserializer::new(&request_buffer, ByteOrder::Big);
+    assert_eq!(Envelope::deserialize(&mut deserializer)?, envelope);
+    assert_eq!(deserializer.position(), Envelope::LEN as u64);
+    let decoded_payload = deserializer.deserialize_next()?;
+    assert_eq!(decoded_payload, Value::Map(payload.into_iter().map(|(k, v)| (k, Value::String(v))).collect()));
+    println!("Envelope: {:?}, payload: {}", envelope, decoded_payload);
+
+    // 请求头带有字节序标志:按另一种字节序读取时直接报错,而不是得到错乱的request_id
+    let mut little = Serializer::new(ByteOrder::Little);
+    envelope.serialize(&mut little)?;
+    assert_eq!(little.byte_order(), ByteOrder::Little);
+    let little_buffer = little.get_buffer();
+    let mut as_big = Deserializer::new(&little_buffer, ByteOrder::Big);
+    assert_eq!(as_big.byte_order(), ByteOrder::Big);
+    let mismatch = Envelope::deserialize(&mut as_big);
+    assert!(matches!(mismatch, Err(SerializationError::ByteOrderMismatch { expected: ByteOrder::Big, found: ByteOrder::Little })), "{:?}", mismatch);
+    println!("Little-endian envelope read as big-endian: {}", mismatch.unwrap_err());
+
     Ok(())
 }
\ No newline at end of file
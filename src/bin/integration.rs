@@ -0,0 +1,770 @@
+//! End-to-end check of the request/response path over real UDP sockets.
+//!
+//! First feeds crafted packets straight to `Server::handle_datagram`, then binds a server on an
+//! ephemeral port in a background thread, points `FlightClient`s at it and asserts on the
+//! responses. Exits non-zero on the first failed check:
+//!
+//! ```text
+//! cargo run --bin integration
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use chrono::{NaiveDateTime, Utc};
+
+#[path = "../config.rs"]
+mod config;
+use config::Config;
+
+#[path = "../controller.rs"]
+mod controller;
+use controller::{FlightController, FlightStats, FlightUpdate, Request, Response, UpdateSink};
+
+#[path = "../serialization.rs"]
+mod serialization;
+use serialization::{ByteOrder, Deserializer, Envelope, InvocationSemantic, Serializer, Value};
+
+#[path = "../rate_limiter.rs"]
+mod rate_limiter;
+
+#[path = "../flight_server.rs"]
+mod flight_server;
+use flight_server::Server;
+
+#[path = "../response_cache.rs"]
+mod response_cache;
+
+#[path = "../seed.rs"]
+mod seed;
+
+#[path = "../flight_client.rs"]
+mod flight_client;
+use flight_client::{action_timeout, FlightClient};
+
+fn client_for(port: u16) -> FlightClient {
+    let mut config = Config::default();
+    config.server.address = SocketAddr::from(([127, 0, 0, 1], port)).to_string();
+    let mut client = FlightClient::new(config).unwrap();
+    client.set_verbose(false);
+    client
+}
+
+/// Encodes a request as the client does: an envelope followed by the payload map
+fn request_packet(request_id: u64, invocation_semantic: impl Into<u8>, action: u8, fields: &[(&str, &str)]) -> Vec<u8> {
+    let payload: HashMap<String, String> = fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+    let mut serializer = Serializer::new(ByteOrder::Little);
+    Envelope { request_id, invocation_semantic: invocation_semantic.into(), action }.serialize(&mut serializer).unwrap();
+    serializer.serialize_map(&payload).unwrap();
+    serializer.get_buffer()
+}
+
+/// Feeds a crafted ReserveSeats packet to `Server::handle_datagram` without any network traffic
+fn check_handle_datagram() {
+    let controller = FlightController::new();
+    controller.add_flight(controller::Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = Server::from_socket(socket, controller, Config::default()).unwrap();
+    let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+
+    let packet = request_packet(1001, InvocationSemantic::AtMostOnce, 3, &[("flight_id", "1"), ("seats", "4")]);
+    let response = server.handle_datagram(&packet, src).unwrap().expect("reserve should be answered");
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    let field = |key: &str| decoded.as_map().unwrap().get(key).and_then(|value| value.as_string()).cloned();
+    assert_eq!(field("status").as_deref(), Some("200"), "reserve: {:?}", decoded);
+    assert_eq!(field("request_id").as_deref(), Some("1001"), "reserve: {:?}", decoded);
+
+    // The retransmission is answered from the dedup store and does not reserve again
+    let replayed = server.handle_datagram(&packet, src).unwrap().expect("retransmission should be answered");
+    assert_eq!(replayed, response);
+    assert_eq!(server.controller.flights()[&1].seats_available, 6);
+
+    // A replay is found from the envelope alone, even if the payload that follows is garbage
+    let mut truncated = packet[..Envelope::LEN].to_vec();
+    truncated.push(0xff);
+    assert_eq!(server.handle_datagram(&truncated, src).unwrap(), Some(response));
+
+    // Flight ids arrive as a typed Int32 array rather than a comma-joined string
+    let packet = request_packet(1002, InvocationSemantic::AtMostOnce, 1, &[("source", "Singapore"), ("destination", "Tokyo")]);
+    let response = server.handle_datagram(&packet, src).unwrap().expect("query should be answered");
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    assert_eq!(decoded.as_map().unwrap().get("flight_ids"), Some(&Value::Array(vec![Value::Int32(1)])), "query: {:?}", decoded);
+
+    // Flight details keep their types on the wire: DateTime, Int64 cents and Int32 seats
+    let packet = request_packet(1003, InvocationSemantic::AtMostOnce, 2, &[("flight_id", "1")]);
+    let response = server.handle_datagram(&packet, src).unwrap().expect("details should be answered");
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    let details = decoded.as_map().unwrap();
+    assert_eq!(details.get("status"), Some(&Value::String("200".to_string())), "details: {:?}", decoded);
+    assert_eq!(details.get("airfare"), Some(&Value::Int64(45000)), "details: {:?}", decoded);
+    assert_eq!(details.get("seats_available"), Some(&Value::Int32(6)), "details: {:?}", decoded);
+    assert_eq!(details.get("departure_time").and_then(|time| time.as_datetime()), Some(server.controller.flights()[&1].departure_time), "details: {:?}", decoded);
+
+    // Malformed packets are errors, not panics
+    assert!(server.handle_datagram(&[0xff, 0x00], src).is_err());
+}
+
+/// A batch details query answers every requested ID, with a per-ID error for unknown flights
+fn check_details_batch() {
+    let controller = FlightController::new();
+    for (flight_id, seats_available) in [(1, 10), (2, 4)] {
+        controller.add_flight(controller::Flight {
+            flight_id,
+            source: "Singapore".to_string(),
+            destination: "Tokyo".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 45000,
+            seats_available,
+            capacity: 10,
+            overbook_limit: 0,
+        }).unwrap();
+    }
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = Server::from_socket(socket, controller, Config::default()).unwrap();
+    let src: SocketAddr = "127.0.0.1:40005".parse().unwrap();
+
+    let packet = request_packet(7001, InvocationSemantic::AtLeastOnce, 13, &[("flight_ids", "2,99,1")]);
+    let response = server.handle_datagram(&packet, src).unwrap().expect("batch should be answered");
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    let fields = decoded.as_map().unwrap();
+    assert_eq!(fields.get("status"), Some(&Value::String("200".to_string())), "batch: {:?}", decoded);
+    let flights = fields.get("flights").and_then(|flights| flights.as_array()).expect("batch should carry flights");
+    let summary: Vec<_> = flights
+        .iter()
+        .map(|entry| {
+            let entry = entry.as_map().unwrap();
+            (entry["flight_id"].as_i32().unwrap(), entry["status"].as_string().unwrap().clone(), entry.get("seats_available").and_then(|seats| seats.as_i32()))
+        })
+        .collect();
+    assert_eq!(summary, vec![(2, "200".to_string(), Some(4)), (99, "404".to_string(), None), (1, "200".to_string(), Some(10))]);
+
+    assert_eq!(status_of(&server, 7002, 13, &[("flight_ids", "1,x")]), "400");
+}
+
+/// Stats add up capacity, sold seats and fares across all flights
+fn check_stats() {
+    let controller = FlightController::new();
+    assert_eq!(controller.stats(), FlightStats::default());
+    for (flight_id, capacity, airfare) in [(1, 100, 20000), (2, 50, 45001)] {
+        controller.add_flight(controller::Flight {
+            flight_id,
+            source: "Singapore".to_string(),
+            destination: "Tokyo".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare,
+            seats_available: capacity,
+            capacity,
+            overbook_limit: 0,
+        }).unwrap();
+    }
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    controller.handle_request(Request::ReserveSeats { flight_id: 1, seats: 30, idempotency_key: None }, &socket, None);
+    controller.handle_request(Request::ReserveSeats { flight_id: 2, seats: 5, idempotency_key: None }, &socket, None);
+
+    let stats = controller.stats();
+    assert_eq!(stats, FlightStats { flights: 2, total_seats: 150, seats_sold: 35, average_fare: 32500 });
+    assert_eq!(stats.to_string(), "2 flights, 35 of 150 seats sold (23.3%), average fare $325.00");
+}
+
+/// Sends one crafted request through `Server::handle_datagram` and returns the status code
+fn status_of(server: &Server, request_id: u64, action: u8, fields: &[(&str, &str)]) -> String {
+    let packet = request_packet(request_id, InvocationSemantic::AtLeastOnce, action, fields);
+    let src: SocketAddr = "127.0.0.1:40001".parse().unwrap();
+    let response = server.handle_datagram(&packet, src).unwrap().unwrap();
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    decoded.as_map().unwrap()["status"].as_string().unwrap().clone()
+}
+
+/// A ping is answered with 200 and the server clock, without touching any flight
+fn check_ping() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = Server::from_socket(socket, FlightController::new(), Config::default()).unwrap();
+    let packet = request_packet(2001, InvocationSemantic::AtMostOnce, 0, &[]);
+    let src: SocketAddr = "127.0.0.1:40002".parse().unwrap();
+
+    let before = Utc::now().timestamp();
+    let response = server.handle_datagram(&packet, src).unwrap().expect("ping should be answered");
+    let after = Utc::now().timestamp();
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    let field = |key: &str| decoded.as_map().unwrap().get(key).and_then(|value| value.as_string()).cloned();
+    assert_eq!(field("status").as_deref(), Some("200"), "ping: {:?}", decoded);
+    let server_time = decoded.as_map().unwrap().get("server_time").and_then(|time| time.as_i64()).expect("ping should carry server_time");
+    assert!((before..=after).contains(&server_time), "server_time {} outside {}..={}", server_time, before, after);
+}
+
+/// Each handler answers 404 for unknown flights and 400 for bad input
+fn check_status_codes() {
+    let controller = FlightController::new();
+    controller.add_flight(controller::Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = Server::from_socket(socket, controller, Config::default()).unwrap();
+
+    let cases: &[(&str, u8, &[(&str, &str)], &str)] = &[
+        ("query", 1, &[("source", "Singapore"), ("destination", "Tokyo")], "200"),
+        ("query, no match", 1, &[("source", "Singapore"), ("destination", "Paris")], "404"),
+        ("query, missing destination", 1, &[("source", "Singapore")], "400"),
+        ("details", 2, &[("flight_id", "1")], "200"),
+        ("details, unknown flight", 2, &[("flight_id", "99")], "404"),
+        ("details, invalid flight_id", 2, &[("flight_id", "abc")], "400"),
+        ("reserve", 3, &[("flight_id", "1"), ("seats", "1")], "200"),
+        ("reserve, unknown flight", 3, &[("flight_id", "99"), ("seats", "1")], "404"),
+        ("reserve, too many seats", 3, &[("flight_id", "1"), ("seats", "100")], "400"),
+        ("reserve, missing seats", 3, &[("flight_id", "1")], "400"),
+        ("monitor", 4, &[("flight_id", "1"), ("monitor_interval", "10")], "200"),
+        ("monitor, unknown flight", 4, &[("flight_id", "99"), ("monitor_interval", "10")], "404"),
+        ("monitor, invalid interval", 4, &[("flight_id", "1"), ("monitor_interval", "0")], "400"),
+        ("unknown action", 200, &[], "400"),
+    ];
+    for (i, (name, action, fields, expected)) in cases.iter().enumerate() {
+        assert_eq!(status_of(&server, 3000 + i as u64, *action, fields), *expected, "{}", name);
+    }
+}
+
+/// A multi-flight reservation whose second leg is full reserves nothing and reports that leg
+fn check_reserve_multi() {
+    let controller = FlightController::new();
+    for (flight_id, seats_available) in [(1, 10), (2, 0)] {
+        controller.add_flight(controller::Flight {
+            flight_id,
+            source: "Singapore".to_string(),
+            destination: "Tokyo".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 45000,
+            seats_available,
+            capacity: 10,
+            overbook_limit: 0,
+        }).unwrap();
+    }
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = Server::from_socket(socket, controller, Config::default()).unwrap();
+    let src: SocketAddr = "127.0.0.1:40004".parse().unwrap();
+
+    let packet = request_packet(6001, InvocationSemantic::AtLeastOnce, 12, &[("legs", "1:2,2:1")]);
+    let response = server.handle_datagram(&packet, src).unwrap().expect("reserve_multi should be answered");
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    let fields = decoded.as_map().unwrap();
+    assert_eq!(fields.get("status"), Some(&Value::String("400".to_string())), "second leg full: {:?}", decoded);
+    assert_eq!(fields.get("failed_leg"), Some(&Value::Int32(1)), "second leg full: {:?}", decoded);
+    assert_eq!(server.controller.flights()[&1].seats_available, 10);
+
+    // The same flight in two legs needs seats for both
+    assert_eq!(status_of(&server, 6002, 12, &[("legs", "1:6,1:6")]), "400");
+    assert_eq!(status_of(&server, 6003, 12, &[("legs", "1:6,3:1")]), "404");
+    assert_eq!(status_of(&server, 6004, 12, &[("legs", "1:x")]), "400");
+    assert_eq!(server.controller.flights()[&1].seats_available, 10);
+
+    assert_eq!(status_of(&server, 6005, 12, &[("legs", "1:6,1:4")]), "200");
+    assert_eq!(server.controller.flights()[&1].seats_available, 0);
+}
+
+/// An unknown invocation semantic is rejected with 400 instead of being silently dropped
+fn check_invocation_semantic() {
+    assert!("exactly-once".parse::<InvocationSemantic>().is_err());
+    let mut config = Config::default();
+    config.client.invocation_semantic = "exactly-once".to_string();
+    let error = FlightClient::new(config).err().expect("client should reject exactly-once");
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server = Server::from_socket(socket, FlightController::new(), Config::default()).unwrap();
+    let packet = request_packet(5001, 2u8, 1, &[("source", "Singapore"), ("destination", "Tokyo")]);
+    let src: SocketAddr = "127.0.0.1:40003".parse().unwrap();
+    let response = server.handle_datagram(&packet, src).unwrap().expect("unknown semantic should be answered");
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    let field = |key: &str| decoded.as_map().unwrap().get(key).and_then(|value| value.as_string()).cloned();
+    assert_eq!(field("status").as_deref(), Some("400"), "exactly-once: {:?}", decoded);
+    assert_eq!(field("message").as_deref(), Some("Unknown invocation semantic"), "exactly-once: {:?}", decoded);
+    assert_eq!(field("request_id").as_deref(), Some("5001"), "exactly-once: {:?}", decoded);
+}
+
+/// A custom action registered on the server is dispatched like the built-in ones
+fn check_register_action() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut server = Server::from_socket(socket, FlightController::new(), Config::default()).unwrap();
+    server.register_action(100, |payload, _, src, _, _| {
+        let text = payload.get("text").and_then(|text| text.as_string()).ok_or("Missing field: text")?;
+        let mut data = HashMap::new();
+        data.insert("status".to_string(), "200".into());
+        data.insert("echo".to_string(), format!("{} from {}", text, src).into());
+        Ok(data)
+    });
+
+    let packet = request_packet(4001, InvocationSemantic::AtLeastOnce, 100, &[("text", "hello")]);
+    let src: SocketAddr = "127.0.0.1:40002".parse().unwrap();
+
+    let response = server.handle_datagram(&packet, src).unwrap().unwrap();
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    let echo = decoded.as_map().unwrap().get("echo").and_then(|echo| echo.as_string()).cloned();
+    assert_eq!(echo.as_deref(), Some("hello from 127.0.0.1:40002"), "echo: {:?}", decoded);
+    assert_eq!(status_of(&server, 4002, 100, &[]), "400");
+}
+
+/// A big-endian server and client talk end to end, and a little-endian client gets a clear error
+fn check_big_endian() {
+    let controller = FlightController::new();
+    controller.add_flight(controller::Flight {
+        flight_id: 3,
+        source: "Oslo".to_string(),
+        destination: "Berlin".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-02 06:30:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 123456789,
+        seats_available: 300,
+        capacity: 300,
+        overbook_limit: 0,
+    }).unwrap();
+    let mut config = Config::default();
+    config.server.address = "127.0.0.1:0".to_string();
+    config.server.byte_order = "big".to_string();
+    let server = Server::with_controller(config, controller).unwrap();
+    let port = server.local_addr().unwrap().port();
+    let shutdown = server.shutdown_handle();
+
+    thread::scope(|s| {
+        s.spawn(|| server.run().unwrap());
+
+        let client_with = |byte_order: &str| {
+            let mut config = Config::default();
+            config.server.address = SocketAddr::from(([127, 0, 0, 1], port)).to_string();
+            config.client.byte_order = byte_order.to_string();
+            config.client.retry = 1;
+            let mut client = FlightClient::new(config).unwrap();
+            client.set_verbose(false);
+            client
+        };
+
+        let response = client_with("big").query_flight_details(3).unwrap();
+        assert!(
+            matches!(response, Response::FlightDetails { airfare: Some(123456789), seats_available: Some(300), .. }),
+            "big-endian details: {:?}", response
+        );
+
+        let error = client_with("little").query_flight_details(3).unwrap_err();
+        assert!(error.to_string().contains("Byte order mismatch"), "little-endian client: {}", error);
+
+        shutdown.store(true, Ordering::SeqCst);
+    });
+}
+
+/// Collects pushed updates in memory instead of sending them
+struct CollectingSink(Arc<Mutex<Vec<(SocketAddr, FlightUpdate)>>>);
+
+impl UpdateSink for CollectingSink {
+    fn send_update(&self, addr: SocketAddr, update: &FlightUpdate) {
+        self.0.lock().unwrap().push((addr, update.clone()));
+    }
+}
+
+/// A reservation pushes one update to each subscriber through the controller's update sink
+fn check_update_sink() {
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let mut controller = FlightController::new();
+    controller.set_update_sink(Box::new(CollectingSink(updates.clone())));
+    controller.add_flight(controller::Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let first: SocketAddr = "127.0.0.1:41001".parse().unwrap();
+    let second: SocketAddr = "127.0.0.1:41002".parse().unwrap();
+    for addr in [first, second] {
+        let response = controller.handle_request(Request::MonitorFlight { flight_id: 1, monitor_interval: 60, threshold: 0 }, &socket, Some(addr));
+        assert!(matches!(response, Response::MonitoringStarted(Ok(()))), "monitor: {:?}", response);
+    }
+
+    let response = controller.handle_request(Request::ReserveSeats { flight_id: 1, seats: 3, idempotency_key: None }, &socket, None);
+    assert!(matches!(response, Response::Reservation(Ok(0))), "reserve: {:?}", response);
+
+    let mut updates = updates.lock().unwrap().clone();
+    updates.sort_by_key(|(addr, _)| *addr);
+    let summary: Vec<_> = updates.iter().map(|(addr, update)| (*addr, update.flight_id, update.seats_available)).collect();
+    assert_eq!(summary, vec![(first, 1, 7), (second, 1, 7)]);
+}
+
+/// Subscribing twice from the same address replaces the subscription instead of doubling updates
+fn check_resubscribe() {
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let mut controller = FlightController::new();
+    controller.set_update_sink(Box::new(CollectingSink(updates.clone())));
+    controller.add_flight(controller::Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = "127.0.0.1:41004".parse().unwrap();
+    for monitor_interval in [60, 120] {
+        controller.handle_request(Request::MonitorFlight { flight_id: 1, monitor_interval, threshold: 0 }, &socket, Some(addr));
+    }
+
+    controller.handle_request(Request::ReserveSeats { flight_id: 1, seats: 1, idempotency_key: None }, &socket, None);
+    controller.handle_request(Request::CancelReservation { flight_id: 1, seats: 1 }, &socket, None);
+    let summary: Vec<_> = updates.lock().unwrap().iter().map(|(addr, update)| (*addr, update.seats_available)).collect();
+    assert_eq!(summary, vec![(addr, 9), (addr, 10)]);
+}
+
+/// A subscriber with a threshold is only notified once seats drop below it
+fn check_monitor_threshold() {
+    let updates = Arc::new(Mutex::new(Vec::new()));
+    let mut controller = FlightController::new();
+    controller.set_update_sink(Box::new(CollectingSink(updates.clone())));
+    controller.add_flight(controller::Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 20,
+        capacity: 20,
+        overbook_limit: 0,
+    }).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr: SocketAddr = "127.0.0.1:41003".parse().unwrap();
+    let response = controller.handle_request(Request::MonitorFlight { flight_id: 1, monitor_interval: 60, threshold: 10 }, &socket, Some(addr));
+    assert!(matches!(response, Response::MonitoringStarted(Ok(()))), "monitor: {:?}", response);
+
+    // 20 -> 15 seats stays above the threshold
+    controller.handle_request(Request::ReserveSeats { flight_id: 1, seats: 5, idempotency_key: None }, &socket, None);
+    assert!(updates.lock().unwrap().is_empty(), "updates above threshold: {:?}", updates.lock().unwrap());
+
+    // 15 -> 9 seats drops below it
+    controller.handle_request(Request::ReserveSeats { flight_id: 1, seats: 6, idempotency_key: None }, &socket, None);
+    let summary: Vec<_> = updates.lock().unwrap().iter().map(|(addr, update)| (*addr, update.seats_available)).collect();
+    assert_eq!(summary, vec![(addr, 9)]);
+
+    let response = controller.handle_request(Request::MonitorFlight { flight_id: 1, monitor_interval: 60, threshold: -1 }, &socket, Some(addr));
+    assert!(matches!(response, Response::MonitoringStarted(Err(_))), "negative threshold: {:?}", response);
+}
+
+/// With a heartbeat configured, an idle subscriber still gets the flight's state within the interval
+fn check_heartbeat() {
+    let controller = FlightController::new();
+    controller.add_flight(controller::Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+    let mut config = Config::default();
+    config.server.address = "127.0.0.1:0".to_string();
+    config.server.heartbeat_interval = 1;
+    let server = Server::with_controller(config, controller).unwrap();
+    let server_addr = server.local_addr().unwrap();
+    let shutdown = server.shutdown_handle();
+
+    thread::scope(|s| {
+        s.spawn(|| server.run().unwrap());
+
+        let subscriber = UdpSocket::bind("127.0.0.1:0").unwrap();
+        subscriber.set_read_timeout(Some(Duration::from_secs(3))).unwrap();
+        let packet = request_packet(8001, InvocationSemantic::AtLeastOnce, 4, &[("flight_id", "1"), ("monitor_interval", "60")]);
+        subscriber.send_to(&packet, server_addr).unwrap();
+        let mut buffer = [0u8; 1024];
+        let amt = subscriber.recv(&mut buffer).expect("monitor should be answered");
+        let decoded = Deserializer::new(&buffer[..amt], ByteOrder::Little).deserialize_next().unwrap();
+        assert_eq!(decoded.as_map().unwrap().get("status"), Some(&Value::String("200".to_string())), "monitor: {:?}", decoded);
+
+        // No reservation happens, yet an update arrives within the one-second interval
+        let started = std::time::Instant::now();
+        let amt = subscriber.recv(&mut buffer).expect("heartbeat should arrive");
+        let elapsed = started.elapsed();
+        let update = Deserializer::new(&buffer[..amt], ByteOrder::Little).deserialize_next().unwrap();
+        let field = |key: &str| update.as_map().unwrap().get(key).and_then(|value| value.as_string()).cloned();
+        assert_eq!(field("action").as_deref(), Some("5"), "heartbeat: {:?}", update);
+        assert_eq!(field("seats_available").as_deref(), Some("10"), "heartbeat: {:?}", update);
+        assert!(elapsed < Duration::from_millis(1500), "heartbeat after {:?}", elapsed);
+
+        shutdown.store(true, Ordering::SeqCst);
+    });
+}
+
+/// Reservations may go up to capacity + overbook_limit, reporting the seats sold beyond capacity
+fn check_overbooking() {
+    let controller = FlightController::new();
+    controller.add_flight(controller::Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 2,
+    }).unwrap();
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let reserve = |seats| controller.handle_request(Request::ReserveSeats { flight_id: 1, seats, idempotency_key: None }, &socket, None);
+
+    let response = reserve(9);
+    assert!(matches!(response, Response::Reservation(Ok(0))), "within capacity: {:?}", response);
+    // Exactly at the limit: one seat fits in capacity, the other two are overbooked
+    let response = reserve(3);
+    assert!(matches!(response, Response::Reservation(Ok(2))), "at limit: {:?}", response);
+    let response = reserve(1);
+    assert!(matches!(&response, Response::Reservation(Err(e)) if e == "Not enough seats available"), "over limit: {:?}", response);
+    let response = controller.handle_request(Request::ReserveMulti { items: vec![(1, 1)] }, &socket, None);
+    assert!(matches!(response, Response::MultiReservation(Err((0, _)))), "reserve_multi over limit: {:?}", response);
+    assert_eq!(controller.stats().seats_sold, 12);
+
+    // The server reports the overbooked count alongside the status
+    let server = Server::from_socket(UdpSocket::bind("127.0.0.1:0").unwrap(), FlightController::new(), Config::default()).unwrap();
+    server.controller.add_flight(controller::Flight {
+        flight_id: 2,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 1,
+        capacity: 1,
+        overbook_limit: 1,
+    }).unwrap();
+    let packet = request_packet(2301, InvocationSemantic::AtLeastOnce, 3, &[("flight_id", "2"), ("seats", "2")]);
+    let response = server.handle_datagram(&packet, "127.0.0.1:40003".parse().unwrap()).unwrap().unwrap();
+    let decoded = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+    assert_eq!(decoded.as_map().unwrap()["overbooked"].as_i32(), Some(1), "reserve: {:?}", decoded);
+    assert_eq!(status_of(&server, 2302, 3, &[("flight_id", "2"), ("seats", "1")]), "400");
+}
+
+/// A client sees only its own subscriptions, ordered by flight ID with the time left on each
+fn check_list_my_monitors() {
+    let controller = FlightController::new();
+    for flight_id in [1, 2, 3] {
+        controller.add_flight(controller::Flight {
+            flight_id,
+            source: "Singapore".to_string(),
+            destination: "Tokyo".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 45000,
+            seats_available: 10,
+            capacity: 10,
+            overbook_limit: 0,
+        }).unwrap();
+    }
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let client: SocketAddr = "127.0.0.1:41001".parse().unwrap();
+    let other: SocketAddr = "127.0.0.1:41002".parse().unwrap();
+    for (flight_id, monitor_interval, addr) in [(2, 60, client), (1, 30, client), (3, 60, other)] {
+        let response = controller.handle_request(Request::MonitorFlight { flight_id, monitor_interval, threshold: 0 }, &socket, Some(addr));
+        assert!(matches!(response, Response::MonitoringStarted(Ok(()))), "monitor: {:?}", response);
+    }
+
+    match controller.handle_request(Request::ListMyMonitors, &socket, Some(client)) {
+        Response::MyMonitors(monitors) => {
+            let flight_ids: Vec<_> = monitors.iter().map(|(flight_id, _)| *flight_id).collect();
+            assert_eq!(flight_ids, vec![1, 2]);
+            assert!((29..=30).contains(&monitors[0].1), "remaining: {:?}", monitors);
+            assert!((59..=60).contains(&monitors[1].1), "remaining: {:?}", monitors);
+        }
+        response => panic!("list monitors: {:?}", response),
+    }
+
+    let unknown: SocketAddr = "127.0.0.1:41003".parse().unwrap();
+    let response = controller.handle_request(Request::ListMyMonitors, &socket, Some(unknown));
+    assert!(matches!(&response, Response::MyMonitors(monitors) if monitors.is_empty()), "no subscriptions: {:?}", response);
+}
+
+/// Flights load from a seed file with defaults for the optional fields; a bad row names the entry
+fn check_seed_file() {
+    let path = std::env::temp_dir().join(format!("flight_seed_{}.toml", std::process::id()));
+    fs::write(&path, r#"
+[[flights]]
+flight_id = 10
+source = "Singapore"
+destination = "Tokyo"
+departure_time = "2024-10-01 08:00:00"
+airfare = 45000
+capacity = 10
+
+[[flights]]
+flight_id = 11
+source = "Tokyo"
+destination = "Singapore"
+departure_time = "2024-10-05 20:30:00"
+airfare = 41000
+capacity = 20
+seats_available = 15
+overbook_limit = 2
+"#).unwrap();
+    let controller = FlightController::new();
+    for flight in seed::load_seed_file(&path).unwrap() {
+        controller.add_flight(flight).unwrap();
+    }
+    {
+        let flights = controller.flights();
+        let summary: Vec<_> = [10, 11].iter()
+            .map(|id| flights.get(id).map(|flight| (flight.source.as_str(), flight.seats_available, flight.capacity, flight.overbook_limit)))
+            .collect();
+        assert_eq!(summary, vec![Some(("Singapore", 10, 10, 0)), Some(("Tokyo", 15, 20, 2))]);
+    }
+
+    fs::write(&path, r#"
+[[flights]]
+flight_id = 12
+source = "Singapore"
+destination = "Tokyo"
+departure_time = "2024-10-01 08:00:00"
+airfare = 45000
+capacity = 10
+
+[[flights]]
+flight_id = 13
+source = "Singapore"
+destination = "Tokyo"
+departure_time = "tomorrow"
+airfare = 45000
+capacity = 10
+"#).unwrap();
+    let error = seed::load_seed_file(&path).unwrap_err();
+    assert_eq!(error.row, Some(2), "error: {}", error);
+    assert!(error.to_string().contains("departure_time"), "error: {}", error);
+
+    let _ = fs::remove_file(&path);
+    assert!(seed::load_seed_file(&path).is_err());
+}
+
+/// Monitor requests use their own timeout while other requests fall back to the global one
+fn check_action_timeouts() {
+    let mut config = Config::default();
+    config.client.timeout = 2;
+    config.client.timeouts.monitor = Some(30);
+    assert_eq!(action_timeout(&config.client, 4), Duration::from_secs(30));
+    assert_eq!(action_timeout(&config.client, 1), Duration::from_secs(2));
+    assert_eq!(action_timeout(&config.client, 11), Duration::from_secs(2));
+}
+
+fn main() {
+    check_handle_datagram();
+    check_action_timeouts();
+    check_ping();
+    check_status_codes();
+    check_invocation_semantic();
+    check_reserve_multi();
+    check_details_batch();
+    check_stats();
+    check_register_action();
+    check_update_sink();
+    check_monitor_threshold();
+    check_resubscribe();
+    check_big_endian();
+    check_heartbeat();
+    check_overbooking();
+    check_list_my_monitors();
+    check_seed_file();
+
+    let controller = FlightController::new();
+    controller.add_flight(controller::Flight {
+        flight_id: 7,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+
+    // Bind to an ephemeral port and read back the port the OS picked
+    let mut config = Config::default();
+    config.server.address = "0.0.0.0:0".to_string();
+    let server = Server::with_controller(config, controller).unwrap();
+    let port = server.local_addr().unwrap().port();
+    let shutdown = server.shutdown_handle();
+
+    thread::scope(|s| {
+        s.spawn(|| server.run().unwrap());
+
+        let mut client = client_for(port);
+        let rtt = client.ping().unwrap();
+        assert!(rtt < Duration::from_secs(1), "ping rtt: {:?}", rtt);
+
+        // QueryFlightIds
+        let response = client.query_flight_ids("Singapore", "Tokyo", None).unwrap();
+        assert!(matches!(&response, Response::FlightIds(ids) if ids == &[7]), "query: {:?}", response);
+        let response = client.query_flight_ids("Singapore", "Paris", None).unwrap();
+        assert!(matches!(&response, Response::FlightIds(ids) if ids.is_empty()), "empty query: {:?}", response);
+
+        // ReserveSeats
+        let response = client.reserve_seats(7, 3).unwrap();
+        assert!(matches!(response, Response::Reservation(Ok(0))), "reserve: {:?}", response);
+        let response = client.reserve_seats(7, 100).unwrap();
+        assert!(matches!(response, Response::Reservation(Err(_))), "overbook: {:?}", response);
+        let response = client.query_flight_details(7).unwrap();
+        assert!(matches!(response, Response::FlightDetails { seats_available: Some(7), .. }), "details: {:?}", response);
+
+        // QueryFlightDetailsBatch returns details for known flights and an error for the rest
+        let response = client.query_flight_details_batch(&[7, 99]).unwrap();
+        match &response {
+            Response::FlightDetailsBatch(details) => {
+                assert!(matches!(&details[..], [(7, Ok(_)), (99, Err(_))]), "batch: {:?}", response);
+                assert_eq!(details[0].1.as_ref().unwrap().seats_available, 7);
+            }
+            _ => panic!("batch: {:?}", response),
+        }
+
+        // ReserveMulti is all or nothing
+        let response = client.reserve_multi(&[(7, 1), (7, 100)]).unwrap();
+        assert!(matches!(&response, Response::MultiReservation(Err((1, _)))), "reserve_multi: {:?}", response);
+        let response = client.query_flight_details(7).unwrap();
+        assert!(matches!(response, Response::FlightDetails { seats_available: Some(7), .. }), "details: {:?}", response);
+
+        // Stats over the wire match the controller's own numbers
+        let response = client.stats().unwrap();
+        assert!(matches!(&response, Response::Stats(stats) if stats.flights == 1 && stats.seats_sold == 3), "stats: {:?}", response);
+
+        // Monitor push after another client's reservation
+        let response = client.monitor_flight(7, 60).unwrap();
+        assert!(matches!(response, Response::MonitoringStarted(Ok(()))), "monitor: {:?}", response);
+        let response = client_for(port).reserve_seats(7, 2).unwrap();
+        assert!(matches!(response, Response::Reservation(Ok(0))), "second reserve: {:?}", response);
+        let update = client.receive_update().unwrap();
+        let field = |key: &str| update.get(key).and_then(|value| value.as_string()).map(|value| value.as_str());
+        assert_eq!(field("action"), Some("5"), "update: {:?}", update);
+        assert_eq!(field("flight_id"), Some("7"), "update: {:?}", update);
+        assert_eq!(field("seats_available"), Some("5"), "update: {:?}", update);
+
+        // The subscription is listed for the monitoring client only
+        let response = client.list_my_monitors().unwrap();
+        assert!(matches!(&response, Response::MyMonitors(monitors) if monitors.len() == 1 && monitors[0].0 == 7), "monitors: {:?}", response);
+        let response = client_for(port).list_my_monitors().unwrap();
+        assert!(matches!(&response, Response::MyMonitors(monitors) if monitors.is_empty()), "other monitors: {:?}", response);
+
+        shutdown.store(true, Ordering::SeqCst);
+    });
+
+    println!("All integration checks passed");
+}
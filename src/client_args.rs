@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use chrono::NaiveDateTime;
+
+use crate::controller::Request;
+
+/// 解析脚本模式的命令行参数(不含程序名和--verbose)
+///
+/// 没有子命令时返回Ok(None),此时进入交互模式;参数错误时返回错误说明
+pub fn parse_command(args: &[String]) -> Result<Option<Request>, String> {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Ok(None),
+    };
+
+    // 其余参数必须是成对的 --name value
+    let mut options = HashMap::new();
+    let mut rest = rest.iter();
+    while let Some(name) = rest.next() {
+        let key = name.strip_prefix("--").ok_or_else(|| format!("Unexpected argument: {}", name))?;
+        let value = rest.next().ok_or_else(|| format!("Missing value for --{}", key))?;
+        if options.insert(key, value.as_str()).is_some() {
+            return Err(format!("Duplicate option: --{}", key));
+        }
+    }
+
+    let request = match command {
+        "query" => Request::QueryFlightIds {
+            source: take(&mut options, "source")?,
+            destination: take(&mut options, "destination")?,
+            max_fare: take_optional(&mut options, "max-fare")?,
+        },
+        "query-by-date" => Request::QueryFlightsByDate {
+            source: take(&mut options, "source")?,
+            destination: take(&mut options, "destination")?,
+            from: take_time(&mut options, "from")?,
+            to: take_time(&mut options, "to")?,
+        },
+        "details" => Request::QueryFlightDetails {
+            flight_id: take(&mut options, "flight-id")?,
+        },
+        "details-batch" => Request::QueryFlightDetailsBatch {
+            flight_ids: take_list(&mut options, "flight-ids")?,
+        },
+        "reserve" => Request::ReserveSeats {
+            flight_id: take(&mut options, "flight-id")?,
+            seats: take(&mut options, "seats")?,
+            idempotency_key: take_optional(&mut options, "idempotency-key")?,
+        },
+        "reserve-multi" => Request::ReserveMulti {
+            items: take_legs(&mut options, "legs")?,
+        },
+        "cancel" => Request::CancelReservation {
+            flight_id: take(&mut options, "flight-id")?,
+            seats: take(&mut options, "seats")?,
+        },
+        "monitor" => Request::MonitorFlight {
+            flight_id: take(&mut options, "flight-id")?,
+            monitor_interval: take(&mut options, "interval")?,
+            threshold: take_optional(&mut options, "threshold")?.unwrap_or(0),
+        },
+        "unmonitor" => Request::Unmonitor {
+            flight_id: take(&mut options, "flight-id")?,
+        },
+        "stats" => Request::Stats,
+        "reset" => Request::ResetFlight {
+            flight_id: take(&mut options, "flight-id")?,
+        },
+        _ => return Err(format!("Unknown command: {}", command)),
+    };
+
+    if let Some(key) = options.keys().next() {
+        return Err(format!("Unknown option for {}: --{}", command, key));
+    }
+    Ok(Some(request))
+}
+
+/// 取出并解析必填选项
+fn take<T: FromStr>(options: &mut HashMap<&str, &str>, key: &str) -> Result<T, String> {
+    take_optional(options, key)?.ok_or_else(|| format!("Missing option: --{}", key))
+}
+
+/// 取出并解析可选选项
+fn take_optional<T: FromStr>(options: &mut HashMap<&str, &str>, key: &str) -> Result<Option<T>, String> {
+    options.remove(key)
+        .map(|value| value.parse().map_err(|_| format!("Invalid value for --{}: {}", key, value)))
+        .transpose()
+}
+
+/// 取出并解析时间选项,格式为 "%Y-%m-%d %H:%M:%S"
+fn take_time(options: &mut HashMap<&str, &str>, key: &str) -> Result<NaiveDateTime, String> {
+    let value = take::<String>(options, key)?;
+    NaiveDateTime::parse_from_str(&value, "%Y-%m-%d %H:%M:%S")
+        .map_err(|_| format!("Invalid value for --{}: {}", key, value))
+}
+
+/// 取出并解析逗号分隔的列表选项
+fn take_list<T: FromStr>(options: &mut HashMap<&str, &str>, key: &str) -> Result<Vec<T>, String> {
+    let value = take::<String>(options, key)?;
+    value.split(',')
+        .map(|item| item.parse().map_err(|_| format!("Invalid value for --{}: {}", key, value)))
+        .collect()
+}
+
+/// 取出多航班预订的选项,格式为 "flight_id:seats,flight_id:seats"
+fn take_legs(options: &mut HashMap<&str, &str>, key: &str) -> Result<Vec<(i32, i32)>, String> {
+    let value = take::<String>(options, key)?;
+    value.split(',')
+        .map(|leg| {
+            let (flight_id, seats) = leg.split_once(':').ok_or_else(|| format!("Invalid value for --{}: {}", key, value))?;
+            match (flight_id.parse(), seats.parse()) {
+                (Ok(flight_id), Ok(seats)) => Ok((flight_id, seats)),
+                _ => Err(format!("Invalid value for --{}: {}", key, value)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Request>, String> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        parse_command(&args)
+    }
+
+    fn request(args: &[&str]) -> Request {
+        parse(args).unwrap().unwrap()
+    }
+
+    fn time(value: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn no_command_starts_interactive_mode() {
+        assert!(parse(&[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn every_subcommand_parses_its_options() {
+        let query = request(&["query", "--source", "Singapore", "--destination", "Tokyo"]);
+        assert!(matches!(query, Request::QueryFlightIds { ref source, ref destination, max_fare: None } if source == "Singapore" && destination == "Tokyo"), "{:?}", query);
+        let query = request(&["query", "--source", "Singapore", "--destination", "Tokyo", "--max-fare", "50000"]);
+        assert!(matches!(query, Request::QueryFlightIds { max_fare: Some(50000), .. }), "{:?}", query);
+
+        let by_date = request(&["query-by-date", "--source", "Singapore", "--destination", "Tokyo", "--from", "2024-10-01 00:00:00", "--to", "2024-10-02 00:00:00"]);
+        assert!(matches!(by_date, Request::QueryFlightsByDate { ref source, ref destination, from, to }
+            if source == "Singapore" && destination == "Tokyo" && from == time("2024-10-01 00:00:00") && to == time("2024-10-02 00:00:00")), "{:?}", by_date);
+
+        assert!(matches!(request(&["details", "--flight-id", "7"]), Request::QueryFlightDetails { flight_id: 7 }));
+        let batch = request(&["details-batch", "--flight-ids", "3,1,2"]);
+        assert!(matches!(batch, Request::QueryFlightDetailsBatch { ref flight_ids } if flight_ids == &[3, 1, 2]), "{:?}", batch);
+
+        assert!(matches!(request(&["reserve", "--flight-id", "1", "--seats", "2"]), Request::ReserveSeats { flight_id: 1, seats: 2, idempotency_key: None }));
+        let keyed = request(&["reserve", "--seats", "2", "--flight-id", "1", "--idempotency-key", "abc"]);
+        assert!(matches!(keyed, Request::ReserveSeats { idempotency_key: Some(ref key), .. } if key == "abc"), "{:?}", keyed);
+        let multi = request(&["reserve-multi", "--legs", "1:2,3:4"]);
+        assert!(matches!(multi, Request::ReserveMulti { ref items } if items == &[(1, 2), (3, 4)]), "{:?}", multi);
+
+        assert!(matches!(request(&["cancel", "--flight-id", "1", "--seats", "2"]), Request::CancelReservation { flight_id: 1, seats: 2 }));
+        assert!(matches!(request(&["monitor", "--flight-id", "1", "--interval", "60"]), Request::MonitorFlight { flight_id: 1, monitor_interval: 60, threshold: 0 }));
+        assert!(matches!(request(&["monitor", "--flight-id", "1", "--interval", "60", "--threshold", "5"]), Request::MonitorFlight { threshold: 5, .. }));
+        assert!(matches!(request(&["unmonitor", "--flight-id", "1"]), Request::Unmonitor { flight_id: 1 }));
+        assert!(matches!(request(&["stats"]), Request::Stats));
+        assert!(matches!(request(&["reset", "--flight-id", "1"]), Request::ResetFlight { flight_id: 1 }));
+    }
+
+    #[test]
+    fn malformed_arguments_are_rejected_with_a_reason() {
+        let error = |args: &[&str]| parse(args).unwrap_err();
+        assert_eq!(error(&["details", "flight-id", "1"]), "Unexpected argument: flight-id");
+        assert_eq!(error(&["details", "--flight-id"]), "Missing value for --flight-id");
+        assert_eq!(error(&["details", "--flight-id", "1", "--flight-id", "2"]), "Duplicate option: --flight-id");
+        assert_eq!(error(&["book", "--flight-id", "1"]), "Unknown command: book");
+        assert_eq!(error(&["details", "--flight-id", "1", "--seats", "2"]), "Unknown option for details: --seats");
+        assert_eq!(error(&["stats", "--flight-id", "1"]), "Unknown option for stats: --flight-id");
+        assert_eq!(error(&["cancel", "--flight-id", "1"]), "Missing option: --seats");
+        assert_eq!(error(&["reserve", "--flight-id", "one", "--seats", "2"]), "Invalid value for --flight-id: one");
+    }
+
+    #[test]
+    fn list_time_and_leg_values_are_validated() {
+        let error = |args: &[&str]| parse(args).unwrap_err();
+        assert_eq!(error(&["details-batch", "--flight-ids", "1,x"]), "Invalid value for --flight-ids: 1,x");
+        assert_eq!(error(&["details-batch", "--flight-ids", ""]), "Invalid value for --flight-ids: ");
+        assert_eq!(error(&["reserve-multi", "--legs", "1:2,3"]), "Invalid value for --legs: 1:2,3");
+        assert_eq!(error(&["reserve-multi", "--legs", "1:two"]), "Invalid value for --legs: 1:two");
+        assert_eq!(error(&["reserve-multi"]), "Missing option: --legs");
+        let by_date = |from| parse(&["query-by-date", "--source", "A", "--destination", "B", "--from", from, "--to", "2024-10-02 00:00:00"]);
+        assert_eq!(by_date("2024-10-01").unwrap_err(), "Invalid value for --from: 2024-10-01");
+        assert!(by_date("2024-10-01 00:00:00").is_ok());
+        assert_eq!(parse(&["query-by-date", "--source", "A", "--destination", "B", "--from", "2024-10-01 00:00:00"]).unwrap_err(), "Missing option: --to");
+    }
+}
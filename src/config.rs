@@ -1,13 +1,23 @@
 // 引入必要的外部crate
 use serde::Deserialize; // 用于反序列化
+use std::env; // 用于读取环境变量
+use std::fmt; // 用于实现错误信息的显示
 use std::fs; // 用于文件系统操作
+use std::net::SocketAddr; // 用于校验地址格式
+use std::path::{Path, PathBuf}; // 用于表示配置文件路径
+use std::str::FromStr; // 用于解析环境变量的值
+use std::thread; // 用于后台监视配置文件
+use std::time::Duration; // 用于设置轮询间隔
 
 // 定义主要的Config结构体
 // #[derive(Deserialize)]属性允许这个结构体从TOML格式反序列化
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 pub struct Config {
     pub server: ServerConfig, // 包含服务器配置的嵌套结构
-    pub client: ClientConfig, // 包含客户端配置的嵌套结构
+    #[serde(default)]
+    pub client: ClientConfig, // 包含客户端配置的嵌套结构,仅部署服务器时可省略
+    #[serde(skip)]
+    source: Option<PathBuf>, // 配置加载自的文件,用于reload和watch
 }
 
 // 定义ServerConfig结构体
@@ -15,7 +25,30 @@ pub struct Config {
 #[derive(Deserialize)]
 pub struct ServerConfig {
     pub address: String, // 服务器地址,作为字符串存储
-    pub loss_rate: f32, // 丢包率
+    #[serde(default)]
+    pub loss_rate: f32, // 丢包率,取值范围0.0到1.0,默认不丢包
+    #[serde(default)]
+    pub request_loss_rate: f32, // 请求丢包率,模拟请求在到达服务器前丢失,取值范围0.0到1.0
+    #[serde(default)]
+    pub allow_admin_commands: bool, // 是否允许管理命令(如重置航班),默认关闭
+    #[serde(default = "default_byte_order")]
+    pub byte_order: String, // 字节序,"little" 或 "big"
+    #[serde(default)]
+    pub store_file: Option<String>, // 去重存储的持久化文件路径,未设置时不持久化
+    #[serde(default = "default_dedup_ttl")]
+    pub dedup_ttl: u64, // 去重存储中响应的有效时间(秒)
+    #[serde(default = "default_workers")]
+    pub workers: usize, // 处理请求的工作线程数,1表示在接收线程中顺序处理
+    #[serde(default)]
+    pub requests_per_sec: f64, // 每个客户端每秒允许的请求数,0表示不限流
+    #[serde(default = "default_burst")]
+    pub burst: u32, // 每个客户端允许的突发请求数
+    #[serde(default)]
+    pub rng_seed: Option<u64>, // 丢包模拟的随机数种子,设置后丢包序列可复现,未设置时使用系统熵
+    #[serde(default)]
+    pub heartbeat_interval: u64, // 监控心跳间隔(秒):即使航班没有变化也定期向监控客户端推送当前状态,0表示关闭
+    #[serde(default)]
+    pub seed_file: Option<String>, // 初始航班数据的TOML文件路径,未设置时使用内置的示例航班
 }
 
 // 定义ClientConfig结构体
@@ -24,21 +57,352 @@ pub struct ClientConfig {
     pub timeout: u32, // 超时时间
     pub retry: u32, // 重试次数
     pub invocation_semantic: String, // 调用语义
+    #[serde(default = "default_byte_order")]
+    pub byte_order: String, // 字节序,"little" 或 "big"
+    #[serde(default)]
+    pub cache_size: usize, // 查询结果缓存的最大条目数,0表示不缓存
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl: u64, // 查询结果缓存的有效时间(秒)
+    #[serde(default = "default_backoff_base_ms")]
+    pub backoff_base_ms: u64, // 重试前等待的初始时间(毫秒),每次重试翻倍
+    #[serde(default = "default_backoff_max_ms")]
+    pub backoff_max_ms: u64, // 重试前等待的最长时间(毫秒)
+    #[serde(default)]
+    pub rng_seed: Option<u64>, // 生成请求ID的随机数种子,仅用于测试:相同种子会生成相同的请求ID
+    #[serde(default)]
+    pub force_duplicate: bool, // 仅用于测试:每个请求沿用上一个请求的request_id,用于验证at-most-once去重
+    #[serde(default)]
+    pub timeouts: ActionTimeouts, // 按请求类型覆盖超时时间,未设置的类型使用timeout
+}
+
+// 按请求类型设置的超时时间(秒),对应配置文件中的[client.timeouts]
+// 未设置的字段使用client.timeout,如监控请求可以比查询等待得更久
+#[derive(Deserialize, Default)]
+pub struct ActionTimeouts {
+    #[serde(default)]
+    pub query: Option<u32>, // 查询航班ID(包括按日期查询)
+    #[serde(default)]
+    pub details: Option<u32>, // 查询航班详情(包括批量查询)
+    #[serde(default)]
+    pub reserve: Option<u32>, // 预订座位(包括多航班预订)
+    #[serde(default)]
+    pub cancel: Option<u32>, // 取消预订
+    #[serde(default)]
+    pub monitor: Option<u32>, // 开始监控
+    #[serde(default)]
+    pub unmonitor: Option<u32>, // 停止监控
+}
+
+// 没有配置文件时使用的服务器默认配置
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            address: "127.0.0.1:8080".to_string(),
+            loss_rate: 0.0,
+            request_loss_rate: 0.0,
+            allow_admin_commands: false,
+            byte_order: default_byte_order(),
+            store_file: None,
+            dedup_ttl: default_dedup_ttl(),
+            workers: default_workers(),
+            requests_per_sec: 0.0,
+            burst: default_burst(),
+            rng_seed: None,
+            heartbeat_interval: 0,
+            seed_file: None,
+        }
+    }
+}
+
+// 缺少[client]部分时使用的客户端默认配置
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            timeout: 10,
+            retry: 3,
+            invocation_semantic: "at-most-once".to_string(),
+            byte_order: default_byte_order(),
+            cache_size: 0,
+            cache_ttl: default_cache_ttl(),
+            backoff_base_ms: default_backoff_base_ms(),
+            backoff_max_ms: default_backoff_max_ms(),
+            rng_seed: None,
+            force_duplicate: false,
+            timeouts: ActionTimeouts::default(),
+        }
+    }
+}
+
+// 未设置FLIGHT_CONFIG时读取的配置文件
+#[allow(dead_code)]
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+// 支持的调用语义
+const INVOCATION_SEMANTICS: [&str; 2] = ["at-most-once", "at-least-once"];
+
+// 支持的字节序
+const BYTE_ORDERS: [&str; 2] = ["little", "big"];
+
+// 配置校验错误,field为出错字段的完整名称(如"server.loss_rate")
+#[derive(Debug)]
+pub struct ConfigError {
+    pub field: &'static str, // 出错的字段
+    pub message: String, // 错误原因
+}
+
+impl ConfigError {
+    fn new(field: &'static str, message: String) -> Self {
+        ConfigError { field, message }
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid config value for {}: {}", self.field, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// 环境变量存在时将其解析后写入target,返回是否发生了覆盖
+fn env_override<T>(name: &str, field: &'static str, target: &mut T) -> Result<bool, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match env::var(name) {
+        Ok(value) => {
+            *target = value
+                .parse()
+                .map_err(|e| ConfigError::new(field, format!("invalid value {:?} in {}: {}", value, name, e)))?;
+            Ok(true)
+        }
+        Err(env::VarError::NotPresent) => Ok(false),
+        Err(env::VarError::NotUnicode(_)) => Err(ConfigError::new(field, format!("{} is not valid unicode", name))),
+    }
+}
+
+// 字节序的默认值
+fn default_byte_order() -> String {
+    "little".to_string()
+}
+
+// 去重存储有效时间的默认值
+fn default_dedup_ttl() -> u64 {
+    3600
+}
+
+// 工作线程数的默认值
+fn default_workers() -> usize {
+    4
+}
+
+// 突发请求数的默认值
+fn default_burst() -> u32 {
+    20
+}
+
+// 查询结果缓存有效时间的默认值
+fn default_cache_ttl() -> u64 {
+    5
+}
+
+// 重试初始等待时间的默认值
+fn default_backoff_base_ms() -> u64 {
+    100
+}
+
+// 重试最长等待时间的默认值
+fn default_backoff_max_ms() -> u64 {
+    2000
 }
 
 // 为Config结构体实现方法
 impl Config {
     // 加载配置的静态方法
+    // 优先读取FLIGHT_CONFIG环境变量指定的文件,否则读取当前目录下的"config.toml"
     // 返回Result类型,成功时包含Config实例,失败时包含错误
     pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        // 从"config.toml"文件读取内容
-        // ?运算符在遇到错误时会提前返回错误
-        let config_text = fs::read_to_string("config.toml")?;
-        
+        Self::load_from(Self::path())
+    }
+
+    // 加载配置,当前目录下没有"config.toml"时使用默认配置
+    // 优先级: FLIGHT_CONFIG指定的文件 > 当前目录下的"config.toml" > 内置默认值
+    // FLIGHT_CONFIG指定的文件不存在、或文件存在但格式错误时仍然返回错误,避免悄悄忽略写错的配置
+    pub fn load_or_default() -> Result<Self, Box<dyn std::error::Error>> {
+        Self::load_explicit_or_default(env::var_os("FLIGHT_CONFIG").map(PathBuf::from), Path::new(DEFAULT_CONFIG_PATH))
+    }
+
+    // explicit为明确指定的配置文件,必须存在;未指定时读取default_path,该文件不存在才使用默认配置
+    fn load_explicit_or_default(explicit: Option<PathBuf>, default_path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        if let Some(path) = explicit {
+            return Self::load_from(path);
+        }
+        if !default_path.exists() {
+            let mut config = Config::default();
+            config.apply_env_overrides()?;
+            config.validate()?;
+            return Ok(config);
+        }
+        Self::load_from(default_path)
+    }
+
+    // 用FLIGHT_<SECTION>_<FIELD>形式的环境变量覆盖配置,如FLIGHT_SERVER_ADDRESS、FLIGHT_CLIENT_TIMEOUT
+    // 无法解析的值直接报错,而不是被忽略
+    fn apply_env_overrides(&mut self) -> Result<(), ConfigError> {
+        env_override("FLIGHT_SERVER_ADDRESS", "server.address", &mut self.server.address)?;
+        env_override("FLIGHT_SERVER_LOSS_RATE", "server.loss_rate", &mut self.server.loss_rate)?;
+        env_override("FLIGHT_SERVER_REQUEST_LOSS_RATE", "server.request_loss_rate", &mut self.server.request_loss_rate)?;
+        env_override("FLIGHT_SERVER_ALLOW_ADMIN_COMMANDS", "server.allow_admin_commands", &mut self.server.allow_admin_commands)?;
+        env_override("FLIGHT_SERVER_BYTE_ORDER", "server.byte_order", &mut self.server.byte_order)?;
+        let mut store_file = String::new();
+        if env_override("FLIGHT_SERVER_STORE_FILE", "server.store_file", &mut store_file)? {
+            self.server.store_file = Some(store_file);
+        }
+        env_override("FLIGHT_SERVER_DEDUP_TTL", "server.dedup_ttl", &mut self.server.dedup_ttl)?;
+        env_override("FLIGHT_SERVER_WORKERS", "server.workers", &mut self.server.workers)?;
+        env_override("FLIGHT_SERVER_REQUESTS_PER_SEC", "server.requests_per_sec", &mut self.server.requests_per_sec)?;
+        env_override("FLIGHT_SERVER_BURST", "server.burst", &mut self.server.burst)?;
+        let mut rng_seed = 0;
+        if env_override("FLIGHT_SERVER_RNG_SEED", "server.rng_seed", &mut rng_seed)? {
+            self.server.rng_seed = Some(rng_seed);
+        }
+        env_override("FLIGHT_SERVER_HEARTBEAT_INTERVAL", "server.heartbeat_interval", &mut self.server.heartbeat_interval)?;
+        let mut seed_file = String::new();
+        if env_override("FLIGHT_SERVER_SEED_FILE", "server.seed_file", &mut seed_file)? {
+            self.server.seed_file = Some(seed_file);
+        }
+        env_override("FLIGHT_CLIENT_TIMEOUT", "client.timeout", &mut self.client.timeout)?;
+        env_override("FLIGHT_CLIENT_RETRY", "client.retry", &mut self.client.retry)?;
+        env_override("FLIGHT_CLIENT_INVOCATION_SEMANTIC", "client.invocation_semantic", &mut self.client.invocation_semantic)?;
+        env_override("FLIGHT_CLIENT_BYTE_ORDER", "client.byte_order", &mut self.client.byte_order)?;
+        env_override("FLIGHT_CLIENT_CACHE_SIZE", "client.cache_size", &mut self.client.cache_size)?;
+        env_override("FLIGHT_CLIENT_CACHE_TTL", "client.cache_ttl", &mut self.client.cache_ttl)?;
+        env_override("FLIGHT_CLIENT_BACKOFF_BASE_MS", "client.backoff_base_ms", &mut self.client.backoff_base_ms)?;
+        env_override("FLIGHT_CLIENT_BACKOFF_MAX_MS", "client.backoff_max_ms", &mut self.client.backoff_max_ms)?;
+        let mut rng_seed = 0;
+        if env_override("FLIGHT_CLIENT_RNG_SEED", "client.rng_seed", &mut rng_seed)? {
+            self.client.rng_seed = Some(rng_seed);
+        }
+        env_override("FLIGHT_CLIENT_FORCE_DUPLICATE", "client.force_duplicate", &mut self.client.force_duplicate)?;
+        let timeouts = &mut self.client.timeouts;
+        for (name, field, target) in [
+            ("FLIGHT_CLIENT_TIMEOUTS_QUERY", "client.timeouts.query", &mut timeouts.query),
+            ("FLIGHT_CLIENT_TIMEOUTS_DETAILS", "client.timeouts.details", &mut timeouts.details),
+            ("FLIGHT_CLIENT_TIMEOUTS_RESERVE", "client.timeouts.reserve", &mut timeouts.reserve),
+            ("FLIGHT_CLIENT_TIMEOUTS_CANCEL", "client.timeouts.cancel", &mut timeouts.cancel),
+            ("FLIGHT_CLIENT_TIMEOUTS_MONITOR", "client.timeouts.monitor", &mut timeouts.monitor),
+            ("FLIGHT_CLIENT_TIMEOUTS_UNMONITOR", "client.timeouts.unmonitor", &mut timeouts.unmonitor),
+        ] {
+            let mut timeout = 0;
+            if env_override(name, field, &mut timeout)? {
+                *target = Some(timeout);
+            }
+        }
+        Ok(())
+    }
+
+    // 检查配置值是否合法,返回的错误指明出错的字段
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.server.address.parse::<SocketAddr>().is_err() {
+            return Err(ConfigError::new("server.address", format!("{:?} is not a valid socket address", self.server.address)));
+        }
+        if !(0.0..=1.0).contains(&self.server.loss_rate) {
+            return Err(ConfigError::new("server.loss_rate", format!("must be within 0.0..=1.0, got {}", self.server.loss_rate)));
+        }
+        if !(0.0..=1.0).contains(&self.server.request_loss_rate) {
+            return Err(ConfigError::new("server.request_loss_rate", format!("must be within 0.0..=1.0, got {}", self.server.request_loss_rate)));
+        }
+        if !BYTE_ORDERS.contains(&self.server.byte_order.as_str()) {
+            return Err(ConfigError::new("server.byte_order", format!("unknown byte order {:?}", self.server.byte_order)));
+        }
+        if self.server.workers < 1 {
+            return Err(ConfigError::new("server.workers", "must be at least 1".to_string()));
+        }
+        if self.server.requests_per_sec.is_nan() || self.server.requests_per_sec < 0.0 {
+            return Err(ConfigError::new("server.requests_per_sec", format!("must not be negative, got {}", self.server.requests_per_sec)));
+        }
+        if self.server.burst < 1 {
+            return Err(ConfigError::new("server.burst", "must be at least 1".to_string()));
+        }
+        if self.client.retry < 1 {
+            return Err(ConfigError::new("client.retry", "must be at least 1".to_string()));
+        }
+        if !INVOCATION_SEMANTICS.contains(&self.client.invocation_semantic.as_str()) {
+            return Err(ConfigError::new(
+                "client.invocation_semantic",
+                format!("unknown invocation semantic {:?}, expected one of {:?}", self.client.invocation_semantic, INVOCATION_SEMANTICS),
+            ));
+        }
+        if self.client.backoff_max_ms < self.client.backoff_base_ms {
+            return Err(ConfigError::new("client.backoff_max_ms", "must not be less than client.backoff_base_ms".to_string()));
+        }
+        if !BYTE_ORDERS.contains(&self.client.byte_order.as_str()) {
+            return Err(ConfigError::new("client.byte_order", format!("unknown byte order {:?}", self.client.byte_order)));
+        }
+        Ok(())
+    }
+
+    // 重新读取配置文件并替换当前配置
+    // 新配置完整解析并校验通过后才一次性替换,失败时当前配置保持不变
+    // 需要&mut self,多线程共享时由调用方加锁(如Mutex<Config>)
+    pub fn reload(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let path = self.source.clone().unwrap_or_else(Self::path);
+        *self = Self::load_from(path)?;
+        Ok(())
+    }
+
+    // 启动后台线程,按interval轮询配置文件的修改时间,文件变化时重新加载并调用on_change
+    // on_change在后台线程中执行,因此必须是Send;与其他线程共享状态时需使用Arc<Mutex<..>>等同步手段
+    // 新文件加载失败时只记录日志,不调用on_change
+    pub fn watch<F>(&self, interval: Duration, mut on_change: F) -> thread::JoinHandle<()>
+    where
+        F: FnMut(Config) + Send + 'static,
+    {
+        let path = self.source.clone().unwrap_or_else(Self::path);
+        let modified = |path: &Path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+        let mut last_modified = modified(&path);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let current = modified(&path);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+            match Self::load_from(&path) {
+                Ok(config) => {
+                    tracing::info!("Reloaded config from {}", path.display());
+                    on_change(config);
+                }
+                Err(e) => tracing::warn!("Failed to reload config: {}", e),
+            }
+        })
+    }
+
+    // 配置文件路径,FLIGHT_CONFIG环境变量优先
+    fn path() -> PathBuf {
+        env::var_os("FLIGHT_CONFIG").map_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH), PathBuf::from)
+    }
+
+    // 从指定路径加载配置
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+
+        // 读取配置文件内容,失败时在错误信息中带上尝试的路径
+        let config_text = fs::read_to_string(path)
+            .map_err(|e| format!("failed to read config file {}: {}", path.display(), e))?;
+
         // 使用toml crate将文本解析为Config结构体
         // 同样使用?运算符处理可能的错误
-        let config: Config = toml::from_str(&config_text)?;
-        
+        let mut config: Config = toml::from_str(&config_text)?;
+        config.source = Some(path.to_path_buf());
+
+        // 环境变量覆盖文件中的配置
+        config.apply_env_overrides()?;
+
+        // 检查配置值是否合法
+        config.validate()?;
+
         // 如果一切正常,返回解析后的Config实例
         Ok(config)
     }
@@ -52,5 +416,227 @@ fn main() {
     // 可以在这里添加代码来测试Config的加载和使用
 }
 
-// 注意: 这个文件假定在同一目录下存在一个名为"config.toml"的配置文件
+// 注意: 这个文件假定在同一目录下存在一个名为"config.toml"的配置文件,
+// 或者通过FLIGHT_CONFIG环境变量指定配置文件路径
 // 该文件应包含与Config和ServerConfig结构体匹配的TOML格式数据
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 校验失败时返回出错的配置项名称
+    fn invalid_field(config: &Config) -> String {
+        config.validate().unwrap_err().field.to_string()
+    }
+
+    // 加载配置时会读取FLIGHT_*环境变量,设置环境变量的测试和加载配置的测试需要持有此锁,避免并行时互相影响
+    static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    fn env_lock() -> std::sync::MutexGuard<'static, ()> {
+        ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    // 将text写入临时配置文件并加载,name用于区分各个测试的文件
+    fn load_text(name: &str, text: &str) -> Result<Config, String> {
+        let _env = env_lock();
+        let path = env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let config = Config::load_from(&path).map_err(|e| e.to_string());
+        fs::remove_file(&path).unwrap();
+        config
+    }
+
+    #[test]
+    fn requests_per_sec_must_be_a_non_negative_number() {
+        let mut config = Config::default();
+        config.server.requests_per_sec = 0.0;
+        assert!(config.validate().is_ok());
+        for requests_per_sec in [-1.0, f64::NAN] {
+            config.server.requests_per_sec = requests_per_sec;
+            assert_eq!(invalid_field(&config), "server.requests_per_sec");
+        }
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn each_invalid_value_names_its_field() {
+        let cases: [(&str, fn(&mut Config)); 11] = [
+            ("server.address", |config| config.server.address = "localhost".to_string()),
+            ("server.loss_rate", |config| config.server.loss_rate = 1.5),
+            ("server.request_loss_rate", |config| config.server.request_loss_rate = -0.1),
+            ("server.byte_order", |config| config.server.byte_order = "middle".to_string()),
+            ("server.workers", |config| config.server.workers = 0),
+            ("server.requests_per_sec", |config| config.server.requests_per_sec = -5.0),
+            ("server.burst", |config| config.server.burst = 0),
+            ("client.retry", |config| config.client.retry = 0),
+            ("client.invocation_semantic", |config| config.client.invocation_semantic = "exactly-once".to_string()),
+            ("client.backoff_max_ms", |config| config.client.backoff_max_ms = config.client.backoff_base_ms - 1),
+            ("client.byte_order", |config| config.client.byte_order = "BIG".to_string()),
+        ];
+        for (field, make_invalid) in cases {
+            let mut config = Config::default();
+            make_invalid(&mut config);
+            assert_eq!(invalid_field(&config), field);
+        }
+    }
+
+    #[test]
+    fn explicitly_named_config_file_must_exist() {
+        let _env = env_lock();
+        let path = env::temp_dir().join(format!("missing-config-{}.toml", std::process::id()));
+        let error = Config::load_explicit_or_default(Some(path.clone()), Path::new(DEFAULT_CONFIG_PATH)).err().expect("a missing config file should be an error");
+        assert!(error.to_string().contains(&path.display().to_string()), "{}", error);
+    }
+
+    #[test]
+    fn explicitly_named_config_file_is_loaded() {
+        let _env = env_lock();
+        let path = env::temp_dir().join(format!("config-{}.toml", std::process::id()));
+        fs::write(&path, "[server]\naddress = \"127.0.0.1:9999\"\n").unwrap();
+        let config = Config::load_explicit_or_default(Some(path.clone()), Path::new(DEFAULT_CONFIG_PATH));
+        fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+        assert_eq!(config.server.address, "127.0.0.1:9999");
+        assert_eq!(config.source, Some(path));
+    }
+
+    #[test]
+    fn config_with_server_and_client_sections_is_loaded() {
+        let config = load_text(
+            "both-sections",
+            "[server]\naddress = \"127.0.0.1:8080\"\n\n[client]\ntimeout = 5\nretry = 2\ninvocation_semantic = \"at-least-once\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.server.address, "127.0.0.1:8080");
+        assert_eq!((config.client.timeout, config.client.retry), (5, 2));
+        assert_eq!(config.client.invocation_semantic, "at-least-once");
+    }
+
+    #[test]
+    fn server_only_config_gets_client_defaults() {
+        let config = load_text("server-only", "[server]\naddress = \"0.0.0.0:8888\"\n").unwrap();
+        let defaults = ClientConfig::default();
+        assert_eq!((config.client.timeout, config.client.retry), (defaults.timeout, defaults.retry));
+        assert_eq!(config.client.invocation_semantic, defaults.invocation_semantic);
+    }
+
+    #[test]
+    fn loss_rate_is_read_and_defaults_to_zero() {
+        let with_loss = load_text("loss-rate", "[server]\naddress = \"127.0.0.1:8080\"\nloss_rate = 0.3\n").unwrap();
+        assert_eq!(with_loss.server.loss_rate, 0.3);
+        let without_loss = load_text("no-loss-rate", "[server]\naddress = \"127.0.0.1:8080\"\n").unwrap();
+        assert_eq!(without_loss.server.loss_rate, 0.0);
+
+        let error = load_text("bad-loss-rate", "[server]\naddress = \"127.0.0.1:8080\"\nloss_rate = 1.5\n").err().unwrap();
+        assert!(error.contains("server.loss_rate"), "{}", error);
+    }
+
+    #[test]
+    fn missing_default_config_file_falls_back_to_defaults() {
+        let _env = env_lock();
+        let path = env::temp_dir().join(format!("no-config-{}.toml", std::process::id()));
+        let config = Config::load_explicit_or_default(None, &path).unwrap();
+        assert_eq!(config.server.address, "127.0.0.1:8080");
+        assert_eq!(config.server.loss_rate, 0.0);
+        assert_eq!(config.client.timeout, ClientConfig::default().timeout);
+        assert!(config.source.is_none());
+    }
+
+    #[test]
+    fn malformed_config_file_is_an_error_even_at_the_default_path() {
+        let _env = env_lock();
+        let path = env::temp_dir().join(format!("malformed-config-{}.toml", std::process::id()));
+        fs::write(&path, "[server\naddress = \"127.0.0.1:8080\"\n").unwrap();
+        let at_default_path = Config::load_explicit_or_default(None, &path).err();
+        let named = Config::load_explicit_or_default(Some(path.clone()), Path::new(DEFAULT_CONFIG_PATH)).err();
+        fs::remove_file(&path).unwrap();
+        assert!(at_default_path.is_some(), "a malformed config.toml should not fall back to defaults");
+        assert!(named.is_some());
+    }
+
+    // 仅包含服务器地址和丢包率的配置文件内容
+    fn with_loss_rate(loss_rate: &str) -> String {
+        format!("[server]\naddress = \"127.0.0.1:8080\"\nloss_rate = {}\n", loss_rate)
+    }
+
+    #[test]
+    fn reload_picks_up_the_changed_file_and_keeps_the_old_config_on_error() {
+        let _env = env_lock();
+        let path = env::temp_dir().join(format!("reload-config-{}.toml", std::process::id()));
+        fs::write(&path, with_loss_rate("0.1")).unwrap();
+        let mut config = Config::load_from(&path).unwrap();
+
+        fs::write(&path, with_loss_rate("0.5")).unwrap();
+        config.reload().unwrap();
+        assert_eq!(config.server.loss_rate, 0.5);
+
+        fs::write(&path, with_loss_rate("2.0")).unwrap();
+        let reloaded = config.reload();
+        fs::remove_file(&path).unwrap();
+        assert!(reloaded.is_err());
+        assert_eq!(config.server.loss_rate, 0.5);
+    }
+
+    #[test]
+    fn watch_calls_back_with_the_changed_config() {
+        let _env = env_lock();
+        let path = env::temp_dir().join(format!("watch-config-{}.toml", std::process::id()));
+        fs::write(&path, with_loss_rate("0.1")).unwrap();
+        let config = Config::load_from(&path).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        config.watch(Duration::from_millis(10), move |config| {
+            let _ = sender.send(config.server.loss_rate);
+        });
+
+        // 等待监视线程记录初始修改时间后再修改文件
+        thread::sleep(Duration::from_millis(50));
+        fs::write(&path, with_loss_rate("0.7")).unwrap();
+        let loss_rate = receiver.recv_timeout(Duration::from_secs(5));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loss_rate, Ok(0.7));
+    }
+
+    #[test]
+    fn environment_variables_override_the_file() {
+        let _env = env_lock();
+        env::set_var("FLIGHT_SERVER_ADDRESS", "0.0.0.0:9000");
+        env::set_var("FLIGHT_CLIENT_TIMEOUT", "30");
+        env::set_var("FLIGHT_CLIENT_TIMEOUTS_MONITOR", "120");
+        let path = env::temp_dir().join(format!("env-config-{}.toml", std::process::id()));
+        fs::write(&path, "[server]\naddress = \"127.0.0.1:8080\"\nloss_rate = 0.2\n\n[client]\ntimeout = 5\nretry = 2\ninvocation_semantic = \"at-most-once\"\n").unwrap();
+        let config = Config::load_from(&path);
+        env::remove_var("FLIGHT_SERVER_ADDRESS");
+        env::remove_var("FLIGHT_CLIENT_TIMEOUT");
+        env::remove_var("FLIGHT_CLIENT_TIMEOUTS_MONITOR");
+        fs::remove_file(&path).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(config.server.address, "0.0.0.0:9000");
+        assert_eq!(config.server.loss_rate, 0.2);
+        assert_eq!(config.client.timeout, 30);
+        assert_eq!(config.client.retry, 2);
+        assert_eq!(config.client.timeouts.monitor, Some(120));
+    }
+
+    #[test]
+    fn unparseable_or_invalid_environment_values_are_errors() {
+        let _env = env_lock();
+        env::set_var("FLIGHT_SERVER_LOSS_RATE", "lots");
+        let unparseable = Config::default().apply_env_overrides().err();
+        env::set_var("FLIGHT_SERVER_LOSS_RATE", "1.5");
+        let mut config = Config::default();
+        let applied = config.apply_env_overrides();
+        env::remove_var("FLIGHT_SERVER_LOSS_RATE");
+
+        let error = unparseable.expect("an unparseable loss rate should be an error");
+        assert_eq!(error.field, "server.loss_rate");
+        assert!(error.message.contains("FLIGHT_SERVER_LOSS_RATE"), "{}", error);
+        // 能解析但超出范围的值由validate拒绝
+        assert!(applied.is_ok());
+        assert_eq!(invalid_field(&config), "server.loss_rate");
+    }
+}
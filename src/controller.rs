@@ -1,173 +1,603 @@
 use std::collections::{HashMap, HashSet};
+use std::sync::{Mutex, RwLock, RwLockReadGuard};
 use std::time::{Duration, Instant};
 
-use std::net::UdpSocket;
-mod serialization;
-use serialization::{ByteOrder,  Serializer};
+use std::net::{SocketAddr, UdpSocket};
+use chrono::NaiveDateTime;
 
-mod flight_models;
-pub use flight_models::{Flight, Request, Response, FlightUpdate, MonitoringClient};
+use crate::serialization::{ByteOrder, Serializer};
+
+pub mod flight_models;
+pub use flight_models::{Flight, FlightDetailsRecord, FlightStats, Request, Response, FlightUpdate, MonitoringClient};
+
+/// Error message for requests naming a flight that doesn't exist
+pub const FLIGHT_NOT_FOUND: &str = "Flight not found";
+
+/// Error message for flight queries that match nothing
+pub const NO_MATCHING_FLIGHTS: &str = "No matching flights found";
+
+/// Error message for per-client requests that arrive without a client address
+pub const MISSING_CLIENT_ADDRESS: &str = "Missing client address";
+
+/// How long a fulfilled reservation's idempotency key is remembered by default
+const DEFAULT_IDEMPOTENCY_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Allowed monitoring durations in seconds (up to one day)
+const MONITOR_INTERVAL_RANGE: std::ops::RangeInclusive<i32> = 1..=86400;
+
+/// Destination for updates pushed to monitoring clients
+///
+/// The controller decides who gets which update; the sink decides how it is delivered.
+pub trait UpdateSink {
+    /// Delivers one update to the monitoring client at `addr`
+    fn send_update(&self, addr: SocketAddr, update: &FlightUpdate);
+}
+
+/// Sends updates over UDP as a serialized map with action "5"
+pub struct UdpSink<'a> {
+    socket: &'a UdpSocket,
+    byte_order: ByteOrder,
+}
+
+impl<'a> UdpSink<'a> {
+    /// Creates a sink sending from `socket`, serializing in `byte_order`
+    pub fn new(socket: &'a UdpSocket, byte_order: ByteOrder) -> Self {
+        Self { socket, byte_order }
+    }
+}
+
+impl UpdateSink for UdpSink<'_> {
+    fn send_update(&self, addr: SocketAddr, update: &FlightUpdate) {
+        let mut serializer = Serializer::new(self.byte_order);
+        let mut map = HashMap::new();
+        map.insert("action".to_string(), "5".to_string());
+        map.insert("flight_id".to_string(), update.flight_id.to_string());
+        map.insert("seats_available".to_string(), update.seats_available.to_string());
+        map.insert("airfare".to_string(), update.airfare.to_string());
+        map.insert("departure_time".to_string(), update.departure_time.to_string());
+        serializer.serialize_map(&map).unwrap();
+
+        if let Err(e) = self.socket.send_to(&serializer.get_buffer(), addr) {
+            tracing::error!("Failed to send update to {:?}: {}", addr, e);
+        }
+    }
+}
 
 /// FlightController manages all flight-related operations and client monitoring
+///
+/// All state sits behind `RwLock`s so the controller can be shared between threads via `Arc`.
+/// When several locks are needed, `flights` is always locked first.
 pub struct FlightController {
     /// Stores all flights, indexed by their flight ID
-    pub flights: HashMap<i32, Flight>,
-    /// Stores monitoring clients for each flight, indexed by flight ID
-    monitoring_clients: HashMap<i32, HashSet<MonitoringClient>>,
+    flights: RwLock<HashMap<i32, Flight>>,
+    /// Stores monitoring subscriptions, indexed by flight ID and client address
+    monitoring_clients: RwLock<HashMap<(i32, SocketAddr), MonitoringClient>>,
+    /// Idempotency keys of fulfilled reservations per flight ID, with the time they were fulfilled
+    /// and the overbooked seat count the reservation returned, which a repeat of the key gets again
+    reservation_keys: Mutex<HashMap<i32, HashMap<String, (Instant, i32)>>>,
+    /// How long a fulfilled idempotency key is remembered
+    idempotency_ttl: Duration,
+    /// Byte order used to serialize updates pushed to monitoring clients
+    byte_order: ByteOrder,
+    /// Where flight updates are pushed; `None` sends them over the socket passed to each call
+    update_sink: Option<Box<dyn UpdateSink + Send + Sync>>,
+}
+
+impl Default for FlightController {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl FlightController {
     /// Creates a new FlightController instance
     pub fn new() -> Self {
         Self {
-            flights: HashMap::new(),
-            monitoring_clients: HashMap::new(),
+            flights: RwLock::new(HashMap::new()),
+            monitoring_clients: RwLock::new(HashMap::new()),
+            reservation_keys: Mutex::new(HashMap::new()),
+            idempotency_ttl: DEFAULT_IDEMPOTENCY_TTL,
+            byte_order: ByteOrder::Little,
+            update_sink: None,
         }
     }
 
-    /// Handles incoming client requests and returns appropriate responses
-    pub fn handle_request(&mut self, request: Request, socket: &UdpSocket, client_addr: Option<std::net::SocketAddr>) -> Response {
-        // Clean expired monitors at the beginning of each request
-        self.clean_expired_monitors();
+    /// Sets the byte order used to serialize monitoring updates
+    #[allow(dead_code)]
+    pub fn set_byte_order(&mut self, byte_order: ByteOrder) {
+        self.byte_order = byte_order;
+    }
+
+    /// Pushes flight updates through `sink` instead of sending them over the request socket
+    #[allow(dead_code)]
+    pub fn set_update_sink(&mut self, sink: Box<dyn UpdateSink + Send + Sync>) {
+        self.update_sink = Some(sink);
+    }
 
+    /// Sets how long fulfilled reservation idempotency keys are remembered
+    #[allow(dead_code)]
+    pub fn set_idempotency_ttl(&mut self, ttl: Duration) {
+        self.idempotency_ttl = ttl;
+    }
+
+    /// Handles incoming client requests and returns appropriate responses
+    ///
+    /// Expired monitors are not swept here, which would take the monitors write lock on every request;
+    /// the server calls `clean_expired_monitors` periodically instead.
+    pub fn handle_request(&self, request: Request, socket: &UdpSocket, client_addr: Option<SocketAddr>) -> Response {
         match request {
-            Request::QueryFlightIds { source, destination } => {
-                let ids = self.query_flight_ids(&source, &destination);
+            Request::QueryFlightIds { source, destination, max_fare } => {
+                let ids = self.query_flight_ids(&source, &destination, max_fare);
+                if ids.is_empty() {
+                    Response::Error(NO_MATCHING_FLIGHTS.to_string())
+                } else {
+                    Response::FlightIds(ids)
+                }
+            }
+            Request::QueryFlightsByDate { source, destination, from, to } => {
+                let ids = self.query_flights_by_date(&source, &destination, from, to);
                 if ids.is_empty() {
-                    Response::Error("No matching flights found".to_string())
+                    Response::Error(NO_MATCHING_FLIGHTS.to_string())
                 } else {
                     Response::FlightIds(ids)
                 }
             }
             Request::QueryFlightDetails { flight_id } => {
-                if let Some(flight) = self.flights.get(&flight_id) {
+                if let Some(flight) = self.flights.read().unwrap().get(&flight_id) {
                     Response::FlightDetails {
                         departure_time: Some(flight.departure_time),
                         airfare: Some(flight.airfare),
                         seats_available: Some(flight.seats_available),
                     }
                 } else {
-                    Response::Error("Flight not found".to_string())
+                    Response::Error(FLIGHT_NOT_FOUND.to_string())
                 }
             }
-            Request::ReserveSeats { flight_id, seats } => {
-                let result = self.reserve_seats(flight_id, seats);
+            Request::QueryFlightDetailsBatch { flight_ids } => {
+                let flights = self.flights.read().unwrap();
+                let details = flight_ids
+                    .into_iter()
+                    .map(|flight_id| {
+                        let record = flights
+                            .get(&flight_id)
+                            .map(|flight| FlightDetailsRecord {
+                                departure_time: flight.departure_time,
+                                airfare: flight.airfare,
+                                seats_available: flight.seats_available,
+                            })
+                            .ok_or_else(|| FLIGHT_NOT_FOUND.to_string());
+                        (flight_id, record)
+                    })
+                    .collect();
+                Response::FlightDetailsBatch(details)
+            }
+            Request::ReserveSeats { flight_id, seats, idempotency_key } => {
+                let result = self.reserve_seats(flight_id, seats, idempotency_key.as_deref());
                 match result {
-                    Ok(_) => {
-                        let updates = self.prepare_monitoring_updates(flight_id);
-                        if !updates.is_empty() {
-                            println!("Callback Triggered {:?}", updates);
-                        }
-
-                        // Send updates to monitoring clients
-                        for (client_addr, update) in updates {
-                            if update.flight_id == flight_id && seats > 0 {
-                                tracing::info!("Sending Update to {:?}", client_addr);
-
-                                // Serialize the update data
-                                let mut serializer = Serializer::new(ByteOrder::Little);
-                                let mut map = HashMap::new();
-                                map.insert("action".to_string(), "5".to_string());
-                                map.insert("flight_id".to_string(), flight_id.to_string());
-                                map.insert("seats_available".to_string(), update.seats_available.to_string());
-                                serializer.serialize_map(&map).unwrap();
-                                let serialized_data = serializer.get_buffer();
-
-                                // Send the serialized data to the client
-                                socket.send_to(&serialized_data, client_addr).unwrap();
-                            }
-                        }
-                        Response::Reservation(Ok(()))
+                    // Seats changed, tell monitoring clients
+                    Ok((overbooked, true)) => {
+                        self.broadcast_update(flight_id, socket);
+                        Response::Reservation(Ok(overbooked))
                     }
+                    // Repeated idempotency key, nothing was reserved this time
+                    Ok((overbooked, false)) => Response::Reservation(Ok(overbooked)),
                     Err(e) => Response::Reservation(Err(e))
                 }
             }
-            Request::MonitorFlight { flight_id, monitor_interval } => {
-                let monitor_result = self.start_monitoring(flight_id, monitor_interval.try_into().unwrap(), client_addr.unwrap());
+            Request::ReserveMulti { items } => {
+                let result = self.reserve_multi(&items);
+                if result.is_ok() {
+                    let flight_ids: HashSet<i32> = items.iter().map(|(flight_id, _)| *flight_id).collect();
+                    for flight_id in flight_ids {
+                        self.broadcast_update(flight_id, socket);
+                    }
+                }
+                Response::MultiReservation(result)
+            }
+            Request::CancelReservation { flight_id, seats } => {
+                let result = self.cancel_reservation(flight_id, seats);
+                if result.is_ok() {
+                    self.broadcast_update(flight_id, socket);
+                }
+                Response::Cancellation(result)
+            }
+            Request::MonitorFlight { flight_id, monitor_interval, threshold } => {
+                let monitor_result = self.start_monitoring(flight_id, monitor_interval, threshold, client_addr.unwrap());
                 match monitor_result {
                     Ok(_) => Response::MonitoringStarted(Ok(())),
                     Err(e) => Response::MonitoringStarted(Err(e))
                 }
             }
+            Request::Unmonitor { flight_id } => {
+                let Some(client_addr) = client_addr else {
+                    return Response::Error(MISSING_CLIENT_ADDRESS.to_string());
+                };
+                self.stop_monitoring(flight_id, client_addr);
+                Response::MonitoringStopped(Ok(()))
+            }
+            Request::Stats => Response::Stats(self.stats()),
+            Request::ListMyMonitors => match client_addr {
+                Some(client_addr) => Response::MyMonitors(self.list_monitors(client_addr)),
+                None => Response::Error(MISSING_CLIENT_ADDRESS.to_string()),
+            },
+            Request::ResetFlight { flight_id } => {
+                let result = self.reset_flight(flight_id);
+                if result.is_ok() {
+                    self.broadcast_update(flight_id, socket);
+                }
+                Response::FlightReset(result)
+            }
         }
     }
 
-    /// Queries flight IDs based on source and destination
-    fn query_flight_ids(&self, source: &str, destination: &str) -> Vec<i32> {
+    /// Queries flight IDs based on source and destination, optionally capped at `max_fare` cents (inclusive)
+    fn query_flight_ids(&self, source: &str, destination: &str, max_fare: Option<i64>) -> Vec<i32> {
+        self.flights
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, flight)| flight.source == source && flight.destination == destination)
+            .filter(|(_, flight)| max_fare.is_none_or(|max_fare| flight.airfare <= max_fare))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Queries flight IDs based on source and destination, departing between `from` and `to` inclusive
+    fn query_flights_by_date(&self, source: &str, destination: &str, from: NaiveDateTime, to: NaiveDateTime) -> Vec<i32> {
         self.flights
+            .read()
+            .unwrap()
             .iter()
             .filter(|(_, flight)| flight.source == source && flight.destination == destination)
+            .filter(|(_, flight)| flight.departure_time >= from && flight.departure_time <= to)
             .map(|(id, _)| *id)
             .collect()
     }
 
     /// Reserves seats for a given flight
-    fn reserve_seats(&mut self, flight_id: i32, seats: i32) -> Result<(), String> {
-        if let Some(flight) = self.flights.get_mut(&flight_id) {
-            if flight.seats_available >= seats {
+    ///
+    /// A flight may be sold up to `capacity + overbook_limit` seats. On success returns how many of the
+    /// reserved seats went beyond capacity, and whether seats were reserved by this call.
+    ///
+    /// If `idempotency_key` was already fulfilled on this flight within the idempotency TTL, nothing is
+    /// reserved and the original reservation's result is returned with `false`, so a client retrying
+    /// with a fresh request ID can't double-book.
+    fn reserve_seats(&self, flight_id: i32, seats: i32, idempotency_key: Option<&str>) -> Result<(i32, bool), String> {
+        if let Some(flight) = self.flights.write().unwrap().get_mut(&flight_id) {
+            // Checked and recorded under the flights lock so concurrent repeats can't both reserve
+            let mut reservation_keys = self.reservation_keys.lock().unwrap();
+            let now = Instant::now();
+            reservation_keys.retain(|_, keys| {
+                keys.retain(|_, (fulfilled, _)| now.duration_since(*fulfilled) < self.idempotency_ttl);
+                !keys.is_empty()
+            });
+            if let Some(key) = idempotency_key {
+                if let Some((_, overbooked)) = reservation_keys.get(&flight_id).and_then(|keys| keys.get(key)) {
+                    return Ok((*overbooked, false));
+                }
+            }
+
+            if seats < 0 {
+                Err("Number of seats must not be negative".to_string())
+            } else if flight.seats_available + flight.overbook_limit >= seats {
+                // Only the part of this reservation that dips below zero counts as overbooked
+                let overbooked = (seats - flight.seats_available.max(0)).max(0);
                 flight.seats_available -= seats;
-                Ok(())
+                if let Some(key) = idempotency_key {
+                    reservation_keys.entry(flight_id).or_default().insert(key.to_string(), (now, overbooked));
+                }
+                Ok((overbooked, true))
             } else {
                 Err("Not enough seats available".to_string())
             }
         } else {
-            Err("Flight not found".to_string())
+            Err(FLIGHT_NOT_FOUND.to_string())
+        }
+    }
+
+    /// Reserves seats on several flights atomically
+    ///
+    /// Every leg is checked before any seats are taken, all under the flights lock, so either every leg
+    /// is reserved or none is. A flight appearing in several legs must have seats for all of them,
+    /// counting its overbook limit.
+    /// On failure returns the index of the first leg that can't be reserved along with the reason.
+    fn reserve_multi(&self, items: &[(i32, i32)]) -> Result<(), (usize, String)> {
+        let mut flights = self.flights.write().unwrap();
+
+        // Check phase: count seats already claimed by earlier legs of the same flight
+        let mut claimed: HashMap<i32, i32> = HashMap::new();
+        for (leg, &(flight_id, seats)) in items.iter().enumerate() {
+            let flight = flights.get(&flight_id).ok_or_else(|| (leg, FLIGHT_NOT_FOUND.to_string()))?;
+            if seats < 0 {
+                return Err((leg, "Number of seats must not be negative".to_string()));
+            }
+            let claimed = claimed.entry(flight_id).or_insert(0);
+            if flight.seats_available + flight.overbook_limit - *claimed < seats {
+                return Err((leg, "Not enough seats available".to_string()));
+            }
+            *claimed += seats;
+        }
+
+        // Commit phase: every leg fits, so nothing below can fail
+        for (flight_id, seats) in claimed {
+            if let Some(flight) = flights.get_mut(&flight_id) {
+                flight.seats_available -= seats;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns cancelled seats to a flight, failing if more seats are cancelled than were reserved
+    fn cancel_reservation(&self, flight_id: i32, seats: i32) -> Result<(), String> {
+        if let Some(flight) = self.flights.write().unwrap().get_mut(&flight_id) {
+            if seats < 0 {
+                Err("Number of seats must not be negative".to_string())
+            } else if flight.seats_sold() >= seats {
+                flight.seats_available += seats;
+                Ok(())
+            } else {
+                Err("Cannot cancel more seats than were reserved".to_string())
+            }
+        } else {
+            Err(FLIGHT_NOT_FOUND.to_string())
+        }
+    }
+
+    /// Restores a flight's available seats to its capacity and returns the restored count
+    ///
+    /// The flight's idempotency keys are forgotten along with its reservations, so a key reused after
+    /// the reset reserves again instead of replaying a reservation that no longer exists.
+    fn reset_flight(&self, flight_id: i32) -> Result<i32, String> {
+        if let Some(flight) = self.flights.write().unwrap().get_mut(&flight_id) {
+            self.reservation_keys.lock().unwrap().remove(&flight_id);
+            flight.seats_available = flight.capacity;
+            Ok(flight.seats_available)
+        } else {
+            Err(FLIGHT_NOT_FOUND.to_string())
         }
     }
     
     /// Starts monitoring a flight for a client
-    fn start_monitoring(&mut self, flight_id: i32, monitor_interval: i32, client_addr: std::net::SocketAddr) -> Result<(), String> {
-        if self.flights.contains_key(&flight_id) {
+    ///
+    /// A client that is already monitoring the flight has its expiration and threshold replaced,
+    /// so re-subscribing never produces duplicate updates. With a non-zero `threshold` the client is only notified while fewer than `threshold` seats are available.
+    fn start_monitoring(&self, flight_id: i32, monitor_interval: i32, threshold: i32, client_addr: SocketAddr) -> Result<(), String> {
+        if !MONITOR_INTERVAL_RANGE.contains(&monitor_interval) {
+            return Err("Invalid monitor interval".to_string());
+        }
+        if threshold < 0 {
+            return Err("Invalid threshold".to_string());
+        }
+        if self.flights.read().unwrap().contains_key(&flight_id) {
             let expiration_time = Instant::now() + Duration::from_secs(monitor_interval as u64);
             let client = MonitoringClient {
-                addr: client_addr,
                 expiration_time,
+                threshold,
             };
-            self.monitoring_clients
-                .entry(flight_id)
-                .or_insert_with(HashSet::new)
-                .insert(client);
-            tracing::info!("Monitoring Clients {:?}", self.monitoring_clients);
+            let mut monitoring_clients = self.monitoring_clients.write().unwrap();
+            monitoring_clients.insert((flight_id, client_addr), client);
+            tracing::info!("Monitoring Clients {:?}", monitoring_clients);
             Ok(())
         } else {
-            Err("Flight not found".to_string())
+            Err(FLIGHT_NOT_FOUND.to_string())
         }
     }
 
-    /// Prepares updates for monitoring clients of a specific flight
-    fn prepare_monitoring_updates(&self, flight_id: i32) -> Vec<(std::net::SocketAddr, FlightUpdate)> {
+    /// Stops a client's monitoring of a flight; does nothing if the client isn't monitoring it
+    fn stop_monitoring(&self, flight_id: i32, client_addr: SocketAddr) {
+        let mut monitoring_clients = self.monitoring_clients.write().unwrap();
+        monitoring_clients.remove(&(flight_id, client_addr));
+        tracing::info!("Monitoring Clients {:?}", monitoring_clients);
+    }
+
+    /// Lists the flights a client is monitoring with the whole seconds left until each subscription
+    /// expires, ordered by flight ID; subscriptions that have already expired are left out
+    fn list_monitors(&self, client_addr: SocketAddr) -> Vec<(i32, i32)> {
+        let now = Instant::now();
+        let mut monitors: Vec<(i32, i32)> = self.monitoring_clients.read().unwrap()
+            .iter()
+            .filter(|((_, addr), client)| *addr == client_addr && client.expiration_time > now)
+            .map(|((flight_id, _), client)| (*flight_id, (client.expiration_time - now).as_secs() as i32))
+            .collect();
+        monitors.sort_unstable();
+        monitors
+    }
+
+    /// Prepares updates for monitoring clients of a specific flight, skipping clients whose threshold isn't reached
+    /// and clients whose subscription has expired but hasn't been swept yet
+    fn prepare_monitoring_updates(&self, flight_id: i32) -> Vec<(SocketAddr, FlightUpdate)> {
+        let now = Instant::now();
         let mut updates = Vec::new();
-        if let Some(clients) = self.monitoring_clients.get(&flight_id) {
-            if let Some(flight) = self.flights.get(&flight_id) {
-                let update = FlightUpdate {
-                    flight_id,
-                    seats_available: flight.seats_available,
-                };
-                for client in clients {
-                    updates.push((client.addr, update.clone()));
+        let flights = self.flights.read().unwrap();
+        if let Some(flight) = flights.get(&flight_id) {
+            let update = FlightUpdate {
+                flight_id,
+                seats_available: flight.seats_available,
+                airfare: flight.airfare,
+                departure_time: flight.departure_time,
+            };
+            for ((_, addr), client) in self.monitoring_clients.read().unwrap().iter().filter(|((id, _), _)| *id == flight_id) {
+                if client.expiration_time > now && (client.threshold == 0 || flight.seats_available < client.threshold) {
+                    updates.push((*addr, update.clone()));
                 }
             }
         }
         updates
     }
 
-    /// Removes expired monitoring clients
-    fn clean_expired_monitors(&mut self) {
+    /// Changes a flight's airfare (in cents) and/or departure time and pushes the new values to its monitoring clients
+    #[allow(dead_code)]
+    pub fn update_flight(&self, flight_id: i32, new_fare: Option<i64>, new_departure: Option<NaiveDateTime>, socket: &UdpSocket) -> Result<(), String> {
+        {
+            let mut flights = self.flights.write().unwrap();
+            let flight = flights.get_mut(&flight_id).ok_or_else(|| FLIGHT_NOT_FOUND.to_string())?;
+            if let Some(fare) = new_fare {
+                if fare < 0 {
+                    return Err("Airfare must not be negative".to_string());
+                }
+                flight.airfare = fare;
+            }
+            if let Some(departure) = new_departure {
+                flight.departure_time = departure;
+            }
+        }
+        self.clean_expired_monitors(socket);
+        self.broadcast_update(flight_id, socket);
+        Ok(())
+    }
+
+    /// Pushes the current seat availability, airfare and departure time of a flight to all its monitoring clients
+    ///
+    /// Called after every change to a flight, so subscribers see reservations, cancellations and resets alike.
+    /// Updates go through the configured update sink, or over `socket` if none is set.
+    pub fn broadcast_update(&self, flight_id: i32, socket: &UdpSocket) {
+        let updates = self.prepare_monitoring_updates(flight_id);
+        if !updates.is_empty() {
+            tracing::debug!("Callback Triggered {:?}", updates);
+        }
+
+        let udp_sink = UdpSink::new(socket, self.byte_order);
+        let sink: &dyn UpdateSink = match &self.update_sink {
+            Some(sink) => sink.as_ref(),
+            None => &udp_sink,
+        };
+        for (client_addr, update) in updates {
+            tracing::info!("Sending Update to {:?}", client_addr);
+            sink.send_update(client_addr, &update);
+        }
+    }
+
+    /// Pushes the current state of every monitored flight to its monitoring clients, even if nothing changed
+    ///
+    /// Sent periodically so idle subscribers can tell a quiet flight from a dead server.
+    /// Expired monitors are removed first, so they get their "monitoring expired" message instead.
+    #[allow(dead_code)]
+    pub fn send_heartbeat(&self, socket: &UdpSocket) {
+        self.clean_expired_monitors(socket);
+        let flight_ids: HashSet<i32> = self.monitoring_clients.read().unwrap().keys().map(|(flight_id, _)| *flight_id).collect();
+        for flight_id in flight_ids {
+            self.broadcast_update(flight_id, socket);
+        }
+    }
+
+    /// Removes expired monitoring clients, sending each a final "monitoring expired" message (action "9")
+    #[allow(dead_code)]
+    pub fn clean_expired_monitors(&self, socket: &UdpSocket) {
         let now = Instant::now();
-        for clients in self.monitoring_clients.values_mut() {
-            clients.retain(|client| client.expiration_time > now);
+        let mut expired = Vec::new();
+        let mut monitoring_clients = self.monitoring_clients.write().unwrap();
+        monitoring_clients.retain(|key, client| {
+            let active = client.expiration_time > now;
+            if !active {
+                expired.push(*key);
+            }
+            active
+        });
+        drop(monitoring_clients);
+
+        for (flight_id, client_addr) in expired {
+            tracing::info!("Monitoring Expired {:?}", (flight_id, client_addr));
+            let mut serializer = Serializer::new(self.byte_order);
+            let mut map = HashMap::new();
+            map.insert("action".to_string(), "9".to_string());
+            map.insert("flight_id".to_string(), flight_id.to_string());
+            serializer.serialize_map(&map).unwrap();
+            if let Err(e) = socket.send_to(&serializer.get_buffer(), client_addr) {
+                tracing::error!("Failed to notify {:?}: {}", client_addr, e);
+            }
+        }
+    }
+
+    /// Returns a read guard over the flights HashMap; other threads can't modify flights while it is held
+    pub fn flights(&self) -> RwLockReadGuard<'_, HashMap<i32, Flight>> {
+        self.flights.read().unwrap()
+    }
+
+    /// Returns flight count, seat totals and average fare across all flights
+    #[allow(dead_code)]
+    pub fn stats(&self) -> FlightStats {
+        let flights = self.flights.read().unwrap();
+        if flights.is_empty() {
+            return FlightStats::default();
+        }
+        let total_fare: i64 = flights.values().map(|flight| flight.airfare).sum();
+        FlightStats {
+            flights: flights.len() as i32,
+            total_seats: flights.values().map(|flight| flight.capacity as i64).sum(),
+            seats_sold: flights.values().map(|flight| flight.seats_sold() as i64).sum(),
+            average_fare: total_fare / flights.len() as i64,
+        }
+    }
+
+    /// Adds a new flight to the controller, failing if a flight with the same ID already exists
+    pub fn add_flight(&self, flight: Flight) -> Result<(), String> {
+        let mut flights = self.flights.write().unwrap();
+        if flights.contains_key(&flight.flight_id) {
+            return Err(format!("Flight {} already exists", flight.flight_id));
         }
-        self.monitoring_clients.retain(|_, clients| !clients.is_empty());
+        flights.insert(flight.flight_id, flight);
+        Ok(())
     }
 
-    /// Returns a reference to the flights HashMap
-    pub fn flights(&self) -> &HashMap<i32, Flight> {
-        &self.flights
+    /// Adds a flight, replacing any existing flight with the same ID
+    #[allow(dead_code)]
+    pub fn upsert_flight(&self, flight: Flight) -> Option<Flight> {
+        self.flights.write().unwrap().insert(flight.flight_id, flight)
     }
 
-    /// Adds a new flight to the controller
-    pub fn add_flight(&mut self, flight: Flight) {
-        self.flights.insert(flight.flight_id, flight);
+    /// Tells every monitoring client that the server is shutting down (action "10") and drops all subscriptions
+    #[allow(dead_code)]
+    pub fn notify_shutdown(&self, socket: &UdpSocket) {
+        let monitoring_clients = std::mem::take(&mut *self.monitoring_clients.write().unwrap());
+        let mut serializer = Serializer::new(self.byte_order);
+        let mut map = HashMap::new();
+        map.insert("action".to_string(), "10".to_string());
+        map.insert("message".to_string(), "server shutting down".to_string());
+        serializer.serialize_map(&map).unwrap();
+        let serialized_data = serializer.get_buffer();
+
+        let addrs: HashSet<_> = monitoring_clients.keys().map(|(_, addr)| *addr).collect();
+        for addr in addrs {
+            tracing::info!("Sending shutdown notice to {:?}", addr);
+            if let Err(e) = socket.send_to(&serialized_data, addr) {
+                tracing::error!("Failed to notify {:?}: {}", addr, e);
+            }
+        }
+    }
+
+    /// Removes a flight along with its monitoring clients, returning the removed flight
+    #[allow(dead_code)]
+    pub fn remove_flight(&self, flight_id: i32) -> Option<Flight> {
+        let mut flights = self.flights.write().unwrap();
+        self.monitoring_clients.write().unwrap().retain(|(id, _), _| *id != flight_id);
+        flights.remove(&flight_id)
+    }
+
+    /// Removes a flight like `remove_flight`, first pushing a final "cancelled" update to its monitoring clients
+    #[allow(dead_code)]
+    pub fn remove_flight_and_notify(&self, flight_id: i32, socket: &UdpSocket) -> Option<Flight> {
+        self.clean_expired_monitors(socket);
+        let addrs: Vec<_> = self.monitoring_clients.read().unwrap().keys()
+            .filter(|(id, _)| *id == flight_id)
+            .map(|(_, addr)| *addr)
+            .collect();
+        if !addrs.is_empty() {
+            let mut serializer = Serializer::new(self.byte_order);
+            let mut map = HashMap::new();
+            map.insert("action".to_string(), "5".to_string());
+            map.insert("flight_id".to_string(), flight_id.to_string());
+            map.insert("cancelled".to_string(), "true".to_string());
+            serializer.serialize_map(&map).unwrap();
+            let serialized_data = serializer.get_buffer();
+
+            for addr in addrs {
+                tracing::info!("Sending cancellation to {:?}", addr);
+                if let Err(e) = socket.send_to(&serialized_data, addr) {
+                    tracing::error!("Failed to notify {:?}: {}", addr, e);
+                }
+            }
+        }
+        self.remove_flight(flight_id)
     }
 
     // Commented out as it's not currently used
@@ -175,4 +605,499 @@ impl FlightController {
     // fn query_flight_details(&self, flight_id: i32) -> Option<&Flight> {
     //     self.flights.get(&flight_id)
     // }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialization::Value;
+    use std::sync::Arc;
+
+    /// A 100-seat flight, the first sample flight of the demo data
+    fn sample_flight(overbook_limit: i32) -> Flight {
+        Flight {
+            flight_id: 1,
+            source: "New York".to_string(),
+            destination: "London".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-09-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 50000,
+            seats_available: 100,
+            capacity: 100,
+            overbook_limit,
+        }
+    }
+
+    fn controller_with(flight: Flight) -> (FlightController, UdpSocket) {
+        let controller = FlightController::new();
+        controller.add_flight(flight).unwrap();
+        (controller, UdpSocket::bind("127.0.0.1:0").unwrap())
+    }
+
+    fn reserve(controller: &FlightController, socket: &UdpSocket, seats: i32, key: Option<&str>) -> Result<i32, String> {
+        let request = Request::ReserveSeats { flight_id: 1, seats, idempotency_key: key.map(str::to_string) };
+        match controller.handle_request(request, socket, None) {
+            Response::Reservation(result) => result,
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn repeated_idempotency_key_replays_the_original_result() {
+        let (controller, socket) = controller_with(sample_flight(5));
+        reserve(&controller, &socket, 99, None).unwrap();
+
+        // Two of these three seats go beyond capacity
+        assert_eq!(reserve(&controller, &socket, 3, Some("booking-1")), Ok(2));
+        assert_eq!(reserve(&controller, &socket, 3, Some("booking-1")), Ok(2));
+        assert_eq!(controller.flights()[&1].seats_available, -2);
+
+        // A different key is a different reservation
+        assert_eq!(reserve(&controller, &socket, 1, Some("booking-3")), Ok(1));
+        assert_eq!(controller.flights()[&1].seats_available, -3);
+    }
+
+    #[test]
+    fn idempotency_key_is_forgotten_after_the_ttl() {
+        let (mut controller, socket) = controller_with(sample_flight(0));
+        controller.set_idempotency_ttl(Duration::ZERO);
+        assert_eq!(reserve(&controller, &socket, 2, Some("booking-1")), Ok(0));
+        assert_eq!(reserve(&controller, &socket, 2, Some("booking-1")), Ok(0));
+        assert_eq!(controller.flights()[&1].seats_available, 96);
+    }
+
+    #[test]
+    fn reset_restores_capacity_and_forgets_idempotency_keys() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        assert_eq!(reserve(&controller, &socket, 30, Some("booking-1")), Ok(0));
+
+        let reset = controller.handle_request(Request::ResetFlight { flight_id: 1 }, &socket, None);
+        assert!(matches!(reset, Response::FlightReset(Ok(100))), "reset: {:?}", reset);
+        assert_eq!(controller.flights()[&1].seats_available, 100);
+
+        // The key's reservation was undone by the reset, so reusing it reserves again
+        assert_eq!(reserve(&controller, &socket, 30, Some("booking-1")), Ok(0));
+        assert_eq!(controller.flights()[&1].seats_available, 70);
+    }
+
+    #[test]
+    fn reset_of_unknown_flight_fails() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        let reset = controller.handle_request(Request::ResetFlight { flight_id: 99 }, &socket, None);
+        assert!(matches!(reset, Response::FlightReset(Err(ref message)) if message == FLIGHT_NOT_FOUND), "reset: {:?}", reset);
+    }
+
+    /// Subscribes a fresh local socket to flight 1, already expired unless `active`
+    fn subscribe(controller: &FlightController, active: bool) -> UdpSocket {
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(2))).unwrap();
+        let expiration_time = if active { Instant::now() + Duration::from_secs(60) } else { Instant::now() };
+        controller.monitoring_clients.write().unwrap()
+            .insert((1, client.local_addr().unwrap()), MonitoringClient { expiration_time, threshold: 0 });
+        client
+    }
+
+    fn receive_message(client: &UdpSocket) -> HashMap<String, Value> {
+        let mut buffer = [0u8; 1024];
+        let len = client.recv(&mut buffer).unwrap();
+        let message = crate::serialization::Deserializer::new(&buffer[..len], ByteOrder::Little).deserialize_next().unwrap();
+        message.as_map().unwrap().clone()
+    }
+
+    fn receive_action(client: &UdpSocket) -> String {
+        receive_message(client)["action"].as_string().unwrap().clone()
+    }
+
+    #[test]
+    fn expired_monitors_get_an_expiry_message_when_swept() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        let expired = subscribe(&controller, false);
+        let active = subscribe(&controller, true);
+
+        // Requests don't sweep, and an expired subscriber is skipped rather than sent the update
+        assert_eq!(reserve(&controller, &socket, 1, None), Ok(0));
+        assert_eq!(controller.monitoring_clients.read().unwrap().len(), 2);
+        assert_eq!(receive_action(&active), "5");
+
+        controller.clean_expired_monitors(&socket);
+        assert_eq!(receive_action(&expired), "9");
+        let remaining: Vec<_> = controller.monitoring_clients.read().unwrap().keys().map(|(_, addr)| *addr).collect();
+        assert_eq!(remaining, vec![active.local_addr().unwrap()]);
+    }
+
+    fn time(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    fn query_by_date(controller: &FlightController, socket: &UdpSocket, from: &str, to: &str) -> Response {
+        let request = Request::QueryFlightsByDate {
+            source: "New York".to_string(),
+            destination: "London".to_string(),
+            from: time(from),
+            to: time(to),
+        };
+        controller.handle_request(request, socket, None)
+    }
+
+    #[test]
+    fn date_range_includes_both_endpoints() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        for (from, to) in [("2024-09-01 10:00:00", "2024-09-05 00:00:00"), ("2024-08-30 00:00:00", "2024-09-01 10:00:00")] {
+            let response = query_by_date(&controller, &socket, from, to);
+            assert!(matches!(response, Response::FlightIds(ref ids) if ids == &[1]), "{}..={}: {:?}", from, to, response);
+        }
+    }
+
+    #[test]
+    fn date_range_just_missing_the_departure_finds_nothing() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        for (from, to) in [("2024-09-01 10:00:01", "2024-09-05 00:00:00"), ("2024-08-30 00:00:00", "2024-09-01 09:59:59")] {
+            let response = query_by_date(&controller, &socket, from, to);
+            assert!(matches!(response, Response::Error(ref message) if message == NO_MATCHING_FLIGHTS), "{}..={}: {:?}", from, to, response);
+        }
+    }
+
+    #[test]
+    fn max_fare_is_an_inclusive_ceiling() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        let query = |max_fare| {
+            let request = Request::QueryFlightIds { source: "New York".to_string(), destination: "London".to_string(), max_fare };
+            controller.handle_request(request, &socket, None)
+        };
+        for max_fare in [None, Some(50000), Some(50001)] {
+            let response = query(max_fare);
+            assert!(matches!(response, Response::FlightIds(ref ids) if ids == &[1]), "max_fare {:?}: {:?}", max_fare, response);
+        }
+        let response = query(Some(49999));
+        assert!(matches!(response, Response::Error(ref message) if message == NO_MATCHING_FLIGHTS), "{:?}", response);
+    }
+
+    fn cancel(controller: &FlightController, socket: &UdpSocket, flight_id: i32, seats: i32) -> Result<(), String> {
+        match controller.handle_request(Request::CancelReservation { flight_id, seats }, socket, None) {
+            Response::Cancellation(result) => result,
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cancel_returns_seats_but_never_more_than_were_sold() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        assert_eq!(reserve(&controller, &socket, 10, None), Ok(0));
+
+        assert_eq!(cancel(&controller, &socket, 1, 4), Ok(()));
+        assert_eq!(controller.flights()[&1].seats_available, 94);
+        assert_eq!(cancel(&controller, &socket, 1, 7), Err("Cannot cancel more seats than were reserved".to_string()));
+        assert_eq!(controller.flights()[&1].seats_available, 94);
+        assert!(cancel(&controller, &socket, 1, -1).is_err());
+        assert_eq!(cancel(&controller, &socket, 1, 6), Ok(()));
+        assert_eq!(controller.flights()[&1].seats_available, 100);
+    }
+
+    #[test]
+    fn cancel_on_unknown_flight_fails() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        assert_eq!(cancel(&controller, &socket, 99, 1), Err(FLIGHT_NOT_FOUND.to_string()));
+    }
+
+    /// Updates pushed to each client address, in the order they were pushed
+    type Recorded = Arc<Mutex<Vec<(SocketAddr, FlightUpdate)>>>;
+
+    /// Collects pushed updates instead of sending them
+    struct RecordingSink(Recorded);
+
+    impl UpdateSink for RecordingSink {
+        fn send_update(&self, addr: SocketAddr, update: &FlightUpdate) {
+            self.0.lock().unwrap().push((addr, update.clone()));
+        }
+    }
+
+    /// Like `controller_with`, with updates recorded rather than sent
+    fn recording_controller(flight: Flight) -> (FlightController, UdpSocket, Recorded) {
+        let (mut controller, socket) = controller_with(flight);
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        controller.set_update_sink(Box::new(RecordingSink(Arc::clone(&updates))));
+        (controller, socket, updates)
+    }
+
+    fn monitor(controller: &FlightController, socket: &UdpSocket, client: SocketAddr, monitor_interval: i32, threshold: i32) -> Result<(), String> {
+        let request = Request::MonitorFlight { flight_id: 1, monitor_interval, threshold };
+        match controller.handle_request(request, socket, Some(client)) {
+            Response::MonitoringStarted(result) => result,
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    fn unmonitor(controller: &FlightController, socket: &UdpSocket, client: SocketAddr) {
+        let stopped = controller.handle_request(Request::Unmonitor { flight_id: 1 }, socket, Some(client));
+        assert!(matches!(stopped, Response::MonitoringStopped(Ok(()))), "{:?}", stopped);
+    }
+
+    #[test]
+    fn updates_stop_after_unmonitor() {
+        let (controller, socket, updates) = recording_controller(sample_flight(0));
+        let client: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        monitor(&controller, &socket, client, 60, 0).unwrap();
+        reserve(&controller, &socket, 1, None).unwrap();
+        assert_eq!(updates.lock().unwrap().len(), 1);
+
+        unmonitor(&controller, &socket, client);
+        reserve(&controller, &socket, 1, None).unwrap();
+        assert_eq!(updates.lock().unwrap().len(), 1);
+
+        // Unsubscribing again is a harmless no-op
+        unmonitor(&controller, &socket, client);
+    }
+
+    #[test]
+    fn unmonitor_without_a_client_address_is_an_error() {
+        let (controller, socket, updates) = recording_controller(sample_flight(0));
+        let client: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        monitor(&controller, &socket, client, 60, 0).unwrap();
+
+        let response = controller.handle_request(Request::Unmonitor { flight_id: 1 }, &socket, None);
+        assert!(matches!(response, Response::Error(ref message) if message == MISSING_CLIENT_ADDRESS), "{:?}", response);
+        reserve(&controller, &socket, 1, None).unwrap();
+        assert_eq!(updates.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn default_controller_starts_empty() {
+        let controller = FlightController::default();
+        assert!(controller.flights().is_empty());
+        assert_eq!(controller.stats(), FlightStats::default());
+    }
+
+    #[test]
+    fn removed_flight_is_not_found_afterwards() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        assert_eq!(controller.remove_flight(1).map(|flight| flight.flight_id), Some(1));
+        assert!(controller.remove_flight(1).is_none());
+
+        let details = controller.handle_request(Request::QueryFlightDetails { flight_id: 1 }, &socket, None);
+        assert!(matches!(details, Response::Error(ref message) if message == FLIGHT_NOT_FOUND), "{:?}", details);
+    }
+
+    #[test]
+    fn removing_a_monitored_flight_tells_its_monitors_and_drops_them() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        let client = subscribe(&controller, true);
+
+        assert!(controller.remove_flight_and_notify(1, &socket).is_some());
+        let message = receive_message(&client);
+        assert_eq!(message["action"], Value::from("5"));
+        assert_eq!(message["cancelled"], Value::from("true"));
+        assert!(controller.monitoring_clients.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn fare_and_departure_changes_reach_subscribers() {
+        let (controller, socket, updates) = recording_controller(sample_flight(0));
+        let client: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        monitor(&controller, &socket, client, 60, 0).unwrap();
+
+        controller.update_flight(1, Some(45000), Some(time("2024-09-02 08:30:00")), &socket).unwrap();
+        let (addr, update) = updates.lock().unwrap()[0].clone();
+        assert_eq!(addr, client);
+        assert_eq!((update.airfare, update.departure_time, update.seats_available), (45000, time("2024-09-02 08:30:00"), 100));
+
+        assert!(controller.update_flight(1, Some(-1), None, &socket).is_err());
+        assert_eq!(controller.flights()[&1].airfare, 45000);
+        assert_eq!(controller.update_flight(99, Some(100), None, &socket), Err(FLIGHT_NOT_FOUND.to_string()));
+        assert_eq!(updates.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn adding_a_flight_id_twice_fails_but_upsert_replaces() {
+        let controller = FlightController::new();
+        controller.add_flight(Flight { flight_id: 0, ..sample_flight(0) }).unwrap();
+        assert_eq!(controller.add_flight(Flight { flight_id: 0, airfare: 1, ..sample_flight(0) }), Err("Flight 0 already exists".to_string()));
+        assert_eq!(controller.flights()[&0].airfare, 50000);
+
+        let replaced = controller.upsert_flight(Flight { flight_id: 0, airfare: 1, ..sample_flight(0) });
+        assert_eq!(replaced.map(|flight| flight.airfare), Some(50000));
+        assert_eq!(controller.flights()[&0].airfare, 1);
+    }
+
+    #[test]
+    fn seats_stay_between_zero_and_capacity_without_overbooking() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        assert_eq!(reserve(&controller, &socket, 30, None), Ok(0));
+        assert_eq!(controller.flights()[&1].seats_sold(), 30);
+
+        assert!(reserve(&controller, &socket, 71, None).is_err());
+        assert_eq!(reserve(&controller, &socket, 70, None), Ok(0));
+        assert_eq!((controller.flights()[&1].seats_available, controller.flights()[&1].seats_sold()), (0, 100));
+
+        assert_eq!(cancel(&controller, &socket, 1, 100), Ok(()));
+        assert!(cancel(&controller, &socket, 1, 1).is_err());
+        assert_eq!(controller.flights()[&1].seats_available, controller.flights()[&1].capacity);
+    }
+
+    #[test]
+    fn concurrent_reservations_never_oversell() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        let reserved: usize = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| scope.spawn(|| (0..20).filter(|_| reserve(&controller, &socket, 1, None).is_ok()).count()))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).sum()
+        });
+        assert_eq!(reserved, 100);
+        assert_eq!(controller.flights()[&1].seats_available, 0);
+    }
+
+    #[test]
+    fn monitor_interval_must_be_between_a_second_and_a_day() {
+        let (controller, socket, _) = recording_controller(sample_flight(0));
+        let client: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        for monitor_interval in [-1, 0, 86401, i32::MAX, i32::MIN] {
+            assert_eq!(monitor(&controller, &socket, client, monitor_interval, 0), Err("Invalid monitor interval".to_string()));
+        }
+        assert!(controller.monitoring_clients.read().unwrap().is_empty());
+        for monitor_interval in [1, 86400] {
+            assert_eq!(monitor(&controller, &socket, client, monitor_interval, 0), Ok(()));
+        }
+    }
+
+    #[test]
+    fn cancel_notifies_subscribers() {
+        let (controller, socket, updates) = recording_controller(sample_flight(0));
+        monitor(&controller, &socket, "127.0.0.1:50001".parse().unwrap(), 60, 0).unwrap();
+        reserve(&controller, &socket, 5, None).unwrap();
+        cancel(&controller, &socket, 1, 2).unwrap();
+        // A failed cancellation changes nothing, so nothing is pushed
+        assert!(cancel(&controller, &socket, 1, 50).is_err());
+
+        let seats: Vec<i32> = updates.lock().unwrap().iter().map(|(_, update)| update.seats_available).collect();
+        assert_eq!(seats, [95, 97]);
+    }
+
+    #[test]
+    fn reservation_pushes_one_update_to_each_subscriber_through_the_sink() {
+        let (controller, socket, updates) = recording_controller(sample_flight(0));
+        let first: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        let second: SocketAddr = "127.0.0.1:50002".parse().unwrap();
+        monitor(&controller, &socket, first, 60, 0).unwrap();
+        monitor(&controller, &socket, second, 60, 0).unwrap();
+        reserve(&controller, &socket, 3, None).unwrap();
+
+        let mut updates: Vec<(SocketAddr, i32)> = updates.lock().unwrap().iter().map(|(addr, update)| (*addr, update.seats_available)).collect();
+        updates.sort_unstable();
+        assert_eq!(updates, [(first, 97), (second, 97)]);
+    }
+
+    #[test]
+    fn threshold_holds_back_updates_until_seats_drop_below_it() {
+        let (controller, socket, updates) = recording_controller(sample_flight(0));
+        monitor(&controller, &socket, "127.0.0.1:50001".parse().unwrap(), 60, 10).unwrap();
+
+        reserve(&controller, &socket, 90, None).unwrap();
+        assert!(updates.lock().unwrap().is_empty(), "10 seats left is not below the threshold");
+        reserve(&controller, &socket, 1, None).unwrap();
+        let seats: Vec<i32> = updates.lock().unwrap().iter().map(|(_, update)| update.seats_available).collect();
+        assert_eq!(seats, [9]);
+    }
+
+    #[test]
+    fn subscribing_twice_replaces_the_subscription() {
+        let (controller, socket, updates) = recording_controller(sample_flight(0));
+        let client: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        monitor(&controller, &socket, client, 600, 0).unwrap();
+        monitor(&controller, &socket, client, 60, 0).unwrap();
+        assert_eq!(controller.monitoring_clients.read().unwrap().len(), 1);
+
+        reserve(&controller, &socket, 1, None).unwrap();
+        assert_eq!(updates.lock().unwrap().len(), 1);
+        // The second subscription's interval replaced the first one's
+        let remaining = controller.list_monitors(client)[0].1;
+        assert!((55..=60).contains(&remaining), "{} seconds left", remaining);
+    }
+
+    fn reserve_multi(controller: &FlightController, socket: &UdpSocket, items: Vec<(i32, i32)>) -> Result<(), (usize, String)> {
+        match controller.handle_request(Request::ReserveMulti { items }, socket, None) {
+            Response::MultiReservation(result) => result,
+            other => panic!("unexpected response {:?}", other),
+        }
+    }
+
+    #[test]
+    fn multi_reservation_with_a_full_second_leg_reserves_nothing() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        controller.add_flight(Flight { flight_id: 2, seats_available: 1, capacity: 1, ..sample_flight(0) }).unwrap();
+
+        assert_eq!(reserve_multi(&controller, &socket, vec![(1, 5), (2, 2)]), Err((1, "Not enough seats available".to_string())));
+        assert_eq!(reserve_multi(&controller, &socket, vec![(1, 5), (99, 1)]), Err((1, FLIGHT_NOT_FOUND.to_string())));
+        // Legs on the same flight share its seats
+        assert_eq!(reserve_multi(&controller, &socket, vec![(1, 60), (1, 50)]), Err((1, "Not enough seats available".to_string())));
+        assert_eq!((controller.flights()[&1].seats_available, controller.flights()[&2].seats_available), (100, 1));
+
+        assert_eq!(reserve_multi(&controller, &socket, vec![(1, 5), (2, 1)]), Ok(()));
+        assert_eq!((controller.flights()[&1].seats_available, controller.flights()[&2].seats_available), (95, 0));
+    }
+
+    #[test]
+    fn batch_details_return_partial_results_in_request_order() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        controller.add_flight(Flight { flight_id: 2, airfare: 30000, seats_available: 50, capacity: 50, ..sample_flight(0) }).unwrap();
+
+        let response = controller.handle_request(Request::QueryFlightDetailsBatch { flight_ids: vec![2, 99, 1] }, &socket, None);
+        let Response::FlightDetailsBatch(details) = response else { panic!("unexpected response {:?}", response) };
+        let record = |airfare, seats_available| Ok(FlightDetailsRecord { departure_time: time("2024-09-01 10:00:00"), airfare, seats_available });
+        assert_eq!(details, vec![(2, record(30000, 50)), (99, Err(FLIGHT_NOT_FOUND.to_string())), (1, record(50000, 100))]);
+    }
+
+    #[test]
+    fn stats_add_up_capacity_sales_and_fares() {
+        let (controller, socket) = controller_with(sample_flight(0));
+        assert_eq!(FlightController::new().stats(), FlightStats::default());
+        controller.add_flight(Flight { flight_id: 2, airfare: 30000, seats_available: 50, capacity: 50, ..sample_flight(0) }).unwrap();
+        reserve(&controller, &socket, 10, None).unwrap();
+        reserve_multi(&controller, &socket, vec![(2, 5)]).unwrap();
+
+        let response = controller.handle_request(Request::Stats, &socket, None);
+        let Response::Stats(stats) = response else { panic!("unexpected response {:?}", response) };
+        assert_eq!(stats, FlightStats { flights: 2, total_seats: 150, seats_sold: 15, average_fare: 40000 });
+    }
+
+    #[test]
+    fn reservation_exactly_at_the_overbook_limit_succeeds() {
+        let (controller, socket) = controller_with(sample_flight(5));
+        assert_eq!(reserve(&controller, &socket, 105, None), Ok(5));
+        assert_eq!(controller.flights()[&1].seats_available, -5);
+        assert_eq!(controller.flights()[&1].seats_sold(), 105);
+        assert_eq!(reserve(&controller, &socket, 1, None), Err("Not enough seats available".to_string()));
+    }
+
+    #[test]
+    fn reservation_over_the_overbook_limit_fails_and_changes_nothing() {
+        let (controller, socket) = controller_with(sample_flight(5));
+        assert_eq!(reserve(&controller, &socket, 106, None), Err("Not enough seats available".to_string()));
+        assert_eq!(controller.flights()[&1].seats_available, 100);
+    }
+
+    #[test]
+    fn list_monitors_shows_only_the_clients_own_subscriptions() {
+        let (controller, socket, _) = recording_controller(sample_flight(0));
+        controller.add_flight(Flight { flight_id: 2, ..sample_flight(0) }).unwrap();
+        let client: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        let other: SocketAddr = "127.0.0.1:50002".parse().unwrap();
+        for (flight_id, monitor_interval, addr) in [(2, 120, client), (1, 60, client), (1, 300, other)] {
+            let request = Request::MonitorFlight { flight_id, monitor_interval, threshold: 0 };
+            assert!(matches!(controller.handle_request(request, &socket, Some(addr)), Response::MonitoringStarted(Ok(()))));
+        }
+
+        let response = controller.handle_request(Request::ListMyMonitors, &socket, Some(client));
+        let Response::MyMonitors(monitors) = response else { panic!("unexpected response {:?}", response) };
+        assert_eq!(monitors.iter().map(|(flight_id, _)| *flight_id).collect::<Vec<_>>(), [1, 2]);
+        assert!((55..=60).contains(&monitors[0].1) && (115..=120).contains(&monitors[1].1), "{:?}", monitors);
+    }
+
+    #[test]
+    fn list_monitors_without_a_client_address_is_an_error() {
+        let (controller, socket, _) = recording_controller(sample_flight(0));
+        let client: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        monitor(&controller, &socket, client, 60, 0).unwrap();
+
+        let response = controller.handle_request(Request::ListMyMonitors, &socket, None);
+        assert!(matches!(response, Response::Error(ref message) if message == MISSING_CLIENT_ADDRESS), "{:?}", response);
+    }
+}
@@ -0,0 +1,1127 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::config::{ClientConfig, Config};
+use crate::controller::{FlightDetailsRecord, FlightStats, Request, Response};
+use crate::response_cache::ResponseCache;
+use crate::serialization::{ByteOrder, Deserializer, Envelope, InvocationSemantic, Serializer, Value};
+
+/// 请求和响应中时间字段的格式
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// 连接检测时等待服务器应答的最长时间
+const PING_DEADLINE: Duration = Duration::from_secs(2);
+
+thread_local! {
+    /// 生成请求ID的随机数生成器,配置了client.rng_seed时在启动时重新播种
+    static RNG: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// 生成随机的请求ID
+fn gen_request_id() -> u64 {
+    RNG.with(|rng| rng.borrow_mut().gen())
+}
+
+/// 第attempt次重试前的等待时间:base每次翻倍,不超过max,并乘以[0.5, 1.0)的随机抖动
+fn backoff_delay(attempt: u32, base: Duration, max: Duration) -> Duration {
+    let exponential = base.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1))).min(max);
+    let jitter = RNG.with(|rng| rng.borrow_mut().gen_range(0.5..1.0));
+    exponential.mul_f64(jitter)
+}
+
+/// 发送数据并等待响应,超时后退避重发,最多尝试 retries 次,返回原始响应字节
+///
+/// 每次尝试各自等待timeout;等待依赖socket的读超时阻塞完成,不会忙等。
+/// is_reply返回false的数据报(如监控推送或之前请求的迟到响应)会被丢弃并继续等待
+fn send_with_retries<F>(socket: &UdpSocket, buf: &[u8], retries: u32, timeout: Duration, backoff_base: Duration, backoff_max: Duration, is_reply: F) -> io::Result<Vec<u8>>
+where
+    F: Fn(&[u8]) -> bool,
+{
+    socket.set_read_timeout(Some(timeout))?;
+    let attempts = retries.max(1);
+    let mut buffer = [0u8; 1024];
+
+    for attempt in 1..=attempts {
+        if attempt > 1 {
+            let delay = backoff_delay(attempt - 1, backoff_base, backoff_max);
+            eprintln!("No response received, resending request in {:?}...", delay);
+            std::thread::sleep(delay);
+        }
+        socket.send(buf)?;
+        loop {
+            match socket.recv(&mut buffer) {
+                Ok(amt) if is_reply(&buffer[..amt]) => return Ok(buffer[..amt].to_vec()),
+                Ok(_) => continue,
+                // 读超时,进行下一次尝试
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    Err(io::Error::new(io::ErrorKind::TimedOut, format!("No response received after {} attempts", attempts)))
+}
+
+/// 将响应字节解码为字段映射,字段值保留服务器发送的类型
+///
+/// 按配置的字节序无法解码、按相反字节序可以解码时,返回说明字节序不一致的错误
+fn decode_response_map(data: &[u8], byte_order: ByteOrder) -> io::Result<HashMap<String, Value>> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut deserializer = Deserializer::new(data, byte_order);
+    let value = match deserializer.deserialize_next() {
+        Ok(value) => value,
+        Err(_) if Deserializer::new(data, byte_order.opposite()).deserialize_next().is_ok() => {
+            return Err(invalid(format!("Byte order mismatch: client uses {} endian, server sent {} endian", byte_order, byte_order.opposite())));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    match value {
+        Value::Map(map) => Ok(map),
+        other => Err(invalid(format!("Expected a map, got {:?}", other))),
+    }
+}
+
+/// 按action选择一次尝试的超时时间:使用[client.timeouts]中对应请求类型的值,未设置时使用client.timeout
+pub fn action_timeout(config: &ClientConfig, action: u8) -> Duration {
+    let timeouts = &config.timeouts;
+    let timeout = match action {
+        1 | 8 => timeouts.query,
+        2 | 13 => timeouts.details,
+        3 | 12 => timeouts.reserve,
+        6 => timeouts.cancel,
+        4 => timeouts.monitor,
+        7 => timeouts.unmonitor,
+        _ => None,
+    };
+    Duration::from_secs(timeout.unwrap_or(config.timeout).into())
+}
+
+/// 创建UDP socket并连接到配置中的服务器,返回socket和解析后的服务器地址
+fn connected_client(config: &Config) -> io::Result<(UdpSocket, SocketAddr)> {
+    let server_addr = config.server.address
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Could not resolve server address {}", config.server.address)))?;
+    // 本地socket需与服务器地址族一致,IPv6服务器(如 "[::1]:8888")需绑定到IPv6通配地址
+    let local_addr = if server_addr.is_ipv6() { "[::]:0" } else { "0.0.0.0:0" };
+    let socket = UdpSocket::bind(local_addr)?;
+    socket.connect(server_addr)?;
+    Ok((socket, server_addr))
+}
+
+/// 往返时间统计:次数、最小、平均和最大值
+#[derive(Debug, Default, Clone)]
+pub struct RttStats {
+    count: u32,
+    total: Duration,
+    min: Option<Duration>,
+    max: Duration,
+}
+
+impl RttStats {
+    /// 记录一次往返时间
+    pub fn record(&mut self, rtt: Duration) {
+        self.count += 1;
+        self.total += rtt;
+        self.min = Some(self.min.map_or(rtt, |min| min.min(rtt)));
+        self.max = self.max.max(rtt);
+    }
+
+    /// 已记录的次数
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn min(&self) -> Option<Duration> {
+        self.min
+    }
+
+    pub fn avg(&self) -> Option<Duration> {
+        (self.count > 0).then(|| self.total / self.count)
+    }
+
+    pub fn max(&self) -> Option<Duration> {
+        (self.count > 0).then_some(self.max)
+    }
+}
+
+impl fmt::Display for RttStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (self.min(), self.avg(), self.max()) {
+            (Some(min), Some(avg), Some(max)) => write!(
+                f,
+                "{} requests, min/avg/max = {:.1}/{:.1}/{:.1}ms",
+                self.count,
+                millis(min),
+                millis(avg),
+                millis(max)
+            ),
+            _ => write!(f, "no requests"),
+        }
+    }
+}
+
+/// 以毫秒表示的时长,保留小数部分
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+/// `FlightClient::follow_updates` 结束监控的原因
+#[derive(Debug, PartialEq, Eq)]
+pub enum MonitorEnd {
+    /// 客户端记录的监控期已过
+    Elapsed,
+    /// 服务器通知监控已到期
+    Expired,
+    /// 服务器正在关闭
+    ServerShutdown,
+}
+
+/// 航班服务客户端:持有连接到服务器的socket、配置和查询缓存,配置只在创建时加载一次
+///
+/// 交互式命令行(`src/bin/client.rs`)只是它的一层包装。其他程序可以通过库目标使用它
+/// (`server::flight_client::FlightClient`),也可以像各个bin一样用`#[path]`引入本模块
+/// (同时引入config、controller、serialization和response_cache模块)。下面的示例在环回地址上
+/// 启动一个服务器并通过客户端调用航班服务:
+///
+/// ```
+/// use std::sync::atomic::Ordering;
+/// use std::thread;
+/// use chrono::NaiveDateTime;
+/// use server::config::Config;
+/// use server::controller::{Flight, Response};
+/// use server::flight_client::FlightClient;
+/// use server::flight_server::Server;
+///
+/// // 在操作系统分配的环回端口上启动服务器
+/// let mut server_config = Config::default();
+/// server_config.server.address = "127.0.0.1:0".to_string();
+/// let server = Server::bind(server_config).unwrap();
+/// server.controller.add_flight(Flight {
+///     flight_id: 0,
+///     source: "New York".to_string(),
+///     destination: "London".to_string(),
+///     departure_time: NaiveDateTime::parse_from_str("2024-08-30 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+///     airfare: 20000,
+///     seats_available: 50,
+///     capacity: 50,
+///     overbook_limit: 0,
+/// }).unwrap();
+/// let server_addr = server.local_addr().unwrap();
+/// let shutdown = server.shutdown_handle();
+///
+/// let (ids, reservation, details) = thread::scope(|s| {
+///     s.spawn(|| server.run().unwrap());
+///     let calls = || -> std::io::Result<_> {
+///         let mut config = Config::default();
+///         config.server.address = server_addr.to_string();
+///         let mut client = FlightClient::new(config)?;
+///         client.set_verbose(false);
+///         client.ping()?;
+///         let ids = client.query_flight_ids("New York", "London", None)?;
+///         Ok((ids, client.reserve_seats(0, 2)?, client.query_flight_details(0)?))
+///     };
+///     // 无论调用是否成功都要关闭服务器,否则scope会一直等待服务器线程
+///     let result = calls();
+///     shutdown.store(true, Ordering::SeqCst);
+///     result
+/// })?;
+///
+/// assert!(matches!(ids, Response::FlightIds(ids) if ids == vec![0]));
+/// assert!(matches!(reservation, Response::Reservation(Ok(0))));
+/// assert!(matches!(details, Response::FlightDetails { seats_available: Some(48), .. }));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+///
+/// 完整的环回示例见 `src/bin/demo_flight_client.rs`
+pub struct FlightClient {
+    socket: UdpSocket,
+    server_addr: SocketAddr,
+    config: Config,
+    byte_order: ByteOrder,
+    // 请求头中的调用语义,创建客户端时从配置解析一次
+    invocation_semantic: InvocationSemantic,
+    cache: ResponseCache,
+    // 是否打印请求和响应,脚本模式下关闭以保持标准输出只有结果
+    verbose: bool,
+    // 上一个请求的request_id,配置了force_duplicate时下一个请求沿用它
+    last_request_id: Option<u64>,
+    // 本次会话的往返时间统计
+    rtt: RttStats,
+}
+
+impl FlightClient {
+    /// 按配置创建socket并连接到服务器;配置了client.rng_seed时用它为请求ID和退避抖动重新播种
+    pub fn new(config: Config) -> io::Result<Self> {
+        let byte_order: ByteOrder = config.client.byte_order.parse()
+            .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let invocation_semantic: InvocationSemantic = config.client.invocation_semantic.parse()
+            .map_err(|e: String| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        if let Some(seed) = config.client.rng_seed {
+            RNG.with(|rng| *rng.borrow_mut() = StdRng::seed_from_u64(seed));
+        }
+        let (socket, server_addr) = connected_client(&config)?;
+        // 查询结果缓存,cache_size为0时不缓存
+        let cache = ResponseCache::new(config.client.cache_size, Duration::from_secs(config.client.cache_ttl));
+        Ok(FlightClient { socket, server_addr, config, byte_order, invocation_semantic, cache, verbose: true, last_request_id: None, rtt: RttStats::default() })
+    }
+
+    /// 向服务器发送心跳请求并返回往返时间,在期限内未收到应答时返回"服务器不可达"错误
+    ///
+    /// 只有request_id与本次心跳相同、状态为200且带有server_time的响应才算应答,
+    /// 其他数据报(如之前请求迟到的响应)被忽略,继续等待到期限为止
+    pub fn ping(&self) -> io::Result<Duration> {
+        let (socket, server_addr) = (&self.socket, self.server_addr);
+        let envelope = Envelope { request_id: gen_request_id(), invocation_semantic: self.invocation_semantic.into(), action: 0 };
+        let request_id = envelope.request_id.to_string();
+
+        let mut serializer = Serializer::new(self.byte_order);
+        envelope.serialize(&mut serializer)?;
+        serializer.serialize_map(&HashMap::<String, String>::new())?;
+        let unreachable = || io::Error::new(io::ErrorKind::NotConnected, format!("server unreachable at {}", server_addr));
+
+        let start = Instant::now();
+        socket.send(&serializer.get_buffer()).map_err(|_| unreachable())?;
+
+        let mut buffer = [0u8; 1024];
+        loop {
+            let remaining = PING_DEADLINE.saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                return Err(unreachable());
+            }
+            socket.set_read_timeout(Some(remaining))?;
+            match socket.recv(&mut buffer) {
+                Ok(len) => {
+                    let is_pong = decode_response_map(&buffer[..len], self.byte_order).is_ok_and(|reply| {
+                        reply.get("request_id").and_then(Value::as_string) == Some(&request_id)
+                            && reply.get("status").and_then(Value::as_string).is_some_and(|status| status == "200")
+                            && reply.contains_key("server_time")
+                    });
+                    if is_pong {
+                        return Ok(start.elapsed());
+                    }
+                }
+                // 超时或端口关闭(ICMP不可达)都视为服务器不可达
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::ConnectionRefused) => return Err(unreachable()),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// 本地socket地址
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// 解析后的服务器地址
+    pub fn server_addr(&self) -> SocketAddr {
+        self.server_addr
+    }
+
+    /// 设置是否打印发送的请求和收到的响应(默认打印)
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    /// 本次会话中成功请求的往返时间统计(不含命中缓存的请求)
+    pub fn rtt_stats(&self) -> &RttStats {
+        &self.rtt
+    }
+
+    /// 查询从source到destination的航班ID,max_fare(分)为可选的票价上限
+    pub fn query_flight_ids(&mut self, source: &str, destination: &str, max_fare: Option<i64>) -> io::Result<Response> {
+        self.send_request(Request::QueryFlightIds {
+            source: source.to_string(),
+            destination: destination.to_string(),
+            max_fare,
+        })
+    }
+
+    /// 查询航班详情
+    pub fn query_flight_details(&mut self, flight_id: i32) -> io::Result<Response> {
+        self.send_request(Request::QueryFlightDetails { flight_id })
+    }
+
+    /// 一次查询多个航班的详情,结果与flight_ids顺序一致,找不到的航班单独返回错误
+    #[allow(dead_code)]
+    pub fn query_flight_details_batch(&mut self, flight_ids: &[i32]) -> io::Result<Response> {
+        self.send_request(Request::QueryFlightDetailsBatch { flight_ids: flight_ids.to_vec() })
+    }
+
+    /// 查询所有航班的统计数据
+    #[allow(dead_code)]
+    pub fn stats(&mut self) -> io::Result<Response> {
+        self.send_request(Request::Stats)
+    }
+
+    /// 查询本客户端当前的监控订阅(航班号, 距离到期的秒数)
+    ///
+    /// 服务器按源地址匹配订阅,所以只能看到用同一个FlightClient(同一个socket)发起的订阅
+    #[allow(dead_code)]
+    pub fn list_my_monitors(&mut self) -> io::Result<Response> {
+        self.send_request(Request::ListMyMonitors)
+    }
+
+    /// 预订座位,成功时返回其中超出容量(超售)的座位数
+    pub fn reserve_seats(&mut self, flight_id: i32, seats: i32) -> io::Result<Response> {
+        self.send_request(Request::ReserveSeats { flight_id, seats, idempotency_key: None })
+    }
+
+    /// 一次预订多个航班的座位(航班号, 座位数),全部成功或全部不预订
+    #[allow(dead_code)]
+    pub fn reserve_multi(&mut self, items: &[(i32, i32)]) -> io::Result<Response> {
+        self.send_request(Request::ReserveMulti { items: items.to_vec() })
+    }
+
+    /// 注册监控航班,之后通过 `follow_updates` 或 `receive_update` 接收服务器推送
+    pub fn monitor_flight(&mut self, flight_id: i32, monitor_interval: i32) -> io::Result<Response> {
+        self.send_request(Request::MonitorFlight { flight_id, monitor_interval, threshold: 0 })
+    }
+
+    /// 注册监控航班,只在剩余座位少于threshold时接收推送
+    #[allow(dead_code)]
+    pub fn monitor_flight_below(&mut self, flight_id: i32, monitor_interval: i32, threshold: i32) -> io::Result<Response> {
+        self.send_request(Request::MonitorFlight { flight_id, monitor_interval, threshold })
+    }
+
+    /// 在monitor_interval秒内接收航班的推送并交给on_update,返回监控结束的原因
+    ///
+    /// 期满或收到到期通知时发送Unmonitor取消订阅后返回;服务器关闭时直接返回。
+    /// 期满时的读超时属于正常结束,不作为错误返回
+    pub fn follow_updates<F>(&mut self, flight_id: i32, monitor_interval: i32, mut on_update: F) -> io::Result<MonitorEnd>
+    where
+        F: FnMut(&HashMap<String, Value>),
+    {
+        let deadline = Instant::now() + Duration::from_secs(monitor_interval.max(0) as u64);
+        let end = loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break MonitorEnd::Elapsed;
+            }
+            let update = match self.receive_update_within(Some(remaining)) {
+                Ok(update) => update,
+                Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => break MonitorEnd::Elapsed,
+                Err(e) => return Err(e),
+            };
+            on_update(&update);
+
+            // 服务器在监控到期时发送action为"9"的消息,关闭时发送action为"10"的消息
+            match update.get("action").and_then(|action| action.as_string()).map(|action| action.as_str()) {
+                Some("9") => break MonitorEnd::Expired,
+                Some("10") => return Ok(MonitorEnd::ServerShutdown),
+                _ => {}
+            }
+        };
+
+        self.send_request(Request::Unmonitor { flight_id })?;
+        Ok(end)
+    }
+
+    /// 阻塞等待下一条服务器推送(座位更新、监控到期或服务器关闭)
+    pub fn receive_update(&self) -> io::Result<HashMap<String, Value>> {
+        self.receive_update_within(None)
+    }
+
+    /// 等待下一条服务器推送,timeout为None时一直阻塞,超时返回WouldBlock或TimedOut错误
+    fn receive_update_within(&self, timeout: Option<Duration>) -> io::Result<HashMap<String, Value>> {
+        self.socket.set_read_timeout(timeout)?;
+        let mut buffer = [0u8; 1024];
+        let amt = self.socket.recv(&mut buffer)?;
+        let mut deserializer = Deserializer::new(&buffer[..amt], self.byte_order);
+        match deserializer.deserialize_next()? {
+            Value::Map(map) => Ok(map),
+            other => Err(io::Error::new(io::ErrorKind::InvalidData, format!("Expected a map, got {:?}", other))),
+        }
+    }
+
+    /// 发送请求并等待响应,同时返回往返时间;use_cache为true时先查缓存,成功的响应会写入缓存
+    ///
+    /// 请求头(request_id、调用语义和action)写在请求体map之前;往返时间从第一次发送计到收到响应,包含重试;命中缓存时为None
+    fn send_request_and_receive_response(&mut self, action: u8, map: HashMap<String, String>, use_cache: bool) -> Result<(HashMap<String, Value>, Option<Duration>), io::Error> {
+        if use_cache {
+            if let Some(cached) = self.cache.get(action, &map) {
+                if self.verbose {
+                    println!("Cached: {:?}", cached);
+                }
+                return Ok((cached, None));
+            }
+        }
+
+        let retry = self.config.client.retry;
+        let timeout = action_timeout(&self.config.client, action);
+        let byte_order = self.byte_order;
+        let envelope = Envelope { request_id: self.next_request_id(), invocation_semantic: self.invocation_semantic.into(), action };
+        let request_id = envelope.request_id.to_string();
+
+        if self.verbose {
+            println!("Request: {:?} {:?}", envelope, map);
+        }
+
+        let mut serializer = Serializer::new(byte_order);
+        envelope.serialize(&mut serializer)?;
+        serializer.serialize_map(&map)?;
+        let send_buffer = serializer.get_buffer();
+
+        let started = Instant::now();
+        let received = send_with_retries(
+            &self.socket,
+            &send_buffer,
+            retry,
+            timeout,
+            Duration::from_millis(self.config.client.backoff_base_ms),
+            Duration::from_millis(self.config.client.backoff_max_ms),
+            // 只接受request_id与本次请求相同的响应(字节序不一致的响应也接受,以便报告错误)
+            |data| [byte_order, byte_order.opposite()].into_iter().any(|order| {
+                decode_response_map(data, order).is_ok_and(|reply| reply.get("request_id").and_then(Value::as_string) == Some(&request_id))
+            }),
+        )?;
+        let rtt = started.elapsed();
+        self.rtt.record(rtt);
+        let result = decode_response_map(&received, byte_order)?;
+        if self.verbose {
+            println!("Received: {:?}", result);
+            println!("RTT: {:.1}ms", millis(rtt));
+        }
+        if self.config.client.force_duplicate {
+            eprintln!("Sent request_id: {}, received request_id: {}", request_id, result["request_id"]);
+        }
+
+        if use_cache && result.get("status").and_then(Value::as_string).map(|status| status.as_str()) == Some("200") {
+            self.cache.insert(action, &map, result.clone());
+        }
+        Ok((result, Some(rtt)))
+    }
+
+    /// 生成下一个请求的request_id;配置了force_duplicate时沿用上一个请求的request_id
+    fn next_request_id(&mut self) -> u64 {
+        let request_id = match self.last_request_id {
+            Some(last) if self.config.client.force_duplicate => {
+                eprintln!("Reusing request_id {} (force_duplicate)", last);
+                last
+            }
+            _ => gen_request_id(),
+        };
+        self.last_request_id = Some(request_id);
+        request_id
+    }
+
+    /// 发送请求并处理响应
+    /// 查询类请求是幂等的,可使用cache中的结果;预订和监控请求总是发送到服务器
+    pub fn send_request(&mut self, request: Request) -> Result<Response, io::Error> {
+        let mut map = HashMap::new();
+
+        if self.verbose {
+            println!("----------------------------------");
+        }
+        // 按请求类型填写请求体,得到请求头中的action以及是否可以使用缓存
+        let (action, use_cache) = match &request {
+            Request::QueryFlightIds { source, destination, max_fare } => {
+                // 构建查询航班ID的请求
+                map.insert("source".to_string(), source.clone());
+                map.insert("destination".to_string(), destination.clone());
+                if let Some(max_fare) = max_fare {
+                    map.insert("max_fare".to_string(), max_fare.to_string());
+                }
+                (1, true)
+            }
+            Request::QueryFlightsByDate { source, destination, from, to } => {
+                // 构建按出发时间范围查询航班ID的请求
+                map.insert("source".to_string(), source.clone());
+                map.insert("destination".to_string(), destination.clone());
+                map.insert("from".to_string(), from.format(TIME_FORMAT).to_string());
+                map.insert("to".to_string(), to.format(TIME_FORMAT).to_string());
+                (8, true)
+            }
+            Request::QueryFlightDetails { flight_id } => {
+                // 构建查询航班详情的请求
+                map.insert("flight_id".to_string(), flight_id.to_string());
+                (2, true)
+            }
+            Request::QueryFlightDetailsBatch { flight_ids } => {
+                // 构建批量查询航班详情的请求,航班号以逗号分隔
+                let ids: Vec<String> = flight_ids.iter().map(|id| id.to_string()).collect();
+                map.insert("flight_ids".to_string(), ids.join(","));
+                (13, true)
+            }
+            Request::ReserveSeats { flight_id, seats, idempotency_key } => {
+                // 构建预订座位的请求
+                map.insert("flight_id".to_string(), flight_id.to_string());
+                map.insert("seats".to_string(), seats.to_string());
+                if let Some(idempotency_key) = idempotency_key {
+                    map.insert("idempotency_key".to_string(), idempotency_key.clone());
+                }
+                (3, false)
+            }
+            Request::ReserveMulti { items } => {
+                // 构建多航班预订的请求,每段为 "flight_id:seats"
+                let legs: Vec<String> = items.iter().map(|(flight_id, seats)| format!("{}:{}", flight_id, seats)).collect();
+                map.insert("legs".to_string(), legs.join(","));
+                (12, false)
+            }
+            Request::CancelReservation { flight_id, seats } => {
+                // 构建取消预订的请求
+                map.insert("flight_id".to_string(), flight_id.to_string());
+                map.insert("seats".to_string(), seats.to_string());
+                (6, false)
+            }
+            Request::MonitorFlight { flight_id, monitor_interval, threshold } => {
+                // 构建监控航班的请求
+                map.insert("flight_id".to_string(), flight_id.to_string());
+                map.insert("monitor_interval".to_string(), monitor_interval.to_string());
+                if *threshold != 0 {
+                    map.insert("threshold".to_string(), threshold.to_string());
+                }
+                (4, false)
+            }
+            Request::Unmonitor { flight_id } => {
+                // 构建停止监控的请求
+                map.insert("flight_id".to_string(), flight_id.to_string());
+                (7, false)
+            }
+            Request::Stats => {
+                // 统计数据随预订变化,不使用缓存
+                (14, false)
+            }
+            Request::ListMyMonitors => {
+                // 订阅随时间到期,不使用缓存
+                (15, false)
+            }
+            Request::ResetFlight { flight_id } => {
+                // 构建重置航班的管理请求
+                map.insert("flight_id".to_string(), flight_id.to_string());
+                (11, false)
+            }
+        };
+
+        // 序列化并发送请求,再处理响应数据
+        let (result, _) = self.send_request_and_receive_response(action, map, use_cache)?;
+        parse_response(&request, &result)
+    }
+}
+
+/// 把服务器的响应映射转换为request对应的Response
+///
+/// 状态码为200表示成功,400表示请求不合法,404表示航班不存在或没有匹配的航班,500表示服务器内部错误。
+/// 缺少字段或字段格式错误时返回InvalidData错误,不会panic;服务器内部错误总是返回Err;
+/// 查询没有匹配的航班(404)时返回空列表;查询航班详情失败时返回NotFound或InvalidInput错误,
+/// 错误信息为服务器的message;其他请求的400/404在Response中以Err(message)返回
+pub fn parse_response(request: &Request, result: &HashMap<String, Value>) -> io::Result<Response> {
+    let status = string_field(result, "status")?.as_str();
+    let ok = match status {
+        "200" => true,
+        "400" | "404" => false,
+        _ => return Err(status_error(status, result)),
+    };
+    match request {
+        Request::QueryFlightIds { .. } | Request::QueryFlightsByDate { .. } => match status {
+            "200" => response_field(result, "flight_ids")?
+                .as_array()
+                .ok_or_else(|| invalid_field("flight_ids"))?
+                .iter()
+                .map(|id| id.as_i32().ok_or_else(|| invalid_field("flight_ids")))
+                .collect::<io::Result<_>>()
+                .map(Response::FlightIds),
+            "404" => Ok(Response::FlightIds(vec![])),
+            _ => Err(status_error(status, result)),
+        },
+        Request::QueryFlightDetails { .. } => {
+            if !ok {
+                return Err(status_error(status, result));
+            }
+            Ok(Response::FlightDetails {
+                departure_time: optional_field(result, "departure_time", Value::as_datetime)?,
+                airfare: optional_field(result, "airfare", Value::as_i64)?,
+                seats_available: optional_field(result, "seats_available", Value::as_i32)?,
+            })
+        }
+        Request::QueryFlightDetailsBatch { .. } => {
+            if !ok {
+                return Err(status_error(status, result));
+            }
+            response_field(result, "flights")?
+                .as_array()
+                .ok_or_else(|| invalid_field("flights"))?
+                .iter()
+                .map(|entry| {
+                    let entry = entry.as_map().ok_or_else(|| invalid_field("flights"))?;
+                    let flight_id = typed_field(entry, "flight_id", Value::as_i32)?;
+                    if string_field(entry, "status")? != "200" {
+                        return Ok((flight_id, Err(string_field(entry, "message")?.clone())));
+                    }
+                    let record = FlightDetailsRecord {
+                        departure_time: typed_field(entry, "departure_time", Value::as_datetime)?,
+                        airfare: typed_field(entry, "airfare", Value::as_i64)?,
+                        seats_available: typed_field(entry, "seats_available", Value::as_i32)?,
+                    };
+                    Ok((flight_id, Ok(record)))
+                })
+                .collect::<io::Result<_>>()
+                .map(Response::FlightDetailsBatch)
+        }
+        Request::ReserveSeats { .. } => {
+            if ok {
+                // 超售座位数
+                Ok(Response::Reservation(Ok(typed_field(result, "overbooked", Value::as_i32)?)))
+            } else {
+                Ok(Response::Reservation(Err(string_field(result, "message")?.clone())))
+            }
+        }
+        Request::ReserveMulti { .. } => {
+            if ok {
+                return Ok(Response::MultiReservation(Ok(())));
+            }
+            // 请求本身不合法时没有failed_leg,按错误状态返回
+            match result.get("failed_leg").and_then(Value::as_i32) {
+                Some(leg) => Ok(Response::MultiReservation(Err((leg as usize, string_field(result, "message")?.clone())))),
+                None => Err(status_error(status, result)),
+            }
+        }
+        Request::CancelReservation { .. } => Ok(Response::Cancellation(status_result(result, ok)?)),
+        Request::MonitorFlight { .. } => Ok(Response::MonitoringStarted(status_result(result, ok)?)),
+        Request::Unmonitor { .. } => Ok(Response::MonitoringStopped(status_result(result, ok)?)),
+        Request::Stats => {
+            if !ok {
+                return Err(status_error(status, result));
+            }
+            Ok(Response::Stats(FlightStats {
+                flights: typed_field(result, "flights", Value::as_i32)?,
+                total_seats: typed_field(result, "total_seats", Value::as_i64)?,
+                seats_sold: typed_field(result, "seats_sold", Value::as_i64)?,
+                average_fare: typed_field(result, "average_fare", Value::as_i64)?,
+            }))
+        }
+        Request::ListMyMonitors => {
+            if !ok {
+                return Err(status_error(status, result));
+            }
+            response_field(result, "monitors")?
+                .as_array()
+                .ok_or_else(|| invalid_field("monitors"))?
+                .iter()
+                .map(|entry| {
+                    let entry = entry.as_map().ok_or_else(|| invalid_field("monitors"))?;
+                    Ok((typed_field(entry, "flight_id", Value::as_i32)?, typed_field(entry, "remaining", Value::as_i32)?))
+                })
+                .collect::<io::Result<_>>()
+                .map(Response::MyMonitors)
+        }
+        Request::ResetFlight { .. } => {
+            if ok {
+                Ok(Response::FlightReset(Ok(typed_field(result, "seats_available", Value::as_i32)?)))
+            } else {
+                Ok(Response::FlightReset(Err(string_field(result, "message")?.clone())))
+            }
+        }
+    }
+}
+
+/// 把失败的状态码转换为io::Error:404为NotFound,400为InvalidInput,其余为Other
+fn status_error(status: &str, result: &HashMap<String, Value>) -> io::Error {
+    let kind = match status {
+        "404" => io::ErrorKind::NotFound,
+        "400" => io::ErrorKind::InvalidInput,
+        _ => io::ErrorKind::Other,
+    };
+    match result.get("message").and_then(Value::as_string) {
+        Some(message) => io::Error::new(kind, message.clone()),
+        None => io::Error::new(kind, format!("Request failed with status {}", status)),
+    }
+}
+
+/// 成功时返回Ok(()),失败时返回服务器的message
+fn status_result(result: &HashMap<String, Value>, ok: bool) -> io::Result<Result<(), String>> {
+    if ok {
+        Ok(Ok(()))
+    } else {
+        Ok(Err(string_field(result, "message")?.clone()))
+    }
+}
+
+/// 取出响应中的字段,缺失时返回InvalidData错误
+fn response_field<'a>(result: &'a HashMap<String, Value>, key: &str) -> io::Result<&'a Value> {
+    result.get(key)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("Missing field in response: {}", key)))
+}
+
+/// 取出响应中的字符串字段,缺失或不是字符串时返回InvalidData错误
+fn string_field<'a>(result: &'a HashMap<String, Value>, key: &str) -> io::Result<&'a String> {
+    response_field(result, key)?.as_string().ok_or_else(|| invalid_field(key))
+}
+
+/// 按类型取出响应中的字段(如Value::as_i32),缺失或类型不符时返回InvalidData错误
+fn typed_field<T>(result: &HashMap<String, Value>, key: &str, get: fn(&Value) -> Option<T>) -> io::Result<T> {
+    get(response_field(result, key)?).ok_or_else(|| invalid_field(key))
+}
+
+/// 取出响应中可以为Null的字段,Null表示服务器没有这项数据
+fn optional_field<T>(result: &HashMap<String, Value>, key: &str, get: fn(&Value) -> Option<T>) -> io::Result<Option<T>> {
+    match response_field(result, key)? {
+        Value::Null => Ok(None),
+        value => get(value).map(Some).ok_or_else(|| invalid_field(key)),
+    }
+}
+
+fn invalid_field(key: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Invalid field in response: {}", key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    /// 在本地绑定一个假服务器,返回它和连接到它的客户端
+    fn client_and_fake_server() -> (FlightClient, UdpSocket) {
+        client_and_fake_server_with(|_| {})
+    }
+
+    /// 同client_and_fake_server,创建客户端前用configure修改客户端配置
+    fn client_and_fake_server_with(configure: impl FnOnce(&mut ClientConfig)) -> (FlightClient, UdpSocket) {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut config = Config::default();
+        config.server.address = server.local_addr().unwrap().to_string();
+        configure(&mut config.client);
+        let mut client = FlightClient::new(config).unwrap();
+        client.set_verbose(false);
+        (client, server)
+    }
+
+    /// 收到心跳请求后依次发回replies生成的响应,参数为心跳的request_id
+    fn answer_ping(server: &UdpSocket, replies: &[fn(&str) -> Vec<u8>]) {
+        let mut buffer = [0u8; 1024];
+        let (len, client_addr) = server.recv_from(&mut buffer).unwrap();
+        let mut deserializer = Deserializer::new(&buffer[..len], ByteOrder::Little);
+        let request_id = Envelope::deserialize(&mut deserializer).unwrap().request_id.to_string();
+        for reply in replies {
+            server.send_to(&reply(&request_id), client_addr).unwrap();
+        }
+    }
+
+    fn pong(request_id: &str, status: &str, server_time: Option<i64>) -> Vec<u8> {
+        let mut map: HashMap<String, Value> = HashMap::new();
+        map.insert("request_id".to_string(), request_id.into());
+        map.insert("status".to_string(), status.into());
+        if let Some(server_time) = server_time {
+            map.insert("server_time".to_string(), server_time.into());
+        }
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_value_map(&map).unwrap();
+        serializer.get_buffer()
+    }
+
+    #[test]
+    fn ping_skips_datagrams_that_are_not_its_pong() {
+        let (client, server) = client_and_fake_server();
+        thread::scope(|scope| {
+            scope.spawn(|| answer_ping(&server, &[
+                |_| vec![0xff, 0x00],
+                |_| pong("1", "200", Some(1_700_000_000)),
+                |request_id| pong(request_id, "500", Some(1_700_000_000)),
+                |request_id| pong(request_id, "200", None),
+                |request_id| pong(request_id, "200", Some(1_700_000_000)),
+            ]));
+            assert!(client.ping().is_ok());
+        });
+    }
+
+    #[test]
+    fn ping_without_a_valid_pong_is_unreachable() {
+        let (client, server) = client_and_fake_server();
+        thread::scope(|scope| {
+            scope.spawn(|| answer_ping(&server, &[|_| pong("1", "200", Some(1_700_000_000))]));
+            let error = client.ping().unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::NotConnected);
+        });
+    }
+
+    #[test]
+    fn client_reports_its_local_and_server_addresses() {
+        let (client, server) = client_and_fake_server();
+        assert_eq!(client.server_addr(), server.local_addr().unwrap());
+        let local_addr = client.local_addr().unwrap();
+        assert!(local_addr.is_ipv4() && local_addr.port() != 0);
+
+        // IPv6服务器地址需要IPv6的本地socket
+        let server = UdpSocket::bind("[::1]:0").unwrap();
+        let mut config = Config::default();
+        config.server.address = server.local_addr().unwrap().to_string();
+        let (socket, server_addr) = connected_client(&config).unwrap();
+        assert_eq!(server_addr, server.local_addr().unwrap());
+        assert!(socket.local_addr().unwrap().is_ipv6());
+    }
+
+    #[test]
+    fn ping_to_a_closed_port_fails_within_the_deadline() {
+        let closed = UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut config = Config::default();
+        config.server.address = closed.to_string();
+        let client = FlightClient::new(config).unwrap();
+
+        let started = Instant::now();
+        assert_eq!(client.ping().unwrap_err().kind(), io::ErrorKind::NotConnected);
+        assert!(started.elapsed() < PING_DEADLINE + Duration::from_secs(1));
+
+        let mut config = Config::default();
+        config.server.address = "localhost".to_string();
+        assert!(FlightClient::new(config).is_err());
+    }
+
+    /// 假服务器:应答收到的每个请求,直到timeout内没有新请求,返回收到的请求头
+    ///
+    /// 应答包含request_id和answer按action生成的字段
+    fn serve_until_idle(server: &UdpSocket, timeout: Duration, answer: fn(u8) -> Vec<(&'static str, Value)>) -> Vec<Envelope> {
+        server.set_read_timeout(Some(timeout)).unwrap();
+        let mut buffer = [0u8; 1024];
+        let mut received = Vec::new();
+        while let Ok((len, client_addr)) = server.recv_from(&mut buffer) {
+            let envelope = Envelope::deserialize(&mut Deserializer::new(&buffer[..len], ByteOrder::Little)).unwrap();
+            let mut map: HashMap<String, Value> = answer(envelope.action).into_iter().map(|(k, v)| (k.to_string(), v)).collect();
+            map.insert("request_id".to_string(), envelope.request_id.to_string().into());
+            let mut serializer = Serializer::new(ByteOrder::Little);
+            serializer.serialize_value_map(&map).unwrap();
+            server.send_to(&serializer.get_buffer(), client_addr).unwrap();
+            received.push(envelope);
+        }
+        received
+    }
+
+    /// 航班详情、预订和停止监控成功的应答
+    fn flight_answers(action: u8) -> Vec<(&'static str, Value)> {
+        let departure = chrono::NaiveDateTime::parse_from_str("2024-10-01 08:00:00", TIME_FORMAT).unwrap();
+        match action {
+            2 => vec![("status", "200".into()), ("departure_time", Value::DateTime(departure)), ("airfare", Value::Int64(45000)), ("seats_available", Value::Int32(10))],
+            3 => vec![("status", "200".into()), ("overbooked", Value::Int32(0))],
+            7 => vec![("status", "200".into())],
+            _ => vec![("status", "400".into()), ("message", "Invalid action".into())],
+        }
+    }
+
+    #[test]
+    fn repeated_queries_are_cached_but_reservations_always_reach_the_server() {
+        let (mut client, server) = client_and_fake_server_with(|config| config.cache_size = 8);
+        let actions = thread::scope(|scope| {
+            let fake = scope.spawn(|| serve_until_idle(&server, Duration::from_millis(500), flight_answers));
+            for _ in 0..2 {
+                assert!(matches!(client.query_flight_details(1).unwrap(), Response::FlightDetails { seats_available: Some(10), .. }));
+            }
+            for _ in 0..2 {
+                assert!(matches!(client.reserve_seats(1, 1).unwrap(), Response::Reservation(Ok(0))));
+            }
+            fake.join().unwrap().into_iter().map(|envelope| envelope.action).collect::<Vec<_>>()
+        });
+        assert_eq!(actions, [2, 3, 3]);
+        // 命中缓存的查询不计入往返时间
+        assert_eq!(client.rtt_stats().count(), 3);
+    }
+
+    #[test]
+    fn unanswered_request_is_sent_once_per_attempt_and_each_attempt_waits() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.connect(server.local_addr().unwrap()).unwrap();
+
+        let started = Instant::now();
+        let error = send_with_retries(&client, b"req", 3, Duration::from_millis(100), Duration::from_millis(1), Duration::from_millis(1), |_| true).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+
+        server.set_read_timeout(Some(Duration::from_millis(100))).unwrap();
+        let mut buffer = [0u8; 16];
+        let mut sent = 0;
+        while server.recv(&mut buffer).is_ok() {
+            sent += 1;
+        }
+        assert_eq!(sent, 3);
+    }
+
+    #[test]
+    fn datagrams_that_are_not_the_reply_are_skipped() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.connect(server.local_addr().unwrap()).unwrap();
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                let (_, client_addr) = server.recv_from(&mut [0u8; 16]).unwrap();
+                server.send_to(b"update", client_addr).unwrap();
+                server.send_to(b"reply", client_addr).unwrap();
+            });
+            let reply = send_with_retries(&client, b"req", 1, Duration::from_secs(5), Duration::ZERO, Duration::ZERO, |data| data == b"reply");
+            assert_eq!(reply.unwrap(), b"reply");
+        });
+    }
+
+    #[test]
+    fn backoff_doubles_per_attempt_up_to_the_cap_with_jitter() {
+        let base = Duration::from_millis(100);
+        let max = Duration::from_millis(1000);
+        for _ in 0..50 {
+            for (attempt, full) in [(1, 100), (2, 200), (3, 400), (4, 800), (5, 1000), (30, 1000)] {
+                let delay = backoff_delay(attempt, base, max);
+                let full = Duration::from_millis(full);
+                assert!(delay >= full / 2 && delay < full, "attempt {}: {:?}", attempt, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_client_settings_fail_when_the_client_is_created() {
+        for configure in [|config: &mut Config| config.client.byte_order = "middle".to_string(), |config: &mut Config| config.client.invocation_semantic = "exactly-once".to_string()] {
+            let mut config = Config::default();
+            config.server.address = "127.0.0.1:9".to_string();
+            configure(&mut config);
+            assert_eq!(FlightClient::new(config).err().map(|e| e.kind()), Some(io::ErrorKind::InvalidInput));
+        }
+    }
+
+    fn response_map(fields: Vec<(&str, Value)>) -> HashMap<String, Value> {
+        fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+    }
+
+    #[test]
+    fn malformed_responses_are_invalid_data_not_panics() {
+        let details = Request::QueryFlightDetails { flight_id: 1 };
+        let reserve = Request::ReserveSeats { flight_id: 1, seats: 1, idempotency_key: None };
+        let ids = Request::QueryFlightIds { source: "Singapore".to_string(), destination: "Tokyo".to_string(), max_fare: None };
+        let malformed = [
+            (&details, response_map(vec![])),
+            (&details, response_map(vec![("status", Value::Int32(200))])),
+            (&details, response_map(vec![("status", "200".into()), ("airfare", Value::Int64(1))])),
+            (&details, response_map(vec![("status", "200".into()), ("departure_time", "tomorrow".into()), ("airfare", Value::Int64(1)), ("seats_available", Value::Int32(1))])),
+            (&reserve, response_map(vec![("status", "200".into()), ("overbooked", "none".into())])),
+            (&reserve, response_map(vec![("status", "400".into())])),
+            (&ids, response_map(vec![("status", "200".into()), ("flight_ids", Value::Array(vec![Value::Int32(1), "2".into()]))])),
+        ];
+        for (request, result) in &malformed {
+            let error = parse_response(request, result).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{:?}", result);
+        }
+
+        // 服务器错误带着服务器的message返回
+        let error = parse_response(&reserve, &response_map(vec![("status", "500".into()), ("message", "disk full".into())])).unwrap_err();
+        assert_eq!((error.kind(), error.to_string()), (io::ErrorKind::Other, "disk full".to_string()));
+        let error = parse_response(&details, &response_map(vec![("status", "404".into()), ("message", "Flight not found".into())])).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    /// 向客户端推送一条action为action的监控消息
+    fn push(server: &UdpSocket, client_addr: SocketAddr, action: &str) {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_map(&HashMap::from([("action".to_string(), action.to_string()), ("flight_id".to_string(), "1".to_string())])).unwrap();
+        server.send_to(&serializer.get_buffer(), client_addr).unwrap();
+    }
+
+    #[test]
+    fn follow_updates_unsubscribes_when_the_interval_ends_or_the_monitor_expires() {
+        for (pushed, expected) in [(&["5"][..], MonitorEnd::Elapsed), (&["5", "9"][..], MonitorEnd::Expired), (&["10"][..], MonitorEnd::ServerShutdown)] {
+            let (mut client, server) = client_and_fake_server();
+            let client_addr = client.local_addr().unwrap();
+            let (end, elapsed, updates, requests) = thread::scope(|scope| {
+                let fake = scope.spawn(|| {
+                    for action in pushed {
+                        push(&server, client_addr, action);
+                    }
+                    serve_until_idle(&server, Duration::from_millis(1500), flight_answers)
+                });
+                let mut updates = 0;
+                let started = Instant::now();
+                let end = client.follow_updates(1, 1, |_| updates += 1).unwrap();
+                (end, started.elapsed(), updates, fake.join().unwrap())
+            });
+
+            assert_eq!(end, expected);
+            assert_eq!(updates, pushed.len());
+            // 只有期满才需要等满监控期;服务器关闭时不再取消订阅
+            assert_eq!(elapsed >= Duration::from_secs(1), expected == MonitorEnd::Elapsed);
+            let unmonitored = requests.iter().any(|envelope| envelope.action == 7);
+            assert_eq!(unmonitored, expected != MonitorEnd::ServerShutdown);
+        }
+    }
+
+    #[test]
+    fn force_duplicate_reuses_the_previous_request_id() {
+        for force_duplicate in [true, false] {
+            let (mut client, server) = client_and_fake_server_with(|config| config.force_duplicate = force_duplicate);
+            let requests = thread::scope(|scope| {
+                let fake = scope.spawn(|| serve_until_idle(&server, Duration::from_millis(500), flight_answers));
+                client.reserve_seats(1, 1).unwrap();
+                client.reserve_seats(1, 1).unwrap();
+                fake.join().unwrap()
+            });
+            assert_eq!(requests.len(), 2);
+            assert_eq!(requests[0].request_id == requests[1].request_id, force_duplicate);
+        }
+    }
+
+    #[test]
+    fn rtt_stats_track_min_average_and_max() {
+        let mut stats = RttStats::default();
+        assert_eq!((stats.min(), stats.avg(), stats.max()), (None, None, None));
+        assert_eq!(stats.to_string(), "no requests");
+
+        for millis in [30, 10, 20] {
+            stats.record(Duration::from_millis(millis));
+        }
+        assert_eq!(stats.count(), 3);
+        assert_eq!(stats.min(), Some(Duration::from_millis(10)));
+        assert_eq!(stats.avg(), Some(Duration::from_millis(20)));
+        assert_eq!(stats.max(), Some(Duration::from_millis(30)));
+        assert_eq!(stats.to_string(), "3 requests, min/avg/max = 10.0/20.0/30.0ms");
+    }
+
+    #[test]
+    fn each_request_type_uses_its_own_timeout_or_the_default() {
+        let mut config = Config::default().client;
+        config.timeout = 5;
+        config.timeouts.monitor = Some(30);
+        config.timeouts.reserve = Some(2);
+
+        assert_eq!(action_timeout(&config, 4), Duration::from_secs(30));
+        assert_eq!(action_timeout(&config, 3), Duration::from_secs(2));
+        assert_eq!(action_timeout(&config, 12), Duration::from_secs(2));
+        assert_eq!(action_timeout(&config, 2), Duration::from_secs(5));
+        assert_eq!(action_timeout(&config, 14), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn flight_ids_are_read_from_a_typed_array() {
+        let ids = Request::QueryFlightIds { source: "Singapore".to_string(), destination: "Tokyo".to_string(), max_fare: None };
+        let found = response_map(vec![("status", "200".into()), ("flight_ids", Value::Array(vec![Value::Int32(3), Value::Int32(1)]))]);
+        assert!(matches!(parse_response(&ids, &found).unwrap(), Response::FlightIds(ids) if ids == vec![3, 1]));
+
+        // 没有匹配的航班是空列表,不是错误
+        let none = response_map(vec![("status", "404".into()), ("message", "No matching flights".into())]);
+        assert!(matches!(parse_response(&ids, &none).unwrap(), Response::FlightIds(ids) if ids.is_empty()));
+
+        // 旧格式的逗号分隔字符串不再被接受
+        let joined = response_map(vec![("status", "200".into()), ("flight_ids", "3,1".into())]);
+        assert_eq!(parse_response(&ids, &joined).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn null_detail_fields_are_read_as_missing() {
+        let details = Request::QueryFlightDetails { flight_id: 1 };
+        let partial = response_map(vec![
+            ("status", "200".into()),
+            ("departure_time", Value::Null),
+            ("airfare", Value::Int64(45000)),
+            ("seats_available", Value::Null),
+        ]);
+        let response = parse_response(&details, &partial).unwrap();
+        assert!(matches!(response, Response::FlightDetails { departure_time: None, airfare: Some(45000), seats_available: None }), "{:?}", response);
+
+        // Null以外的错误类型仍然是错误
+        let mistyped = response_map(vec![
+            ("status", "200".into()),
+            ("departure_time", Value::Null),
+            ("airfare", "45000".into()),
+            ("seats_available", Value::Int32(10)),
+        ]);
+        assert_eq!(parse_response(&details, &mistyped).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,4 +1,4 @@
-use std::net::SocketAddr;
+use std::fmt;
 use chrono::NaiveDateTime;
 
 /// Represents a flight with its details
@@ -8,8 +8,55 @@ pub struct Flight {
     pub source: String,        // Departure airport
     pub destination: String,   // Arrival airport
     pub departure_time: NaiveDateTime,  // Scheduled departure time
-    pub airfare: f32,          // Price of the flight
+    pub airfare: i64,          // Price of the flight in cents
     pub seats_available: i32,  // Number of available seats
+    pub capacity: i32,         // Total number of seats on the flight
+    pub overbook_limit: i32,   // Seats that may be sold beyond capacity; seats_available goes negative once they are
+}
+
+/// Formats an amount in cents as dollars, e.g. `29999` as `$299.99`
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let cents = cents.unsigned_abs();
+    format!("{}${}.{:02}", sign, cents / 100, cents % 100)
+}
+
+impl fmt::Display for Flight {
+    /// Formats a one-line summary, e.g. `#1 New York→London 2024-09-01 10:00 $500.00 (100 seats)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {}→{} {} {} ({} seats)",
+            self.flight_id,
+            self.source,
+            self.destination,
+            self.departure_time.format("%Y-%m-%d %H:%M"),
+            format_cents(self.airfare),
+            self.seats_available
+        )
+    }
+}
+
+#[allow(dead_code)]
+impl Flight {
+    /// Returns the number of seats reserved so far
+    pub fn seats_sold(&self) -> i32 {
+        self.capacity - self.seats_available
+    }
+
+    /// Returns a multi-line description of the flight for the details view
+    pub fn detailed(&self) -> String {
+        format!(
+            "Flight #{}\n  From:      {}\n  To:        {}\n  Departure: {}\n  Airfare:   {}\n  Seats:     {} of {} available",
+            self.flight_id,
+            self.source,
+            self.destination,
+            self.departure_time.format("%Y-%m-%d %H:%M"),
+            format_cents(self.airfare),
+            self.seats_available,
+            self.capacity
+        )
+    }
 }
 
 /// Enum representing different types of requests that can be made to the flight system
@@ -18,24 +65,70 @@ pub enum Request {
     /// Query to get flight IDs based on source and destination
     QueryFlightIds { 
         source: String,        // Departure airport
-        destination: String    // Arrival airport
+        destination: String,   // Arrival airport
+        max_fare: Option<i64>  // Highest airfare in cents to include (if any)
     },
     
+    /// Query to get flight IDs based on source and destination departing within a time range
+    QueryFlightsByDate {
+        source: String,        // Departure airport
+        destination: String,   // Arrival airport
+        from: NaiveDateTime,   // Earliest departure time (inclusive)
+        to: NaiveDateTime      // Latest departure time (inclusive)
+    },
+
     /// Query to get details of a specific flight
     QueryFlightDetails { 
         flight_id: i32         // ID of the flight to query
     },
     
+    /// Query to get details of several flights in one round trip
+    QueryFlightDetailsBatch {
+        flight_ids: Vec<i32>   // IDs of the flights to query, in the order results are wanted
+    },
+
     /// Request to reserve seats on a flight
     ReserveSeats { 
         flight_id: i32,        // ID of the flight to reserve seats on
-        seats: i32             // Number of seats to reserve
+        seats: i32,            // Number of seats to reserve
+        idempotency_key: Option<String>,  // Client-chosen key; repeating it on the same flight doesn't reserve again
     },
     
+    /// Request to reserve seats on several flights at once, all or nothing
+    ReserveMulti {
+        items: Vec<(i32, i32)> // (flight_id, seats) for each leg, in booking order
+    },
+
+    /// Request to return previously reserved seats to a flight
+    CancelReservation {
+        flight_id: i32,        // ID of the flight to cancel seats on
+        seats: i32             // Number of seats to cancel
+    },
+
     /// Request to monitor updates for a specific flight
     MonitorFlight { 
         flight_id: i32,        // ID of the flight to monitor
-        monitor_interval: i32  // Interval (in seconds) for monitoring updates
+        monitor_interval: i32, // Interval (in seconds) for monitoring updates
+        threshold: i32         // Only notify once seats_available drops below this; 0 notifies on every change
+    },
+
+    /// Request to stop monitoring updates for a specific flight
+    Unmonitor {
+        flight_id: i32         // ID of the flight to stop monitoring
+    },
+
+    /// Request for aggregate capacity and occupancy numbers across all flights
+    Stats,
+
+    /// Request for the requesting client's own active monitoring subscriptions
+    ///
+    /// Subscriptions are matched by the request's source address, so a client whose address changed
+    /// (a new socket, or a NAT mapping that was reassigned) won't see the ones it made before.
+    ListMyMonitors,
+
+    /// Admin request to restore a flight's seats to its full capacity
+    ResetFlight {
+        flight_id: i32         // ID of the flight to reset
     },
 }
 
@@ -49,31 +142,205 @@ pub enum Response {
     /// Response containing details of a specific flight
     FlightDetails {
         departure_time: Option<NaiveDateTime>,  // Scheduled departure time (if available)
-        airfare: Option<f32>,                   // Price of the flight (if available)
+        airfare: Option<i64>,                   // Price of the flight in cents (if available)
         seats_available: Option<i32>,           // Number of available seats (if available)
     },
     
+    /// Response containing details of several flights, one entry per requested ID in request order
+    FlightDetailsBatch(Vec<(i32, Result<FlightDetailsRecord, String>)>),  // (flight_id, details or the reason they're missing)
+
     /// Response to a seat reservation request
-    Reservation(Result<(), String>),  // Ok(()) if successful, Err(String) if failed
+    Reservation(Result<i32, String>),  // Ok(seats of this reservation beyond capacity) if successful, Err(String) if failed
     
+    /// Response to a multi-flight reservation request
+    MultiReservation(Result<(), (usize, String)>),  // Ok(()) if every leg was reserved, Err((index of the failed leg, reason)) otherwise
+
+    /// Response to a reservation cancellation request
+    Cancellation(Result<(), String>),  // Ok(()) if the seats were returned, Err(String) if failed
+
     /// Response to a flight monitoring request
     MonitoringStarted(Result<(), String>),  // Ok(()) if started successfully, Err(String) if failed
     
+    /// Response to a request to stop monitoring
+    MonitoringStopped(Result<(), String>),  // Ok(()) once the client is no longer subscribed
+
+    /// Response containing aggregate numbers across all flights
+    Stats(FlightStats),
+
+    /// Response listing the client's active subscriptions, ordered by flight ID
+    MyMonitors(Vec<(i32, i32)>),  // (flight_id, seconds until the subscription expires)
+
+    /// Response to a flight reset request
+    FlightReset(Result<i32, String>),  // Ok(seats_available) after the reset, Err(String) if failed
+
     /// General error response
     Error(String),  // Description of the error
 }
 
+impl fmt::Display for Response {
+    /// Formats the response for people, e.g. `Flight IDs: 1, 3` or `Reservation failed: Not enough seats available`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let optional = |value: Option<String>| value.unwrap_or_else(|| "unknown".to_string());
+        match self {
+            Response::FlightIds(flight_ids) => {
+                let ids: Vec<String> = flight_ids.iter().map(|id| id.to_string()).collect();
+                write!(f, "Flight IDs: {}", ids.join(", "))
+            }
+            Response::FlightDetails { departure_time, airfare, seats_available } => write!(
+                f,
+                "Departure: {}\nAirfare:   {}\nSeats:     {} available",
+                optional(departure_time.map(|time| time.format("%Y-%m-%d %H:%M").to_string())),
+                optional(airfare.map(format_cents)),
+                optional(seats_available.map(|seats| seats.to_string()))
+            ),
+            Response::FlightDetailsBatch(details) => {
+                let lines: Vec<String> = details
+                    .iter()
+                    .map(|(flight_id, record)| match record {
+                        Ok(record) => format!(
+                            "#{} {} {} ({} seats)",
+                            flight_id,
+                            record.departure_time.format("%Y-%m-%d %H:%M"),
+                            format_cents(record.airfare),
+                            record.seats_available
+                        ),
+                        Err(e) => format!("#{} {}", flight_id, e),
+                    })
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Response::Reservation(Ok(0)) => write!(f, "Seats reserved"),
+            Response::Reservation(Ok(overbooked)) => write!(f, "Seats reserved, {} of them overbooked", overbooked),
+            Response::Reservation(Err(e)) => write!(f, "Reservation failed: {}", e),
+            Response::MultiReservation(Ok(())) => write!(f, "Seats reserved on every leg"),
+            Response::MultiReservation(Err((leg, e))) => write!(f, "Reservation failed on leg {}: {}", leg + 1, e),
+            Response::Cancellation(Ok(())) => write!(f, "Reservation cancelled"),
+            Response::Cancellation(Err(e)) => write!(f, "Cancellation failed: {}", e),
+            Response::MonitoringStarted(Ok(())) => write!(f, "Monitoring started"),
+            Response::MonitoringStarted(Err(e)) => write!(f, "Monitoring failed: {}", e),
+            Response::MonitoringStopped(Ok(())) => write!(f, "Monitoring stopped"),
+            Response::MonitoringStopped(Err(e)) => write!(f, "Stopping monitoring failed: {}", e),
+            Response::Stats(stats) => write!(f, "{}", stats),
+            Response::MyMonitors(monitors) if monitors.is_empty() => write!(f, "No active subscriptions"),
+            Response::MyMonitors(monitors) => {
+                let lines: Vec<String> = monitors
+                    .iter()
+                    .map(|(flight_id, remaining)| format!("Flight {}: expires in {}s", flight_id, remaining))
+                    .collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Response::FlightReset(Ok(seats_available)) => write!(f, "Flight reset, {} seats available", seats_available),
+            Response::FlightReset(Err(e)) => write!(f, "Reset failed: {}", e),
+            Response::Error(e) => write!(f, "Error: {}", e),
+        }
+    }
+}
+
+/// Details of one flight in a batch details response
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlightDetailsRecord {
+    pub departure_time: NaiveDateTime,  // Scheduled departure time
+    pub airfare: i64,                   // Price of the flight in cents
+    pub seats_available: i32,           // Number of available seats
+}
+
+/// Aggregate capacity and occupancy numbers across all flights
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlightStats {
+    pub flights: i32,          // Number of flights
+    pub total_seats: i64,      // Sum of all flights' capacity
+    pub seats_sold: i64,       // Sum of seats reserved across all flights
+    pub average_fare: i64,     // Mean airfare in cents, rounded down; 0 when there are no flights
+}
+
+impl fmt::Display for FlightStats {
+    /// Formats a one-line summary, e.g. `3 flights, 120 of 300 seats sold (40.0%), average fare $350.00`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let occupancy = if self.total_seats > 0 { self.seats_sold as f64 * 100.0 / self.total_seats as f64 } else { 0.0 };
+        write!(
+            f,
+            "{} flights, {} of {} seats sold ({:.1}%), average fare {}",
+            self.flights,
+            self.seats_sold,
+            self.total_seats,
+            occupancy,
+            format_cents(self.average_fare)
+        )
+    }
+}
+
 /// Represents an update to a flight's information
 #[derive(Clone, Debug)]
 #[allow(dead_code)]
 pub struct FlightUpdate {
     pub flight_id: i32,        // ID of the flight that was updated
     pub seats_available: i32,  // New number of available seats
+    pub airfare: i64,          // Current price of the flight in cents
+    pub departure_time: NaiveDateTime,  // Current scheduled departure time
 }
 
-/// Represents a client that is monitoring flight updates
-#[derive(Eq, PartialEq, Hash, Debug)]
+/// Represents a client's subscription to one flight's updates
+///
+/// Subscriptions are keyed by flight ID and client address, so the address isn't stored here.
+#[derive(Debug)]
 pub struct MonitoringClient {
-    pub addr: SocketAddr,                  // Network address of the client
     pub expiration_time: std::time::Instant,  // Time when the monitoring should expire
-}
\ No newline at end of file
+    pub threshold: i32,                    // Only notify below this many available seats; 0 means every change
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sample flight 1 of the demo data
+    fn sample_flight() -> Flight {
+        Flight {
+            flight_id: 1,
+            source: "New York".to_string(),
+            destination: "London".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-09-01 10:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 50000,
+            seats_available: 100,
+            capacity: 100,
+            overbook_limit: 0,
+        }
+    }
+
+    #[test]
+    fn flight_summary_is_one_line() {
+        assert_eq!(sample_flight().to_string(), "#1 New York→London 2024-09-01 10:00 $500.00 (100 seats)");
+    }
+
+    #[test]
+    fn flight_details_list_every_field() {
+        let mut flight = sample_flight();
+        flight.seats_available = 98;
+        assert_eq!(
+            flight.detailed(),
+            "Flight #1\n  From:      New York\n  To:        London\n  Departure: 2024-09-01 10:00\n  Airfare:   $500.00\n  Seats:     98 of 100 available"
+        );
+    }
+
+    #[test]
+    fn cents_are_formatted_as_dollars() {
+        assert_eq!(format_cents(29999), "$299.99");
+        assert_eq!(format_cents(5), "$0.05");
+        assert_eq!(format_cents(-150), "-$1.50");
+    }
+
+    #[test]
+    fn responses_are_formatted_for_people() {
+        assert_eq!(Response::FlightIds(vec![1, 3]).to_string(), "Flight IDs: 1, 3");
+        assert_eq!(Response::Reservation(Ok(2)).to_string(), "Seats reserved, 2 of them overbooked");
+        assert_eq!(
+            Response::MultiReservation(Err((1, "Flight not found".to_string()))).to_string(),
+            "Reservation failed on leg 2: Flight not found"
+        );
+        let details = Response::FlightDetails {
+            departure_time: Some(sample_flight().departure_time),
+            airfare: Some(50000),
+            seats_available: None,
+        };
+        assert_eq!(details.to_string(), "Departure: 2024-09-01 10:00\nAirfare:   $500.00\nSeats:     unknown available");
+    }
+}
@@ -0,0 +1,1533 @@
+use std::net::UdpSocket;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::error::Error;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use chrono::NaiveDateTime;
+use chrono::Utc;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::{Config, ServerConfig};
+use crate::controller::{self, FlightController};
+use crate::rate_limiter::RateLimiter;
+use crate::serialization::{ByteOrder, Deserializer, Envelope, InvocationSemantic, SerializationError, Serializer, Value};
+
+/// 接收循环检查关闭标志的间隔
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// 清理过期监控的间隔:订阅过期后最迟在这段时间内收到"监控已过期"消息
+const MONITOR_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 响应状态码:成功
+const STATUS_OK: &str = "200";
+/// 响应状态码:请求字段缺失或不合法,或请求无法满足(如座位不足)
+const STATUS_BAD_REQUEST: &str = "400";
+/// 响应状态码:航班不存在或没有匹配的航班
+const STATUS_NOT_FOUND: &str = "404";
+/// 响应状态码:服务器内部错误
+const STATUS_INTERNAL_ERROR: &str = "500";
+
+/// 持久化去重存储时时间戳的格式
+const STORE_TIMESTAMP_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+
+struct RequestInfo {
+    timestamp: NaiveDateTime,
+    response: Vec<u8>,
+}
+
+/// 请求处理函数:参数为请求字段、航班控制器、客户端地址、服务器socket和服务器配置,返回响应字段
+///
+/// 响应字段的值可以是任意Value(如Int32数组),序列化时保留其类型
+///
+/// 返回Err时服务器回复400错误响应,错误信息作为message。签名相同的闭包和函数都自动实现这个trait
+pub trait ActionFn:
+    Fn(&HashMap<String, Value>, &FlightController, SocketAddr, &UdpSocket, &ServerConfig) -> Result<HashMap<String, Value>, Box<dyn Error>> + Send + Sync
+{
+}
+
+impl<F> ActionFn for F where
+    F: Fn(&HashMap<String, Value>, &FlightController, SocketAddr, &UdpSocket, &ServerConfig) -> Result<HashMap<String, Value>, Box<dyn Error>> + Send + Sync
+{
+}
+
+/// 已注册的请求处理函数
+pub type ActionHandler = Box<dyn ActionFn>;
+
+/// 一个数据报的应答
+enum Reply {
+    /// 心跳应答,不经过丢包模拟
+    Pong(Vec<u8>),
+    /// 请求的响应,按丢包率模拟投递
+    Response(Vec<u8>),
+}
+
+/// UDP航班服务器:持有socket、航班控制器和请求去重存储
+pub struct Server {
+    socket: UdpSocket,
+    config: Config,
+    byte_order: ByteOrder,
+    // 当前响应丢包率,可在运行时通过配置热加载修改
+    loss_rate: Arc<Mutex<f32>>,
+    // 当前请求丢包率,同样支持热加载
+    request_loss_rate: Arc<Mutex<f32>>,
+    /// 航班控制器,可在启动前添加航班;内部自带读写锁,可被多个工作线程共享
+    pub controller: FlightController,
+    // 已处理请求的响应缓存,按request_id索引
+    store_request: Arc<Mutex<HashMap<u64, RequestInfo>>>,
+    // 正在被工作线程处理的at-most-once请求,防止重传被并发执行两次
+    in_progress: Mutex<HashSet<u64>>,
+    // 丢包模拟使用的随机数生成器,配置了rng_seed时丢包序列可复现
+    rng: Mutex<StdRng>,
+    // 按客户端地址限流,requests_per_sec为0时不限流
+    rate_limiter: Option<Mutex<RateLimiter>>,
+    // 置为true后run在当前数据报处理完后退出
+    shutdown: Arc<AtomicBool>,
+    // 按action分发的请求处理函数,包含内置操作和通过register_action注册的操作
+    actions: HashMap<u8, ActionHandler>,
+}
+
+impl Server {
+    /// 按配置绑定UDP socket并创建服务器
+    pub fn bind(config: Config) -> Result<Server, Box<dyn Error>> {
+        Server::with_controller(config, FlightController::new())
+    }
+
+    /// 按配置绑定UDP socket,使用已添加好航班的控制器创建服务器
+    pub fn with_controller(config: Config, controller: FlightController) -> Result<Server, Box<dyn Error>> {
+        let socket = bind_socket(&config.server)?;
+        Server::from_socket(socket, controller, config)
+    }
+
+    /// 使用已绑定的socket和已添加好航班的控制器创建服务器
+    pub fn from_socket(socket: UdpSocket, mut controller: FlightController, config: Config) -> Result<Server, Box<dyn Error>> {
+        let byte_order: ByteOrder = config.server.byte_order.parse()?;
+        controller.set_byte_order(config.server.byte_order.parse()?);
+        // 设置较短的读超时,使接收循环能及时发现关闭标志
+        socket.set_read_timeout(Some(SHUTDOWN_POLL_INTERVAL))?;
+
+        let mut server = Server {
+            socket,
+            loss_rate: Arc::new(Mutex::new(config.server.loss_rate)),
+            request_loss_rate: Arc::new(Mutex::new(config.server.request_loss_rate)),
+            rng: Mutex::new(config.server.rng_seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64)),
+            rate_limiter: (config.server.requests_per_sec > 0.0)
+                .then(|| Mutex::new(RateLimiter::new(config.server.requests_per_sec, config.server.burst))),
+            config,
+            byte_order,
+            controller,
+            store_request: Arc::new(Mutex::new(HashMap::new())),
+            in_progress: Mutex::new(HashSet::new()),
+            shutdown: Arc::new(AtomicBool::new(false)),
+            actions: builtin_actions(),
+        };
+
+        // 重启后恢复去重存储,使跨重启的重试仍能命中缓存的响应
+        if let Some(store_file) = server.config.server.store_file.clone() {
+            if Path::new(&store_file).exists() {
+                server.load_from_file(&store_file)?;
+            }
+        }
+        Ok(server)
+    }
+
+    /// 将去重存储写入配置的持久化文件(未配置时不做任何操作)
+    pub fn flush(&self) -> io::Result<()> {
+        match &self.config.server.store_file {
+            Some(store_file) => self.save_to_file(store_file),
+            None => Ok(()),
+        }
+    }
+
+    /// 将去重存储保存到文件
+    ///
+    /// 文件内容使用本crate的序列化格式:条目数,随后每个条目依次为request_id(Int64)、时间戳和响应字节(Bytes)
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let store = self.store_request.lock().unwrap();
+        let mut serializer = Serializer::new(self.byte_order);
+        serializer.serialize_int32(store.len() as i32)?;
+        for (request_id, info) in store.iter() {
+            serializer.serialize_int64(*request_id as i64)?;
+            serializer.serialize_string(&info.timestamp.format(STORE_TIMESTAMP_FORMAT).to_string())?;
+            serializer.serialize_bytes(&info.response)?;
+        }
+        fs::write(path, serializer.get_buffer())?;
+        tracing::info!("Saved {} stored responses", store.len());
+        Ok(())
+    }
+
+    /// 从文件加载去重存储,丢弃超过dedup_ttl的条目
+    pub fn load_from_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+        let data = fs::read(path)?;
+        let mut deserializer = Deserializer::new(&data, self.byte_order);
+        let ttl = chrono::Duration::seconds(self.config.server.dedup_ttl as i64);
+        let now = Utc::now().naive_utc();
+
+        let count = deserializer.deserialize_next()?.as_i32().ok_or_else(|| invalid("Invalid store entry count"))?;
+        let mut store = self.store_request.lock().unwrap();
+        for _ in 0..count {
+            let request_id = deserializer.deserialize_next()?.as_i64().ok_or_else(|| invalid("Invalid store request_id"))? as u64;
+            let timestamp = deserializer.deserialize_next()?.as_string()
+                .and_then(|timestamp| NaiveDateTime::parse_from_str(timestamp, STORE_TIMESTAMP_FORMAT).ok())
+                .ok_or_else(|| invalid("Invalid store timestamp"))?;
+            let response = match deserializer.deserialize_next()? {
+                Value::Bytes(bytes) => bytes,
+                _ => return Err(invalid("Invalid store response")),
+            };
+
+            if now - timestamp <= ttl {
+                store.insert(request_id, RequestInfo { timestamp, response });
+            }
+        }
+        tracing::info!("Loaded {} stored responses", store.len());
+        Ok(())
+    }
+
+    /// 清除去重存储中超过dedup_ttl的条目,返回清除的条目数
+    pub fn sweep_store(&self) -> usize {
+        let ttl = chrono::Duration::seconds(self.config.server.dedup_ttl as i64);
+        let now = Utc::now().naive_utc();
+        let mut store = self.store_request.lock().unwrap();
+        let before = store.len();
+        store.retain(|_, info| now - info.timestamp <= ttl);
+        let removed = before - store.len();
+        if removed > 0 {
+            tracing::info!("Swept {} expired stored responses", removed);
+        }
+        removed
+    }
+
+    /// 监视配置文件,文件变化时更新响应和请求丢包率,从下一个请求开始生效
+    ///
+    /// 航班状态和去重存储不受影响;其他配置项仍需重启服务器才能生效
+    pub fn watch_config(&self, interval: Duration) {
+        let loss_rate = Arc::clone(&self.loss_rate);
+        let request_loss_rate = Arc::clone(&self.request_loss_rate);
+        self.config.watch(interval, move |config| {
+            *loss_rate.lock().unwrap() = config.server.loss_rate;
+            *request_loss_rate.lock().unwrap() = config.server.request_loss_rate;
+        });
+    }
+
+    /// 注册action对应的请求处理函数,无需修改分发逻辑即可添加新操作
+    ///
+    /// 已存在的action(包括内置操作)会被覆盖;action 0 是心跳请求,在分发之前处理,不能被覆盖
+    pub fn register_action<F: ActionFn + 'static>(&mut self, action: u8, handler: F) {
+        self.actions.insert(action, Box::new(handler));
+    }
+
+    /// 返回服务器实际绑定的地址(绑定到端口0时可用于获取分配的端口)
+    pub fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// 返回关闭标志,置为true后run会停止接收请求、通知监控客户端并返回
+    pub fn shutdown_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.shutdown)
+    }
+
+    /// 循环处理客户端请求,直到关闭标志被置为true
+    ///
+    /// workers为1时在当前线程中按到达顺序逐个处理;大于1时由当前线程接收数据报,
+    /// 再分发给工作线程并行处理,响应由工作线程直接发送。多线程模式下不保证处理顺序,
+    /// 同一客户端先后发出的请求也可能乱序执行;at-most-once请求仍然只执行一次。
+    /// 另有一个线程定期清理过期的监控订阅和去重条目;配置了heartbeat_interval时它还定期向监控客户端推送航班当前状态
+    pub fn run(&self) -> Result<(), Box<dyn Error>> {
+        let heartbeat_interval = self.config.server.heartbeat_interval;
+        thread::scope(|scope| {
+            scope.spawn(|| self.housekeeping((heartbeat_interval > 0).then(|| Duration::from_secs(heartbeat_interval))));
+            self.serve()
+        })
+    }
+
+    /// 每隔MONITOR_SWEEP_INTERVAL清理一次过期监控和过期的去重条目,设置了heartbeat时每隔heartbeat推送一次航班当前状态,
+    /// 直到关闭标志被置为true
+    fn housekeeping(&self, heartbeat: Option<Duration>) {
+        let mut next_sweep = Instant::now() + MONITOR_SWEEP_INTERVAL;
+        let mut next_heartbeat = heartbeat.map(|interval| Instant::now() + interval);
+        while !self.shutdown.load(Ordering::SeqCst) {
+            thread::sleep(SHUTDOWN_POLL_INTERVAL);
+            let now = Instant::now();
+            if let (Some(next), Some(interval)) = (next_heartbeat.as_mut(), heartbeat) {
+                if now >= *next {
+                    // 推送前会先清理过期监控;这里重新计时了清理周期,所以一并清理去重存储
+                    self.controller.send_heartbeat(&self.socket);
+                    self.sweep_store();
+                    *next += interval;
+                    next_sweep = now + MONITOR_SWEEP_INTERVAL;
+                }
+            }
+            if now >= next_sweep {
+                self.controller.clean_expired_monitors(&self.socket);
+                // 清理过期的去重条目,避免存储无限增长
+                self.sweep_store();
+                next_sweep = now + MONITOR_SWEEP_INTERVAL;
+            }
+        }
+    }
+
+    /// 接收并处理请求直到关闭,随后通知监控客户端
+    fn serve(&self) -> Result<(), Box<dyn Error>> {
+        tracing::info!("UDP Server listening on {} with {} worker(s)", self.local_addr()?, self.config.server.workers);
+        if self.config.server.workers <= 1 {
+            while !self.shutdown.load(Ordering::SeqCst) {
+                if let Err(e) = self.run_once() {
+                    tracing::error!("Error processing datagram: {}", e);
+                }
+            }
+            self.controller.notify_shutdown(&self.socket);
+            return Ok(());
+        }
+
+        let (sender, receiver) = mpsc::channel::<(Vec<u8>, SocketAddr)>();
+        let receiver = Mutex::new(receiver);
+        thread::scope(|scope| {
+            for _ in 0..self.config.server.workers {
+                scope.spawn(|| loop {
+                    // 只在取任务时持有锁,处理请求时其他工作线程可以继续取任务
+                    let job = receiver.lock().unwrap().recv();
+                    match job {
+                        Ok((data, src)) => {
+                            if let Err(e) = self.process(&data, src) {
+                                tracing::error!("Error processing datagram from {}: {}", src, e);
+                            }
+                        }
+                        // 发送端已关闭,退出工作线程
+                        Err(_) => break,
+                    }
+                });
+            }
+
+            while !self.shutdown.load(Ordering::SeqCst) {
+                if let Some((data, src)) = self.receive() {
+                    sender.send((data, src))?;
+                }
+            }
+            // 关闭发送端,工作线程处理完剩余请求后退出
+            drop(sender);
+            Ok::<(), Box<dyn Error>>(())
+        })?;
+        self.controller.notify_shutdown(&self.socket);
+        Ok(())
+    }
+
+    /// 接收并处理一个数据报
+    pub fn run_once(&self) -> Result<(), Box<dyn Error>> {
+        match self.receive() {
+            Some((data, src)) => self.process(&data, src),
+            None => Ok(()),
+        }
+    }
+
+    /// 接收一个数据报,读超时、接收失败、客户端超过限流或模拟请求丢失时返回None
+    fn receive(&self) -> Option<(Vec<u8>, SocketAddr)> {
+        let mut buf = [0; 4096];
+        match self.socket.recv_from(&mut buf) {
+            Ok((_, src)) if self.rate_limiter.as_ref().is_some_and(|limiter| !limiter.lock().unwrap().allow(src)) => {
+                tracing::warn!("Rate limit exceeded, dropping datagram from {}", src);
+                None
+            }
+            Ok((_, src)) if self.roll() < *self.request_loss_rate.lock().unwrap() => {
+                tracing::info!("Request loss rate triggered, dropped request from {}", src);
+                None
+            }
+            Ok((amt, src)) => Some((buf[..amt].to_vec(), src)),
+            Err(ref e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => None,
+            Err(e) => {
+                tracing::error!("Couldn't receive a datagram: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 处理一个来自src的数据报并投递响应
+    fn process(&self, request_data: &[u8], src: SocketAddr) -> Result<(), Box<dyn Error>> {
+        match self.respond(request_data, src)? {
+            // 心跳请求不涉及航班状态,直接应答,不经过丢包模拟
+            Some(Reply::Pong(response)) => {
+                self.socket.send_to(&response, src)?;
+                tracing::debug!("Sent pong to {}", src);
+            }
+            Some(Reply::Response(response)) => self.deliver(&response, src)?,
+            None => {}
+        }
+        Ok(())
+    }
+
+    /// 处理一个来自src的数据报,返回应发回给客户端的响应字节,不经过丢包模拟也不发送
+    ///
+    /// 去重逻辑与run相同:at-most-once请求重复到达时返回存储的响应。
+    /// 返回None表示不应答(同一请求正在被处理,或请求执行失败)
+    pub fn handle_datagram(&self, request_data: &[u8], src: SocketAddr) -> Result<Option<Vec<u8>>, Box<dyn Error>> {
+        Ok(self.respond(request_data, src)?.map(|reply| match reply {
+            Reply::Pong(response) | Reply::Response(response) => response,
+        }))
+    }
+
+    /// 解析请求并执行(或从去重存储中取出)响应
+    ///
+    /// 先读取定长的请求头(Envelope),心跳和去重只依赖请求头;确定需要执行请求时才解码请求体
+    fn respond(&self, request_data: &[u8], src: SocketAddr) -> Result<Option<Reply>, Box<dyn Error>> {
+        let mut deserializer = Deserializer::new(request_data, self.byte_order);
+        let envelope = match Envelope::deserialize(&mut deserializer) {
+            Ok(envelope) => envelope,
+            Err(SerializationError::ByteOrderMismatch { found, .. }) => return Ok(self.byte_order_mismatch(request_data, found, src)),
+            Err(e) => return Err(e.into()),
+        };
+        tracing::debug!("Request {} from {} (invocation semantic {}, action {})", envelope.request_id, src, envelope.invocation_semantic, envelope.action);
+
+        // 心跳请求不涉及航班状态,不经过去重
+        if envelope.action == 0 {
+            let mut response = ping();
+            response.insert("request_id".to_string(), envelope.request_id.to_string().into());
+            let mut serializer = Serializer::new(self.byte_order);
+            serializer.serialize_value_map(&response)?;
+            return Ok(Some(Reply::Pong(serializer.get_buffer())));
+        }
+
+        // 未知的调用语义回复400,而不是不应答让客户端一直重试
+        let at_most_once = match InvocationSemantic::try_from(envelope.invocation_semantic) {
+            Ok(semantic) => semantic == InvocationSemantic::AtMostOnce,
+            Err(semantic) => {
+                tracing::warn!("Unknown invocation semantic {} from {}", semantic, src);
+                return self.bad_request(&envelope, "Unknown invocation semantic");
+            }
+        };
+
+        // 先执行(或从去重存储中取出)响应,再单独决定是否投递:
+        // 副作用最多执行一次,丢包模拟只影响投递
+        if at_most_once {
+            let cached = self.store_request.lock().unwrap().get(&envelope.request_id).map(|info| info.response.clone());
+            if let Some(response) = cached {
+                // 如果已经处理过，直接使用存储的响应,无需解码请求体
+                tracing::debug!("Replaying cached response to {}", src);
+                return Ok(Some(Reply::Response(response)));
+            }
+        }
+
+        // 请求头完好但请求体无法解码时同样回复400,客户端能按request_id认领这个错误
+        let payload = match deserializer.deserialize_next() {
+            Ok(payload) => payload,
+            Err(e) => {
+                tracing::warn!("Undecodable payload from {}: {}", src, e);
+                return self.bad_request(&envelope, "Invalid payload format");
+            }
+        };
+        let Some(payload) = payload.as_map() else {
+            tracing::warn!("Payload from {} is not a map", src);
+            return self.bad_request(&envelope, "Invalid payload format");
+        };
+
+        let response = if at_most_once {
+            // 同一请求的重传正由其他工作线程处理,丢弃本次重传,客户端会再次重试
+            if !self.in_progress.lock().unwrap().insert(envelope.request_id) {
+                tracing::debug!("Request {} is already being processed, dropping duplicate", envelope.request_id);
+                return Ok(None);
+            }
+            // 上面检查store_request和登记处理中标记之间,另一个工作线程可能刚好执行完同一请求
+            // 并清除了标记,登记成功后需要再检查一次,否则会重复执行
+            let cached = self.store_request.lock().unwrap().get(&envelope.request_id).map(|info| info.response.clone());
+            if let Some(response) = cached {
+                self.in_progress.lock().unwrap().remove(&envelope.request_id);
+                return Ok(Some(Reply::Response(response)));
+            }
+            self.execute_once(&envelope, payload, src)
+        } else {
+            // 每次收到请求都重新执行
+            self.execute(&envelope, payload, src)
+        };
+
+        Ok(response.map(Reply::Response))
+    }
+
+    /// 构造带request_id的400错误回复,用于请求头可以解析但请求本身无法处理的情况
+    fn bad_request(&self, envelope: &Envelope, message: &str) -> Result<Option<Reply>, Box<dyn Error>> {
+        let mut response = error_response(STATUS_BAD_REQUEST, message);
+        response.insert("request_id".to_string(), envelope.request_id.to_string().into());
+        let mut serializer = Serializer::new(self.byte_order);
+        serializer.serialize_value_map(&response)?;
+        Ok(Some(Reply::Response(serializer.get_buffer())))
+    }
+
+    /// 请求头的字节序标志与服务器不一致,说明客户端配置了不同的字节序:
+    /// 按客户端的字节序读出request_id并回复400错误,避免客户端把数字解码错或一直重试
+    fn byte_order_mismatch(&self, request_data: &[u8], peer_order: ByteOrder, src: SocketAddr) -> Option<Reply> {
+        let mut deserializer = Deserializer::new(request_data, peer_order);
+        let envelope = Envelope::deserialize(&mut deserializer).ok()?;
+        tracing::warn!("Byte order mismatch: {} sent a {} endian request, server uses {} endian", src, peer_order, self.byte_order);
+
+        let message = format!("Byte order mismatch: server uses {} endian", self.byte_order);
+        let mut response = error_response(STATUS_BAD_REQUEST, &message);
+        response.insert("request_id".to_string(), envelope.request_id.to_string().into());
+        let mut serializer = Serializer::new(peer_order);
+        serializer.serialize_value_map(&response).ok()?;
+        Some(Reply::Response(serializer.get_buffer()))
+    }
+
+    /// 执行at-most-once请求并在投递之前将响应存入store_request,随后清除其处理中标记
+    fn execute_once(&self, envelope: &Envelope, payload: &HashMap<String, Value>, src: SocketAddr) -> Option<Vec<u8>> {
+        let response = self.execute(envelope, payload, src);
+        if let Some(response) = &response {
+            let mut store = self.store_request.lock().unwrap();
+            store.insert(envelope.request_id, RequestInfo {
+                timestamp: Utc::now().naive_utc(),
+                response: response.clone(),
+            });
+            tracing::debug!("Stored response for request {}, {} stored", envelope.request_id, store.len());
+        }
+        self.in_progress.lock().unwrap().remove(&envelope.request_id);
+        response
+    }
+
+    /// 执行请求并返回序列化后的响应,处理失败时记录错误并返回None
+    fn execute(&self, envelope: &Envelope, payload: &HashMap<String, Value>, src: SocketAddr) -> Option<Vec<u8>> {
+        match self.handle_request(envelope, payload, src) {
+            Ok(response) => Some(response),
+            Err(e) => {
+                tracing::error!("Error processing request: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 处理客户端请求:按请求头中的action调用处理函数,返回序列化后的响应
+    fn handle_request(&self, envelope: &Envelope, payload: &HashMap<String, Value>, src: SocketAddr) -> Result<Vec<u8>, Box<dyn Error>> {
+        tracing::info!("----------------------------------");
+        tracing::info!("{:?} Request: {:?} {:?}", src, envelope, payload);
+
+        // 根据action调用注册的处理函数,请求字段缺失或格式错误时返回400错误响应而不是中断服务器
+        let result = match self.actions.get(&envelope.action) {
+            Some(handler) => handler(payload, &self.controller, src, &self.socket, &self.config.server),
+            None => Err("Invalid action".into()),
+        };
+        let mut response = result.unwrap_or_else(|e| error_response(STATUS_BAD_REQUEST, &e.to_string()));
+
+        // 添加request_id到响应中
+        response.insert("request_id".to_string(), envelope.request_id.to_string().into());
+
+        tracing::info!("Response: {:?}", response);
+
+        // 序列化响应数据
+        let mut serializer = Serializer::new(self.byte_order);
+        serializer.serialize_value_map(&response)?;
+        Ok(serializer.get_buffer())
+    }
+
+    /// 为丢包模拟生成[0, 1)之间的随机数
+    fn roll(&self) -> f32 {
+        self.rng.lock().unwrap().gen::<f32>()
+    }
+
+    /// 投递响应,按当前丢包率模拟响应丢失
+    fn deliver(&self, response: &[u8], src: SocketAddr) -> io::Result<()> {
+        let loss_rate = *self.loss_rate.lock().unwrap();
+        if self.roll() >= loss_rate {
+            self.socket.send_to(response, src)?;
+            tracing::debug!("Sent response to {}", src);
+        } else {
+            tracing::info!("Loss rate triggered, dropped response to {}", src);
+        }
+        Ok(())
+    }
+}
+
+/// 按配置的地址绑定服务器socket
+///
+/// 地址通过 to_socket_addrs 解析,同时支持IPv4和IPv6(如 "[::]:8888")
+pub fn bind_socket(config: &ServerConfig) -> io::Result<UdpSocket> {
+    let server_addrs: Vec<SocketAddr> = config.address.to_socket_addrs()?.collect();
+    UdpSocket::bind(&server_addrs[..])
+}
+
+/// 在已绑定的socket上运行服务器:监视配置文件、处理请求直到Ctrl-C,退出前持久化去重存储
+///
+/// controller应已添加好航班;传入完整的Config以便从其来源文件热加载丢包率。
+/// 每个进程只能调用一次(Ctrl-C处理函数只能注册一次)
+pub fn run_server(socket: UdpSocket, controller: FlightController, config: Config) -> Result<(), Box<dyn Error>> {
+    let server = Server::from_socket(socket, controller, config)?;
+    // 监视配置文件,运行时修改loss_rate无需重启服务器
+    server.watch_config(Duration::from_secs(1));
+    // Ctrl-C时设置关闭标志,run返回后持久化去重存储再退出
+    let shutdown = server.shutdown_handle();
+    ctrlc::set_handler(move || shutdown.store(true, Ordering::SeqCst))?;
+    let result = server.run();
+    // 退出前持久化去重存储
+    server.flush()?;
+    result
+}
+
+/// 内置操作的请求处理函数,按action索引;action 0(心跳)在分发之前处理,11为重置航班的管理命令,12为多航班预订,13为批量查询航班详情,14为统计数据,15为查询自己的监控订阅
+fn builtin_actions() -> HashMap<u8, ActionHandler> {
+    let mut actions: HashMap<u8, ActionHandler> = HashMap::new();
+    actions.insert(1, Box::new(|payload, controller, _, socket, _| query_flight_ids(payload, controller, socket)));
+    actions.insert(2, Box::new(|payload, controller, _, socket, _| query_flight_details(payload, controller, socket)));
+    actions.insert(3, Box::new(|payload, controller, _, socket, _| reserve_seats(payload, controller, socket)));
+    actions.insert(4, Box::new(|payload, controller, src, socket, _| monitor_flight(payload, controller, src, socket)));
+    actions.insert(6, Box::new(|payload, controller, _, socket, _| cancel_reservation(payload, controller, socket)));
+    actions.insert(7, Box::new(|payload, controller, src, socket, _| unmonitor_flight(payload, controller, src, socket)));
+    actions.insert(8, Box::new(|payload, controller, _, socket, _| query_flights_by_date(payload, controller, socket)));
+    actions.insert(11, Box::new(reset_flight));
+    actions.insert(12, Box::new(|payload, controller, _, socket, _| reserve_multi(payload, controller, socket)));
+    actions.insert(13, Box::new(|payload, controller, _, socket, _| query_flight_details_batch(payload, controller, socket)));
+    actions.insert(14, Box::new(|_, controller, _, socket, _| flight_stats(controller, socket)));
+    actions.insert(15, Box::new(|_, controller, src, socket, _| list_my_monitors(controller, src, socket)));
+    actions
+}
+
+/// 读取请求中的字符串字段,缺失或类型错误时返回描述性错误
+fn required_field<'a>(payload: &'a HashMap<String, Value>, field: &str) -> Result<&'a String, String> {
+    payload.get(field)
+        .ok_or_else(|| format!("Missing field: {}", field))?
+        .as_string()
+        .ok_or_else(|| format!("Invalid field: {}", field))
+}
+
+/// 读取请求中的字段并解析为指定类型
+fn parse_field<T: FromStr>(payload: &HashMap<String, Value>, field: &str) -> Result<T, String> {
+    required_field(payload, field)?.parse().map_err(|_| format!("Invalid field: {}", field))
+}
+
+/// 构造带状态码的错误响应
+fn error_response(status: &str, message: &str) -> HashMap<String, Value> {
+    let mut data = HashMap::new();
+    data.insert("status".to_string(), status.into());
+    data.insert("message".to_string(), message.into());
+    data
+}
+
+/// 按控制器返回的错误信息选择状态码:找不到航班为404,其余为400
+fn error_status(message: &str) -> &'static str {
+    if message == controller::FLIGHT_NOT_FOUND || message == controller::NO_MATCHING_FLIGHTS {
+        STATUS_NOT_FOUND
+    } else {
+        STATUS_BAD_REQUEST
+    }
+}
+
+/// 心跳检测,附带服务器当前时间(Unix秒,Int64),不访问航班数据
+fn ping() -> HashMap<String, Value> {
+    let mut data = HashMap::new();
+    data.insert("status".to_string(), STATUS_OK.into());
+    data.insert("server_time".to_string(), Utc::now().timestamp().into());
+    data
+}
+
+/// 查询航班ID
+fn query_flight_ids(payload: &HashMap<String, Value>, controller: &FlightController,  socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let source = required_field(payload, "source")?;
+    let destination = required_field(payload, "destination")?;
+
+    // max_fare为可选字段(单位为分),缺省时不限制票价
+    let max_fare = payload.get("max_fare").map(|_| parse_field::<i64>(payload, "max_fare")).transpose()?;
+
+    let request = controller::Request::QueryFlightIds { source: source.to_string(), destination: destination.to_string(), max_fare };
+    let response = controller.handle_request(request, socket, None); 
+
+    tracing::info!("response: {:?}", response);
+
+    Ok(flight_ids_response(response))
+}
+
+/// 按出发时间范围查询航班ID,时间格式为"%Y-%m-%d %H:%M:%S",两端均包含
+fn query_flights_by_date(payload: &HashMap<String, Value>, controller: &FlightController, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let source = required_field(payload, "source")?;
+    let destination = required_field(payload, "destination")?;
+    let from = NaiveDateTime::parse_from_str(required_field(payload, "from")?, "%Y-%m-%d %H:%M:%S").map_err(|_| "Invalid field: from")?;
+    let to = NaiveDateTime::parse_from_str(required_field(payload, "to")?, "%Y-%m-%d %H:%M:%S").map_err(|_| "Invalid field: to")?;
+
+    let request = controller::Request::QueryFlightsByDate { source: source.to_string(), destination: destination.to_string(), from, to };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    Ok(flight_ids_response(response))
+}
+
+/// 将航班ID查询结果转换为响应数据
+fn flight_ids_response(response: controller::Response) -> HashMap<String, Value> {
+    match response {
+        controller::Response::FlightIds(flight_ids) => {
+            if flight_ids.is_empty() {
+                let mut data = HashMap::new();
+                data.insert("status".to_string(), STATUS_NOT_FOUND.into());
+                data.insert("message".to_string(), controller::NO_MATCHING_FLIGHTS.into());
+                data
+            } else {
+                // 航班ID以Int32数组发送,客户端无需再拆分字符串
+                let mut data = HashMap::new();
+                data.insert("status".to_string(), STATUS_OK.into());
+                data.insert("flight_ids".to_string(), Value::Array(flight_ids.into_iter().map(Value::Int32).collect()));
+                data
+            } 
+        }
+        controller::Response::Error(e) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+            data
+        }
+        _ => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+            data
+        }
+    }
+}
+
+/// 查询航班详情
+fn query_flight_details(payload: &HashMap<String, Value>, controller: &FlightController, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let flight_id = parse_field::<i32>(payload, "flight_id")?;
+
+    let request = controller::Request::QueryFlightDetails { flight_id };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    match response {
+        controller::Response::FlightDetails { departure_time, airfare, seats_available } => {
+            // 字段按实际类型发送:出发时间为DateTime,票价(分)为Int64,座位数为Int32;缺少的字段发送Null
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_OK.into());
+            data.insert("departure_time".to_string(), departure_time.map_or(Value::Null, Value::DateTime));
+            data.insert("airfare".to_string(), airfare.map_or(Value::Null, Value::from));
+            data.insert("seats_available".to_string(), seats_available.map_or(Value::Null, Value::from));
+            Ok(data)
+        }
+        controller::Response::Error(e) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+            Ok(data)
+        }
+        _ => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+            Ok(data)
+        }   
+    }
+}
+
+/// 批量查询航班详情,flight_ids为逗号分隔的航班号
+///
+/// flights为与请求顺序一致的数组,每个元素是一个映射:包含flight_id和status,
+/// 找到的航班带有与单个查询相同类型的详情字段,找不到的航班为404并带有message
+fn query_flight_details_batch(payload: &HashMap<String, Value>, controller: &FlightController, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let flight_ids = required_field(payload, "flight_ids")?
+        .split(',')
+        .map(|id| id.trim().parse::<i32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| "Invalid flight_ids".to_string())?;
+
+    let request = controller::Request::QueryFlightDetailsBatch { flight_ids };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    let mut data = HashMap::new();
+    match response {
+        controller::Response::FlightDetailsBatch(details) => {
+            let flights = details
+                .into_iter()
+                .map(|(flight_id, record)| {
+                    let mut entry = HashMap::new();
+                    entry.insert("flight_id".to_string(), Value::from(flight_id));
+                    match record {
+                        Ok(record) => {
+                            entry.insert("status".to_string(), STATUS_OK.into());
+                            entry.insert("departure_time".to_string(), Value::DateTime(record.departure_time));
+                            entry.insert("airfare".to_string(), record.airfare.into());
+                            entry.insert("seats_available".to_string(), record.seats_available.into());
+                        }
+                        Err(e) => {
+                            entry.insert("status".to_string(), error_status(&e).into());
+                            entry.insert("message".to_string(), e.into());
+                        }
+                    }
+                    Value::Map(entry)
+                })
+                .collect::<Vec<_>>();
+            data.insert("status".to_string(), STATUS_OK.into());
+            data.insert("flights".to_string(), flights.into());
+        }
+        _ => {
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+        }
+    }
+    Ok(data)
+}
+
+/// 所有航班的统计数据:航班数、总座位数、已售座位数和平均票价(分)
+fn flight_stats(controller: &FlightController, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let response = controller.handle_request(controller::Request::Stats, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    let mut data = HashMap::new();
+    match response {
+        controller::Response::Stats(stats) => {
+            data.insert("status".to_string(), STATUS_OK.into());
+            data.insert("flights".to_string(), stats.flights.into());
+            data.insert("total_seats".to_string(), stats.total_seats.into());
+            data.insert("seats_sold".to_string(), stats.seats_sold.into());
+            data.insert("average_fare".to_string(), stats.average_fare.into());
+        }
+        _ => {
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+        }
+    }
+    Ok(data)
+}
+
+/// 列出请求方地址当前的监控订阅:航班号和距离到期的秒数
+///
+/// 订阅按源地址匹配,客户端换了socket或经过NAT后地址变化时看不到之前的订阅
+fn list_my_monitors(controller: &FlightController, client_addr: SocketAddr, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let response = controller.handle_request(controller::Request::ListMyMonitors, socket, Some(client_addr));
+    tracing::info!("response: {:?}", response);
+
+    let mut data = HashMap::new();
+    match response {
+        controller::Response::MyMonitors(monitors) => {
+            let monitors = monitors
+                .into_iter()
+                .map(|(flight_id, remaining)| {
+                    let mut entry = HashMap::new();
+                    entry.insert("flight_id".to_string(), Value::from(flight_id));
+                    entry.insert("remaining".to_string(), Value::from(remaining));
+                    Value::Map(entry)
+                })
+                .collect::<Vec<_>>();
+            data.insert("status".to_string(), STATUS_OK.into());
+            data.insert("monitors".to_string(), monitors.into());
+        }
+        _ => {
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+        }
+    }
+    Ok(data)
+}
+
+/// 预订座位
+fn reserve_seats(payload: &HashMap<String, Value>, controller: &FlightController, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let flight_id = parse_field::<i32>(payload, "flight_id")?;
+    let seats = parse_field::<i32>(payload, "seats")?;
+    // idempotency_key为可选字段,重复的key不会再次预订
+    let idempotency_key = payload.get("idempotency_key").and_then(|key| key.as_string()).cloned();
+
+    let request = controller::Request::ReserveSeats { flight_id, seats, idempotency_key };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    match response {
+        controller::Response::Reservation(reservation_result) => match reservation_result {
+            Err(message) => {
+                let mut data = HashMap::new();
+                data.insert("status".to_string(), error_status(&message).into());
+                data.insert("message".to_string(), message.into());
+                Ok(data)
+            }
+            Ok(overbooked) => {
+                let mut data = HashMap::new();
+                data.insert("status".to_string(), STATUS_OK.into());
+                // 超出座位容量(超售)的座位数
+                data.insert("overbooked".to_string(), Value::Int32(overbooked));
+                Ok(data)
+            }
+        },
+        controller::Response::Error(e) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+            Ok(data)
+        }
+        _ => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+            Ok(data)
+        }
+    }
+}
+
+/// 解析多航班预订的legs字段,格式为 "flight_id:seats,flight_id:seats"
+fn parse_legs(legs: &str) -> Result<Vec<(i32, i32)>, String> {
+    legs.split(',')
+        .map(|leg| {
+            let (flight_id, seats) = leg.split_once(':').ok_or_else(|| format!("Invalid leg: {}", leg))?;
+            match (flight_id.trim().parse(), seats.trim().parse()) {
+                (Ok(flight_id), Ok(seats)) => Ok((flight_id, seats)),
+                _ => Err(format!("Invalid leg: {}", leg)),
+            }
+        })
+        .collect()
+}
+
+/// 一次预订多个航班的座位,任何一段失败时所有航班都不预订,failed_leg为失败的那一段(从0开始)
+fn reserve_multi(payload: &HashMap<String, Value>, controller: &FlightController, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let items = parse_legs(required_field(payload, "legs")?)?;
+
+    let request = controller::Request::ReserveMulti { items };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    let mut data = HashMap::new();
+    match response {
+        controller::Response::MultiReservation(Ok(())) => {
+            data.insert("status".to_string(), STATUS_OK.into());
+        }
+        controller::Response::MultiReservation(Err((leg, e))) => {
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+            data.insert("failed_leg".to_string(), (leg as i32).into());
+        }
+        _ => {
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+        }
+    }
+    Ok(data)
+}
+
+/// 取消预订
+fn cancel_reservation(payload: &HashMap<String, Value>, controller: &FlightController, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let flight_id = parse_field::<i32>(payload, "flight_id")?;
+    let seats = parse_field::<i32>(payload, "seats")?;
+
+    let request = controller::Request::CancelReservation { flight_id, seats };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    match response {
+        controller::Response::Cancellation(Ok(())) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_OK.into());
+            Ok(data)
+        }
+        controller::Response::Cancellation(Err(e)) | controller::Response::Error(e) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+            Ok(data)
+        }
+        _ => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+            Ok(data)
+        }
+    }
+}
+
+/// 监控航班
+fn monitor_flight(payload: &HashMap<String, Value>, controller: &FlightController, client_addr: SocketAddr, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let flight_id = parse_field::<i32>(payload, "flight_id")?;
+    // 超出i32范围的监控时长同样视为非法,返回400而不是让线程panic
+    let monitor_interval = match required_field(payload, "monitor_interval")?.parse::<i32>() {
+        Ok(monitor_interval) => monitor_interval,
+        Err(_) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_BAD_REQUEST.into());
+            data.insert("message".to_string(), "Invalid monitor interval".into());
+            return Ok(data);
+        }
+    };
+
+    // threshold为可选字段,缺省为0,即每次变化都推送
+    let threshold = match payload.get("threshold") {
+        Some(_) => parse_field::<i32>(payload, "threshold")?,
+        None => 0,
+    };
+
+    let request = controller::Request::MonitorFlight { flight_id: flight_id, monitor_interval: monitor_interval, threshold };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, Some(client_addr));
+    tracing::info!("response: {:?}", response);
+
+    match response {
+        controller::Response::MonitoringStarted(monitor_result) => {
+            if monitor_result.is_err() {
+                let mut data = HashMap::new();
+                let message = monitor_result.err().unwrap();
+                data.insert("status".to_string(), error_status(&message).into());
+                data.insert("message".to_string(), message.into());
+                Ok(data)
+            } else {
+                let mut data = HashMap::new();  
+                data.insert("status".to_string(), STATUS_OK.into());
+                Ok(data)
+            }
+        }
+        controller::Response::Error(e) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+            Ok(data)
+        }
+        _ => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+            Ok(data)
+        }
+    }
+}
+
+/// 停止监控航班
+fn unmonitor_flight(payload: &HashMap<String, Value>, controller: &FlightController, client_addr: SocketAddr, socket: &UdpSocket) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    let flight_id = parse_field::<i32>(payload, "flight_id")?;
+
+    let request = controller::Request::Unmonitor { flight_id };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, Some(client_addr));
+    tracing::info!("response: {:?}", response);
+
+    let mut data = HashMap::new();
+    match response {
+        controller::Response::MonitoringStopped(Ok(())) => {
+            data.insert("status".to_string(), STATUS_OK.into());
+        }
+        controller::Response::MonitoringStopped(Err(e)) | controller::Response::Error(e) => {
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+        }
+        _ => {
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+        }
+    }
+    Ok(data)
+}
+
+/// 重置航班座位(管理命令,需要在配置中开启 allow_admin_commands)
+fn reset_flight(payload: &HashMap<String, Value>, controller: &FlightController, _client_addr: SocketAddr, socket: &UdpSocket, config: &ServerConfig) -> Result<HashMap<String, Value>, Box<dyn Error>> {
+    if !config.allow_admin_commands {
+        let mut data = HashMap::new();
+        data.insert("status".to_string(), STATUS_BAD_REQUEST.into());
+        data.insert("message".to_string(), "Admin commands are disabled".into());
+        return Ok(data);
+    }
+
+    let flight_id = parse_field::<i32>(payload, "flight_id")?;
+
+    let request = controller::Request::ResetFlight { flight_id };
+    tracing::info!("request: {:?}", request);
+    let response = controller.handle_request(request, socket, None);
+    tracing::info!("response: {:?}", response);
+
+    match response {
+        controller::Response::FlightReset(Ok(seats_available)) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_OK.into());
+            data.insert("seats_available".to_string(), seats_available.into());
+            Ok(data)
+        }
+        controller::Response::FlightReset(Err(e)) | controller::Response::Error(e) => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), error_status(&e).into());
+            data.insert("message".to_string(), e.into());
+            Ok(data)
+        }
+        _ => {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_INTERNAL_ERROR.into());
+            data.insert("message".to_string(), "Unknown error".into());
+            Ok(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按客户端的方式编码请求:请求头后跟字段map
+    fn request_packet(request_id: u64, action: u8, fields: &[(&str, &str)]) -> Vec<u8> {
+        let payload: HashMap<String, String> = fields.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        Envelope { request_id, invocation_semantic: InvocationSemantic::AtMostOnce.into(), action }.serialize(&mut serializer).unwrap();
+        serializer.serialize_map(&payload).unwrap();
+        serializer.get_buffer()
+    }
+
+    fn status_of(response: &[u8]) -> String {
+        let decoded = Deserializer::new(response, ByteOrder::Little).deserialize_next().unwrap();
+        decoded.as_map().unwrap()["status"].as_string().unwrap().clone()
+    }
+
+    /// 发送一个请求并解码响应map
+    fn request(server: &Server, request_id: u64, action: u8, fields: &[(&str, &str)]) -> HashMap<String, Value> {
+        let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let response = server.handle_datagram(&request_packet(request_id, action, fields), src).unwrap().unwrap();
+        Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap().as_map().unwrap().clone()
+    }
+
+    fn server_with_flight(capacity: i32) -> Server {
+        server_with_config(capacity, Config::default())
+    }
+
+    /// 按config创建服务器,其中有一个容量为capacity的航班1
+    fn server_with_config(capacity: i32, config: Config) -> Server {
+        let controller = FlightController::new();
+        controller.add_flight(controller::Flight {
+            flight_id: 1,
+            source: "Singapore".to_string(),
+            destination: "Tokyo".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 45000,
+            seats_available: capacity,
+            capacity,
+            overbook_limit: 0,
+        }).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        Server::from_socket(socket, controller, config).unwrap()
+    }
+
+    /// 等待client收到下一条消息并解码
+    fn receive_map(client: &UdpSocket) -> HashMap<String, Value> {
+        let mut buffer = [0u8; 1024];
+        let len = client.recv(&mut buffer).unwrap();
+        Deserializer::new(&buffer[..len], ByteOrder::Little).deserialize_next().unwrap().as_map().unwrap().clone()
+    }
+
+    #[test]
+    fn concurrent_retransmissions_do_not_oversell() {
+        const CAPACITY: i32 = 100;
+        const REQUESTS: u64 = 200;
+        const COPIES: usize = 8;
+        let server = server_with_flight(CAPACITY);
+        let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+
+        // 每个请求由多个线程同时重传,直到得到应答;任何一次重复执行都会多占一个座位
+        let answers: Vec<(u64, Vec<u8>)> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..REQUESTS)
+                .flat_map(|request_id| (0..COPIES).map(move |_| request_id))
+                .map(|request_id| {
+                    let server = &server;
+                    scope.spawn(move || {
+                        let packet = request_packet(request_id, 3, &[("flight_id", "1"), ("seats", "1")]);
+                        loop {
+                            if let Some(response) = server.handle_datagram(&packet, src).unwrap() {
+                                return (request_id, response);
+                            }
+                            thread::yield_now();
+                        }
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        let mut by_request: HashMap<u64, Vec<u8>> = HashMap::new();
+        for (request_id, response) in answers {
+            let first = by_request.entry(request_id).or_insert_with(|| response.clone());
+            assert_eq!(*first, response, "request {} was answered differently", request_id);
+        }
+        let reserved = by_request.values().filter(|response| status_of(response) == STATUS_OK).count();
+        assert_eq!(reserved, CAPACITY as usize);
+        assert_eq!(server.controller.flights()[&1].seats_available, 0);
+    }
+
+    #[test]
+    fn saved_store_replays_fresh_entries_and_drops_expired_ones() {
+        let path = std::env::temp_dir().join(format!("dedup-store-{}.bin", std::process::id()));
+        let server = server_with_flight(10);
+        let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let fresh = server.handle_datagram(&request_packet(1, 3, &[("flight_id", "1"), ("seats", "1")]), src).unwrap().unwrap();
+        server.handle_datagram(&request_packet(2, 3, &[("flight_id", "1"), ("seats", "1")]), src).unwrap().unwrap();
+        let ttl = chrono::Duration::seconds(server.config.server.dedup_ttl as i64);
+        server.store_request.lock().unwrap().get_mut(&2).unwrap().timestamp -= ttl + chrono::Duration::seconds(1);
+        server.save_to_file(&path).unwrap();
+
+        let mut restarted = server_with_flight(10);
+        restarted.load_from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // 请求头足以命中存储的响应;过期的条目不再命中,需要解码请求体并重新执行
+        let envelope_only = |request_id| request_packet(request_id, 3, &[])[..Envelope::LEN].to_vec();
+        assert_eq!(restarted.handle_datagram(&envelope_only(1), src).unwrap(), Some(fresh));
+        let missing_body = restarted.handle_datagram(&envelope_only(2), src).unwrap().unwrap();
+        assert_eq!(status_of(&missing_body), STATUS_BAD_REQUEST);
+        assert!(restarted.handle_datagram(&request_packet(2, 3, &[("flight_id", "1"), ("seats", "1")]), src).unwrap().is_some());
+        assert_eq!(restarted.controller.flights()[&1].seats_available, 9);
+    }
+
+    #[test]
+    fn big_endian_server_answers_big_endian_requests_and_rejects_little_endian_ones() {
+        let controller = FlightController::new();
+        let mut config = Config::default();
+        config.server.byte_order = "big".to_string();
+        let server = Server::from_socket(UdpSocket::bind("127.0.0.1:0").unwrap(), controller, config).unwrap();
+        let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let ping = |byte_order, request_id| {
+            let mut serializer = Serializer::new(byte_order);
+            Envelope { request_id, invocation_semantic: InvocationSemantic::AtMostOnce.into(), action: 0 }.serialize(&mut serializer).unwrap();
+            serializer.serialize_map(&HashMap::<String, String>::new()).unwrap();
+            let response = server.handle_datagram(&serializer.get_buffer(), src).unwrap().unwrap();
+            // 响应按请求的字节序编码,客户端总能读出错误信息
+            Deserializer::new(&response, byte_order).deserialize_next().unwrap().as_map().unwrap().clone()
+        };
+
+        let pong = ping(ByteOrder::Big, 0x0102_0304_0506_0708);
+        assert_eq!(pong["status"], STATUS_OK.into());
+        assert_eq!(pong["request_id"], 0x0102_0304_0506_0708_u64.to_string().into());
+
+        let mismatch = ping(ByteOrder::Little, 42);
+        assert_eq!(mismatch["status"], STATUS_BAD_REQUEST.into());
+        assert_eq!(mismatch["request_id"], "42".into());
+        assert_eq!(mismatch["message"], "Byte order mismatch: server uses big endian".into());
+    }
+
+    #[test]
+    fn airfare_in_cents_arrives_exactly() {
+        let server = server_with_flight(10);
+        server.controller.add_flight(controller::Flight {
+            flight_id: 2,
+            source: "Singapore".to_string(),
+            destination: "Tokyo".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-10-02 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 29999,
+            seats_available: 10,
+            capacity: 10,
+            overbook_limit: 0,
+        }).unwrap();
+
+        let details = request(&server, 1, 2, &[("flight_id", "2")]);
+        assert_eq!(details["airfare"], Value::Int64(29999));
+        assert_eq!(controller::flight_models::format_cents(details["airfare"].as_i64().unwrap()), "$299.99");
+    }
+
+    #[test]
+    fn sweep_store_drops_only_expired_entries() {
+        let server = server_with_flight(10);
+        request(&server, 1, 3, &[("flight_id", "1"), ("seats", "1")]);
+        request(&server, 2, 3, &[("flight_id", "1"), ("seats", "1")]);
+        let ttl = chrono::Duration::seconds(server.config.server.dedup_ttl as i64);
+        server.store_request.lock().unwrap().get_mut(&2).unwrap().timestamp -= ttl + chrono::Duration::seconds(1);
+
+        assert_eq!(server.sweep_store(), 1);
+        assert_eq!(server.sweep_store(), 0);
+        let store = server.store_request.lock().unwrap();
+        assert!(store.contains_key(&1) && !store.contains_key(&2));
+    }
+
+    #[test]
+    fn shutdown_stops_run_notifies_monitors_and_flush_keeps_the_store() {
+        let path = std::env::temp_dir().join(format!("dedup-store-shutdown-{}.bin", std::process::id()));
+        let config = || {
+            let mut config = Config::default();
+            config.server.store_file = Some(path.to_string_lossy().into_owned());
+            config
+        };
+        let server = server_with_config(10, config());
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let packet = request_packet(1, 4, &[("flight_id", "1"), ("monitor_interval", "60")]);
+        server.handle_datagram(&packet, client.local_addr().unwrap()).unwrap().unwrap();
+
+        let started = Instant::now();
+        thread::scope(|scope| {
+            let running = scope.spawn(|| server.run().unwrap());
+            server.shutdown_handle().store(true, Ordering::SeqCst);
+            running.join().unwrap();
+        });
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(receive_map(&client)["action"], "10".into());
+
+        // 退出前持久化的去重存储在重启时自动加载
+        server.flush().unwrap();
+        let restarted = server_with_config(10, config());
+        fs::remove_file(&path).unwrap();
+        assert!(restarted.store_request.lock().unwrap().contains_key(&1));
+    }
+
+    #[test]
+    fn request_loss_skips_the_request_and_response_loss_only_the_reply() {
+        let send_reserve = |request_loss_rate, loss_rate| {
+            let mut config = Config::default();
+            config.server.request_loss_rate = request_loss_rate;
+            config.server.loss_rate = loss_rate;
+            let server = server_with_config(10, config);
+            let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+            client.set_read_timeout(Some(Duration::from_millis(300))).unwrap();
+            client.send_to(&request_packet(1, 3, &[("flight_id", "1"), ("seats", "1")]), server.local_addr().unwrap()).unwrap();
+            server.run_once().unwrap();
+            let answered = client.recv(&mut [0u8; 1024]).is_ok();
+            let seats_available = server.controller.flights()[&1].seats_available;
+            (seats_available, answered)
+        };
+
+        // 请求丢失时请求不会被执行;响应丢失时请求已执行,只是客户端收不到应答
+        assert_eq!(send_reserve(1.0, 0.0), (10, false));
+        assert_eq!(send_reserve(0.0, 1.0), (9, false));
+        assert_eq!(send_reserve(0.0, 0.0), (9, true));
+    }
+
+    #[test]
+    fn same_rng_seed_gives_the_same_loss_rolls() {
+        let rolls = |seed| {
+            let mut config = Config::default();
+            config.server.rng_seed = Some(seed);
+            let server = server_with_config(10, config);
+            (0..20).map(|_| server.roll()).collect::<Vec<f32>>()
+        };
+        assert_eq!(rolls(7), rolls(7));
+        assert_ne!(rolls(7), rolls(8));
+    }
+
+    #[test]
+    fn malformed_requests_get_a_400_instead_of_crashing() {
+        let server = server_with_flight(10);
+        let bad_request = |request_id, action, fields: &[(&str, &str)], message: &str| {
+            let response = request(&server, request_id, action, fields);
+            assert_eq!(response["status"], STATUS_BAD_REQUEST.into(), "action {} {:?}", action, fields);
+            assert_eq!(response["message"], message.into(), "action {} {:?}", action, fields);
+        };
+
+        for (request_id, action) in [2, 3, 4, 6, 7].into_iter().enumerate() {
+            bad_request(request_id as u64 + 1, action, &[], "Missing field: flight_id");
+        }
+        bad_request(10, 1, &[("source", "Singapore")], "Missing field: destination");
+        bad_request(11, 3, &[("flight_id", "one"), ("seats", "1")], "Invalid field: flight_id");
+        bad_request(12, 99, &[], "Invalid action");
+
+        // 无法解码的数据报返回错误,服务器继续处理后续请求
+        let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        assert!(server.handle_datagram(&[0xff, 0x00, 0x01], src).is_err());
+        assert_eq!(request(&server, 13, 2, &[("flight_id", "1")])["status"], STATUS_OK.into());
+    }
+
+    #[test]
+    fn undecodable_or_non_map_payload_gets_a_400_with_the_request_id() {
+        let server = server_with_flight(10);
+        let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let header = |request_id| {
+            let mut serializer = Serializer::new(ByteOrder::Little);
+            Envelope { request_id, invocation_semantic: InvocationSemantic::AtMostOnce.into(), action: 3 }.serialize(&mut serializer).unwrap();
+            serializer
+        };
+
+        // 请求头后跟无法解码的字节
+        let mut garbage = header(20).get_buffer();
+        garbage.extend_from_slice(&[0xff, 0x00, 0x01]);
+        // 请求头后跟一个不是map的值
+        let mut not_a_map = header(21);
+        not_a_map.serialize_string("flight_id=1").unwrap();
+
+        for (request_id, packet) in [(20, garbage), (21, not_a_map.get_buffer())] {
+            let response = server.handle_datagram(&packet, src).unwrap().unwrap();
+            let response = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap().as_map().unwrap().clone();
+            assert_eq!(response["status"], STATUS_BAD_REQUEST.into());
+            assert_eq!(response["message"], "Invalid payload format".into());
+            assert_eq!(response["request_id"], request_id.to_string().into());
+        }
+
+        // 错误回复不会存入去重存储,用同一request_id重发正确的请求会正常执行
+        assert_eq!(request(&server, 20, 3, &[("flight_id", "1"), ("seats", "1")])["status"], STATUS_OK.into());
+        assert_eq!(server.controller.flights()[&1].seats_available, 9);
+    }
+
+    #[test]
+    fn server_on_a_bound_socket_answers_a_reservation() {
+        let mut config = Config::default();
+        config.server.address = "127.0.0.1:0".to_string();
+        let socket = bind_socket(&config.server).unwrap();
+        let controller = FlightController::new();
+        controller.add_flight(controller::Flight {
+            flight_id: 1,
+            source: "Singapore".to_string(),
+            destination: "Tokyo".to_string(),
+            departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+            airfare: 45000,
+            seats_available: 10,
+            capacity: 10,
+            overbook_limit: 0,
+        }).unwrap();
+        let server = Server::from_socket(socket, controller, config).unwrap();
+
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.send_to(&request_packet(1, 3, &[("flight_id", "1"), ("seats", "3")]), server.local_addr().unwrap()).unwrap();
+        server.run_once().unwrap();
+
+        let response = receive_map(&client);
+        assert_eq!(response["status"], STATUS_OK.into());
+        assert_eq!(response["overbooked"], Value::Int32(0));
+        assert_eq!(server.controller.flights()[&1].seats_available, 7);
+    }
+
+    #[test]
+    fn missing_flights_are_404_and_other_failures_400() {
+        let server = server_with_flight(10);
+        let status = |request_id, action, fields: &[(&str, &str)]| request(&server, request_id, action, fields)["status"].clone();
+
+        assert_eq!(status(1, 2, &[("flight_id", "9")]), STATUS_NOT_FOUND.into());
+        assert_eq!(status(2, 3, &[("flight_id", "9"), ("seats", "1")]), STATUS_NOT_FOUND.into());
+        assert_eq!(status(3, 6, &[("flight_id", "9"), ("seats", "1")]), STATUS_NOT_FOUND.into());
+        assert_eq!(status(4, 4, &[("flight_id", "9"), ("monitor_interval", "60")]), STATUS_NOT_FOUND.into());
+        assert_eq!(status(5, 1, &[("source", "Singapore"), ("destination", "Paris")]), STATUS_NOT_FOUND.into());
+
+        assert_eq!(status(6, 3, &[("flight_id", "1"), ("seats", "11")]), STATUS_BAD_REQUEST.into());
+        assert_eq!(status(7, 3, &[("flight_id", "1"), ("seats", "-1")]), STATUS_BAD_REQUEST.into());
+        assert_eq!(status(8, 6, &[("flight_id", "1"), ("seats", "1")]), STATUS_BAD_REQUEST.into());
+    }
+
+    #[test]
+    fn registered_actions_are_dispatched_but_ping_cannot_be_replaced() {
+        let mut server = server_with_flight(10);
+        server.register_action(42, |payload, controller, _, _, _| {
+            let mut data = HashMap::new();
+            data.insert("status".to_string(), STATUS_OK.into());
+            data.insert("echo".to_string(), payload["word"].clone());
+            data.insert("flights".to_string(), Value::Int32(controller.flights().len() as i32));
+            Ok(data)
+        });
+        server.register_action(1, |_, _, _, _, _| Err("queries are disabled".into()));
+        server.register_action(0, |_, _, _, _, _| Err("not a ping".into()));
+
+        let echo = request(&server, 1, 42, &[("word", "hello")]);
+        assert_eq!((echo["status"].clone(), echo["echo"].clone(), echo["flights"].clone()), (STATUS_OK.into(), "hello".into(), Value::Int32(1)));
+        let disabled = request(&server, 2, 1, &[("source", "Singapore"), ("destination", "Tokyo")]);
+        assert_eq!((disabled["status"].clone(), disabled["message"].clone()), (STATUS_BAD_REQUEST.into(), "queries are disabled".into()));
+        assert_eq!(request(&server, 3, 0, &[])["status"], STATUS_OK.into());
+    }
+
+    #[test]
+    fn responses_keep_the_types_of_their_values() {
+        let server = server_with_flight(10);
+        let departure = NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+
+        let details = request(&server, 1, 2, &[("flight_id", "1")]);
+        assert_eq!(details["departure_time"], Value::DateTime(departure));
+        assert_eq!(details["airfare"], Value::Int64(45000));
+        assert_eq!(details["seats_available"], Value::Int32(10));
+
+        let ids = request(&server, 2, 1, &[("source", "Singapore"), ("destination", "Tokyo")]);
+        assert_eq!(ids["flight_ids"], Value::Array(vec![Value::Int32(1)]));
+
+        let reservation = request(&server, 3, 3, &[("flight_id", "1"), ("seats", "2")]);
+        assert_eq!(reservation["overbooked"], Value::Int32(0));
+        // 状态码和request_id仍为字符串
+        assert_eq!(reservation["status"], Value::String(STATUS_OK.to_string()));
+        assert_eq!(reservation["request_id"], Value::String("3".to_string()));
+    }
+
+    #[test]
+    fn unknown_semantic_is_rejected_and_at_least_once_runs_every_time() {
+        let server = server_with_flight(10);
+        let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+        let reserve = |invocation_semantic| {
+            let payload: HashMap<String, String> = [("flight_id", "1"), ("seats", "1")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+            let mut serializer = Serializer::new(ByteOrder::Little);
+            Envelope { request_id: 5, invocation_semantic, action: 3 }.serialize(&mut serializer).unwrap();
+            serializer.serialize_map(&payload).unwrap();
+            let response = server.handle_datagram(&serializer.get_buffer(), src).unwrap().unwrap();
+            Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap().as_map().unwrap().clone()
+        };
+
+        let rejected = reserve(7);
+        assert_eq!(rejected["status"], STATUS_BAD_REQUEST.into());
+        assert_eq!(rejected["message"], "Unknown invocation semantic".into());
+        assert_eq!(rejected["request_id"], "5".into());
+        assert_eq!(server.controller.flights()[&1].seats_available, 10);
+
+        // at-least-once的重传每次都重新执行,不进入去重存储
+        reserve(InvocationSemantic::AtLeastOnce.into());
+        reserve(InvocationSemantic::AtLeastOnce.into());
+        assert_eq!(server.controller.flights()[&1].seats_available, 8);
+        assert!(server.store_request.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn idle_monitor_receives_heartbeats() {
+        let mut config = Config::default();
+        config.server.heartbeat_interval = 1;
+        let server = server_with_config(10, config);
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let packet = request_packet(1, 4, &[("flight_id", "1"), ("monitor_interval", "60")]);
+        server.handle_datagram(&packet, client.local_addr().unwrap()).unwrap().unwrap();
+
+        // 注册监控不会推送,航班也没有变化,收到的更新只能来自心跳
+        let updates: Vec<_> = thread::scope(|scope| {
+            scope.spawn(|| server.run().unwrap());
+            let updates = (0..2).map(|_| receive_map(&client)).collect();
+            server.shutdown_handle().store(true, Ordering::SeqCst);
+            updates
+        });
+        for update in updates {
+            assert_eq!(update["action"], "5".into());
+            assert_eq!(update["seats_available"], "10".into());
+        }
+    }
+
+    #[test]
+    fn server_bound_to_ipv6_loopback_answers_pings() {
+        let mut config = Config::default();
+        config.server.address = "[::1]:0".to_string();
+        let server = Server::bind(config).unwrap();
+        let server_addr = server.local_addr().unwrap();
+        assert!(server_addr.is_ipv6());
+
+        let client = UdpSocket::bind("[::1]:0").unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        client.send_to(&request_packet(1, 0, &[]), server_addr).unwrap();
+        server.run_once().unwrap();
+        let pong = receive_map(&client);
+        assert_eq!((pong["status"].clone(), pong["request_id"].clone()), (STATUS_OK.into(), "1".into()));
+    }
+
+    #[test]
+    fn running_server_sweeps_the_store_in_the_background() {
+        let server = server_with_flight(10);
+        request(&server, 1, 3, &[("flight_id", "1"), ("seats", "1")]);
+        let ttl = chrono::Duration::seconds(server.config.server.dedup_ttl as i64);
+        server.store_request.lock().unwrap().get_mut(&1).unwrap().timestamp -= ttl + chrono::Duration::seconds(1);
+
+        // 处理请求时不再清理,清理由后台的housekeeping完成
+        request(&server, 2, 0, &[]);
+        assert!(server.store_request.lock().unwrap().contains_key(&1));
+        thread::scope(|scope| {
+            scope.spawn(|| server.run().unwrap());
+            thread::sleep(MONITOR_SWEEP_INTERVAL + Duration::from_millis(500));
+            server.shutdown_handle().store(true, Ordering::SeqCst);
+        });
+        assert!(server.store_request.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn max_fare_filters_and_a_bad_max_fare_is_rejected_by_name() {
+        let server = server_with_flight(10);
+        let query = |request_id, fields: &[(&str, &str)]| request(&server, request_id, 1, fields);
+        let route = [("source", "Singapore"), ("destination", "Tokyo")];
+
+        assert_eq!(query(1, &[route[0], route[1], ("max_fare", "45000")])["status"], STATUS_OK.into());
+        assert_eq!(query(2, &[route[0], route[1], ("max_fare", "44999")])["status"], STATUS_NOT_FOUND.into());
+        let bad = query(3, &[route[0], route[1], ("max_fare", "cheap")]);
+        assert_eq!((bad["status"].clone(), bad["message"].clone()), (STATUS_BAD_REQUEST.into(), "Invalid field: max_fare".into()));
+
+        // 不是字符串的max_fare同样被拒绝,而不是被忽略后返回未过滤的结果
+        let mut payload: HashMap<String, Value> = route.iter().map(|(k, v)| (k.to_string(), Value::from(*v))).collect();
+        payload.insert("max_fare".to_string(), Value::Int64(1));
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        Envelope { request_id: 4, invocation_semantic: InvocationSemantic::AtMostOnce.into(), action: 1 }.serialize(&mut serializer).unwrap();
+        serializer.serialize_value_map(&payload).unwrap();
+        let response = server.handle_datagram(&serializer.get_buffer(), "127.0.0.1:40000".parse().unwrap()).unwrap().unwrap();
+        let response = Deserializer::new(&response, ByteOrder::Little).deserialize_next().unwrap();
+        assert_eq!(response.as_map().unwrap()["message"], "Invalid field: max_fare".into());
+    }
+}
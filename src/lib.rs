@@ -0,0 +1,16 @@
+//! Shared modules of the flight reservation system.
+//!
+//! The binaries include these files directly with `#[path]`; the library target exists so the
+//! modules' unit tests and doc examples are compiled and run by `cargo test`.
+
+pub mod client_args;
+pub mod config;
+#[path = "controller.rs"]
+pub mod controller;
+pub mod flight_client;
+pub mod flight_server;
+pub mod log;
+pub mod rate_limiter;
+pub mod response_cache;
+pub mod seed;
+pub mod serialization;
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// How often idle buckets are evicted
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Token bucket for a single client
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+/// Per-client token-bucket rate limiter keyed by source address
+pub struct RateLimiter {
+    /// Tokens added per second
+    rate: f64,
+    /// Maximum number of tokens a bucket can hold
+    burst: f64,
+    buckets: HashMap<SocketAddr, Bucket>,
+    last_eviction: Instant,
+}
+
+impl RateLimiter {
+    /// Creates a limiter allowing `rate` requests per second per client, with bursts of up to `burst` requests
+    pub fn new(rate: f64, burst: u32) -> Self {
+        RateLimiter {
+            rate,
+            burst: burst as f64,
+            buckets: HashMap::new(),
+            last_eviction: Instant::now(),
+        }
+    }
+
+    /// Takes a token from the client's bucket, returning false if the client is over its limit
+    pub fn allow(&mut self, addr: SocketAddr) -> bool {
+        let now = Instant::now();
+        self.evict_idle(now);
+
+        let burst = self.burst;
+        let bucket = self.buckets.entry(addr).or_insert(Bucket { tokens: burst, last_refill: now });
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Drops buckets that have refilled completely, since a fresh bucket would behave the same
+    fn evict_idle(&mut self, now: Instant) {
+        if now.duration_since(self.last_eviction) < EVICTION_INTERVAL {
+            return;
+        }
+        let (rate, burst) = (self.rate, self.burst);
+        self.buckets.retain(|_, bucket| bucket.tokens + now.duration_since(bucket.last_refill).as_secs_f64() * rate < burst);
+        self.last_eviction = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    #[test]
+    fn allows_a_burst_then_limits_each_client_separately() {
+        let mut limiter = RateLimiter::new(1.0, 3);
+        assert!((0..3).all(|_| limiter.allow(client(1))));
+        assert!(!limiter.allow(client(1)));
+        // Another client has its own bucket
+        assert!(limiter.allow(client(2)));
+    }
+
+    #[test]
+    fn bucket_refills_at_the_configured_rate() {
+        let mut limiter = RateLimiter::new(50.0, 1);
+        assert!(limiter.allow(client(1)));
+        assert!(!limiter.allow(client(1)));
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(limiter.allow(client(1)));
+    }
+}
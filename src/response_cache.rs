@@ -0,0 +1,121 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use crate::serialization::Value;
+
+/// Cache key built from a request's action and payload map, sorted so that field order doesn't matter.
+///
+/// The request_id and invocation semantic travel in the envelope, so identical queries share a key.
+type CacheKey = (u8, Vec<(String, String)>);
+
+/// Client-side LRU cache of responses to idempotent queries
+pub struct ResponseCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: HashMap<CacheKey, (Instant, HashMap<String, Value>)>,
+    /// Keys ordered from least to most recently used
+    order: VecDeque<CacheKey>,
+}
+
+impl ResponseCache {
+    /// Creates a cache holding at most `capacity` responses, each valid for `ttl`.
+    /// A capacity of 0 disables caching.
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        ResponseCache {
+            capacity,
+            ttl,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Builds the cache key for a request
+    fn key(action: u8, request: &HashMap<String, String>) -> CacheKey {
+        let mut fields: Vec<(String, String)> = request
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        fields.sort();
+        (action, fields)
+    }
+
+    /// Moves a key to the most recently used position
+    fn touch(&mut self, key: &CacheKey) {
+        self.order.retain(|k| k != key);
+        self.order.push_back(key.clone());
+    }
+
+    /// Returns the cached response for a request if present and not expired
+    pub fn get(&mut self, action: u8, request: &HashMap<String, String>) -> Option<HashMap<String, Value>> {
+        let key = Self::key(action, request);
+        let (inserted_at, response) = self.entries.get(&key)?;
+        if inserted_at.elapsed() > self.ttl {
+            self.entries.remove(&key);
+            self.order.retain(|k| k != &key);
+            return None;
+        }
+        let response = response.clone();
+        self.touch(&key);
+        Some(response)
+    }
+
+    /// Stores the response for a request, evicting the least recently used entry when full
+    pub fn insert(&mut self, action: u8, request: &HashMap<String, String>, response: HashMap<String, Value>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = Self::key(action, request);
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key.clone(), (Instant::now(), response));
+        self.touch(&key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(flight_id: &str) -> HashMap<String, String> {
+        HashMap::from([("flight_id".to_string(), flight_id.to_string())])
+    }
+
+    fn response(seats: i32) -> HashMap<String, Value> {
+        HashMap::from([("seats_available".to_string(), Value::Int32(seats))])
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted_when_full() {
+        let mut cache = ResponseCache::new(2, Duration::from_secs(60));
+        cache.insert(2, &request("1"), response(1));
+        cache.insert(2, &request("2"), response(2));
+        // Reading flight 1 makes flight 2 the least recently used
+        assert_eq!(cache.get(2, &request("1")), Some(response(1)));
+        cache.insert(2, &request("3"), response(3));
+
+        assert_eq!(cache.get(2, &request("2")), None);
+        assert_eq!(cache.get(2, &request("1")), Some(response(1)));
+        assert_eq!(cache.get(2, &request("3")), Some(response(3)));
+        // The action is part of the key
+        assert_eq!(cache.get(13, &request("1")), None);
+    }
+
+    #[test]
+    fn entries_expire_after_the_ttl() {
+        let mut cache = ResponseCache::new(2, Duration::from_millis(50));
+        cache.insert(2, &request("1"), response(1));
+        assert!(cache.get(2, &request("1")).is_some());
+        std::thread::sleep(Duration::from_millis(80));
+        assert_eq!(cache.get(2, &request("1")), None);
+    }
+
+    #[test]
+    fn zero_capacity_disables_caching() {
+        let mut cache = ResponseCache::new(0, Duration::from_secs(60));
+        cache.insert(2, &request("1"), response(1));
+        assert_eq!(cache.get(2, &request("1")), None);
+    }
+}
@@ -0,0 +1,185 @@
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+
+use crate::controller::Flight;
+
+/// Format of `departure_time` in a seed file, e.g. `2024-08-30 10:00:00`
+const DEPARTURE_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Top level of a seed file: a list of `[[flights]]` tables
+#[derive(Deserialize)]
+struct SeedFile {
+    #[serde(default)]
+    flights: Vec<SeedFlight>,
+}
+
+/// One `[[flights]]` table as written in the seed file
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedFlight {
+    flight_id: i32,
+    source: String,
+    destination: String,
+    departure_time: String,
+    airfare: i64,
+    capacity: i32,
+    /// Defaults to `capacity`, i.e. nothing sold yet
+    seats_available: Option<i32>,
+    #[serde(default)]
+    overbook_limit: i32,
+}
+
+/// Error loading a seed file, naming the file and, for a bad row, which `[[flights]]` entry it was
+#[derive(Debug)]
+pub struct SeedError {
+    pub path: PathBuf,
+    pub row: Option<usize>,    // 1-based index of the offending [[flights]] entry, if the file parsed
+    pub message: String,
+}
+
+impl fmt::Display for SeedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.row {
+            Some(row) => write!(f, "invalid seed file {}: flight #{}: {}", self.path.display(), row, self.message),
+            None => write!(f, "invalid seed file {}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for SeedError {}
+
+impl SeedFlight {
+    /// Checks the row and converts it into a `Flight`
+    fn into_flight(self) -> Result<Flight, String> {
+        let departure_time = NaiveDateTime::parse_from_str(&self.departure_time, DEPARTURE_TIME_FORMAT)
+            .map_err(|e| format!("invalid departure_time {:?} (expected YYYY-MM-DD HH:MM:SS): {}", self.departure_time, e))?;
+        if self.airfare < 0 {
+            return Err(format!("airfare must not be negative, got {}", self.airfare));
+        }
+        if self.capacity < 0 {
+            return Err(format!("capacity must not be negative, got {}", self.capacity));
+        }
+        if self.overbook_limit < 0 {
+            return Err(format!("overbook_limit must not be negative, got {}", self.overbook_limit));
+        }
+        let seats_available = self.seats_available.unwrap_or(self.capacity);
+        if !(-self.overbook_limit..=self.capacity).contains(&seats_available) {
+            return Err(format!(
+                "seats_available must be within {}..={}, got {}",
+                -self.overbook_limit, self.capacity, seats_available
+            ));
+        }
+        Ok(Flight {
+            flight_id: self.flight_id,
+            source: self.source,
+            destination: self.destination,
+            departure_time,
+            airfare: self.airfare,
+            seats_available,
+            capacity: self.capacity,
+            overbook_limit: self.overbook_limit,
+        })
+    }
+}
+
+/// Loads the initial flights from a TOML seed file
+///
+/// Each flight is a `[[flights]]` table with `flight_id`, `source`, `destination`, `departure_time`
+/// (`YYYY-MM-DD HH:MM:SS`), `airfare` in cents and `capacity`, plus optional `seats_available`
+/// (defaults to `capacity`) and `overbook_limit` (defaults to 0). The first malformed row fails the
+/// whole load so a typo can't silently drop a flight.
+pub fn load_seed_file<P: AsRef<Path>>(path: P) -> Result<Vec<Flight>, SeedError> {
+    let path = path.as_ref();
+    let error = |row, message| SeedError { path: path.to_path_buf(), row, message };
+
+    let text = fs::read_to_string(path).map_err(|e| error(None, e.to_string()))?;
+    let seed: SeedFile = toml::from_str(&text).map_err(|e| error(None, e.to_string()))?;
+    seed.flights
+        .into_iter()
+        .enumerate()
+        .map(|(index, flight)| flight.into_flight().map_err(|message| error(Some(index + 1), message)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes `text` to a seed file named after `name` and loads it
+    fn load_text(name: &str, text: &str) -> Result<Vec<Flight>, SeedError> {
+        let path = std::env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+        fs::write(&path, text).unwrap();
+        let flights = load_seed_file(&path);
+        fs::remove_file(&path).unwrap();
+        flights
+    }
+
+    #[test]
+    fn seed_file_flights_are_loaded_with_defaults() {
+        let flights = load_text(
+            "seed",
+            r#"
+[[flights]]
+flight_id = 1
+source = "Singapore"
+destination = "Tokyo"
+departure_time = "2024-10-01 08:00:00"
+airfare = 45000
+capacity = 180
+
+[[flights]]
+flight_id = 2
+source = "Tokyo"
+destination = "Singapore"
+departure_time = "2024-10-05 21:30:00"
+airfare = 42000
+capacity = 180
+seats_available = -2
+overbook_limit = 5
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(flights.len(), 2);
+        assert_eq!((flights[0].flight_id, flights[0].seats_available, flights[0].overbook_limit), (1, 180, 0));
+        assert_eq!(flights[0].departure_time.to_string(), "2024-10-01 08:00:00");
+        assert_eq!((flights[1].flight_id, flights[1].seats_available, flights[1].overbook_limit), (2, -2, 5));
+    }
+
+    #[test]
+    fn bad_row_is_reported_with_its_position() {
+        let error = load_text(
+            "bad-seed",
+            r#"
+[[flights]]
+flight_id = 1
+source = "Singapore"
+destination = "Tokyo"
+departure_time = "2024-10-01 08:00:00"
+airfare = 45000
+capacity = 180
+
+[[flights]]
+flight_id = 2
+source = "Tokyo"
+destination = "Singapore"
+departure_time = "2024-10-05"
+airfare = 42000
+capacity = 180
+"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.row, Some(2));
+        assert!(error.to_string().contains("flight #2: invalid departure_time \"2024-10-05\""), "{}", error);
+    }
+
+    #[test]
+    fn unknown_fields_and_missing_files_are_errors() {
+        let error = load_text("typo-seed", "[[flights]]\nflight_id = 1\nsource = \"A\"\ndestination = \"B\"\ndeparture_time = \"2024-10-01 08:00:00\"\nairfare = 1\ncapacity = 1\ncapacitty = 2\n").unwrap_err();
+        assert_eq!(error.row, None);
+        assert!(load_seed_file(std::env::temp_dir().join("missing-seed.toml")).is_err());
+    }
+}
@@ -1,18 +1,72 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::io::{Cursor, Read, Write};
+use std::str::FromStr;
 use byteorder::{BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt};
+use chrono::{DateTime, NaiveDateTime};
 
 /// Represents the byte order for serialization and deserialization.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(dead_code)]
 pub enum ByteOrder {
     Big,
     Little,
 }
 
+impl FromStr for ByteOrder {
+    type Err = String;
+
+    /// Parses a byte order from its config name, `"little"` or `"big"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "little" => Ok(ByteOrder::Little),
+            "big" => Ok(ByteOrder::Big),
+            _ => Err(format!("Invalid byte order '{}', expected \"little\" or \"big\"", s)),
+        }
+    }
+}
+
+impl ByteOrder {
+    /// Returns the other byte order, used to recognise packets from a peer configured differently.
+    pub fn opposite(self) -> ByteOrder {
+        match self {
+            ByteOrder::Big => ByteOrder::Little,
+            ByteOrder::Little => ByteOrder::Big,
+        }
+    }
+
+    /// Returns the flag byte that marks this byte order in an [`Envelope`], `b'B'` or `b'L'`.
+    fn flag(self) -> u8 {
+        match self {
+            ByteOrder::Big => b'B',
+            ByteOrder::Little => b'L',
+        }
+    }
+
+    /// Returns the byte order marked by an [`Envelope`] flag byte.
+    fn from_flag(flag: u8) -> Option<ByteOrder> {
+        match flag {
+            b'B' => Some(ByteOrder::Big),
+            b'L' => Some(ByteOrder::Little),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ByteOrder {
+    /// Writes the config name accepted by `FromStr`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ByteOrder::Big => write!(f, "big"),
+            ByteOrder::Little => write!(f, "little"),
+        }
+    }
+}
+
 /// Represents the supported data types for serialization and deserialization.
-#[derive(Debug, Clone, Copy)]
+///
+/// Every serialized value is prefixed with its type tag, see [`DataType::tag`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DataType {
     Int32,
     Bool,
@@ -20,11 +74,18 @@ pub enum DataType {
     Float,
     Array,
     Map,
+    Int64,
+    Double,
+    Null,
+    Bytes,
+    VarInt32,
+    DateTime,
+    Enum,
 }
 
 impl DataType {
-    /// Converts the DataType to its corresponding u8 representation.
-    fn to_u8(self) -> u8 {
+    /// Returns the u8 type tag written before values of this type.
+    pub fn tag(self) -> u8 {
         match self {
             DataType::Int32 => 1,
             DataType::Bool => 2,
@@ -32,11 +93,18 @@ impl DataType {
             DataType::Float => 4,
             DataType::Array => 5,
             DataType::Map => 6,
+            DataType::Int64 => 7,
+            DataType::Double => 8,
+            DataType::Null => 9,
+            DataType::Bytes => 10,
+            DataType::VarInt32 => 11,
+            DataType::DateTime => 12,
+            DataType::Enum => 13,
         }
     }
 
-    /// Converts a u8 value to its corresponding DataType, if valid.
-    fn from_u8(value: u8) -> Option<Self> {
+    /// Converts a u8 type tag to its corresponding DataType, if valid.
+    pub fn from_tag(value: u8) -> Option<Self> {
         match value {
             1 => Some(DataType::Int32),
             2 => Some(DataType::Bool),
@@ -44,33 +112,101 @@ impl DataType {
             4 => Some(DataType::Float),
             5 => Some(DataType::Array),
             6 => Some(DataType::Map),
+            7 => Some(DataType::Int64),
+            8 => Some(DataType::Double),
+            9 => Some(DataType::Null),
+            10 => Some(DataType::Bytes),
+            11 => Some(DataType::VarInt32),
+            12 => Some(DataType::DateTime),
+            13 => Some(DataType::Enum),
             _ => None,
         }
     }
 }
 
+/// Magic bytes at the start of a versioned buffer.
+pub const MAGIC: [u8; 2] = *b"FS";
+
 /// Handles the serialization of data into a byte buffer.
 pub struct Serializer {
     buffer: Vec<u8>,
     byte_order: ByteOrder,
+    /// Whether i32 values and length prefixes are written as LEB128 varints
+    varint: bool,
 }
 
 impl Serializer {
     /// Creates a new Serializer with the specified byte order.
     pub fn new(byte_order: ByteOrder) -> Self {
+        Serializer::with_buffer(Vec::new(), byte_order)
+    }
+
+    /// Creates a new Serializer with a buffer pre-sized to hold `capacity` bytes.
+    #[allow(dead_code)]
+    pub fn with_capacity(byte_order: ByteOrder, capacity: usize) -> Self {
+        Serializer::with_buffer(Vec::with_capacity(capacity), byte_order)
+    }
+
+    /// Creates a new Serializer whose buffer starts with the protocol magic and a version byte.
+    #[allow(dead_code)]
+    pub fn new_versioned(byte_order: ByteOrder, version: u8) -> Self {
+        let mut buffer = Vec::with_capacity(MAGIC.len() + 1);
+        buffer.extend_from_slice(&MAGIC);
+        buffer.push(version);
+        Serializer::with_buffer(buffer, byte_order)
+    }
+
+    /// Creates a new Serializer that appends to a caller-provided buffer.
+    ///
+    /// The buffer keeps its capacity, so a pre-sized buffer can be reused across messages.
+    #[allow(dead_code)]
+    pub fn with_buffer(buffer: Vec<u8>, byte_order: ByteOrder) -> Self {
         Serializer {
-            buffer: Vec::new(),
+            buffer,
             byte_order,
+            varint: false,
         }
     }
 
+    /// Creates a new Serializer that writes i32 values and length prefixes as
+    /// zigzag LEB128 varints, which takes 1 byte instead of 4 for small values.
+    ///
+    /// Varints carry their own type tag, so any Deserializer decodes them without configuration.
+    #[allow(dead_code)]
+    pub fn new_varint(byte_order: ByteOrder) -> Self {
+        let mut serializer = Serializer::new(byte_order);
+        serializer.varint = true;
+        serializer
+    }
+
     /// Writes the data type to the buffer.
     fn write_type(&mut self, data_type: DataType) -> std::io::Result<()> {
-        self.buffer.write_u8(data_type.to_u8())
+        self.buffer.write_u8(data_type.tag())
+    }
+
+    /// Writes a type tag followed by a length prefix.
+    ///
+    /// Lengths are encoded as i32, so a length above `i32::MAX` is rejected with `InvalidInput`
+    /// before anything is written instead of wrapping to a negative, unparseable prefix.
+    fn write_header(&mut self, data_type: DataType, len: usize) -> std::io::Result<()> {
+        let len = i32::try_from(len).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{:?} length {} exceeds i32::MAX", data_type, len))
+        })?;
+        self.write_type(data_type)?;
+        self.serialize_int32(len)
     }
 
     /// Serializes an i32 value.
     pub fn serialize_int32(&mut self, value: i32) -> std::io::Result<()> {
+        if self.varint {
+            self.write_type(DataType::VarInt32)?;
+            let mut zigzag = ((value << 1) ^ (value >> 31)) as u32;
+            while zigzag >= 0x80 {
+                self.buffer.write_u8((zigzag as u8) | 0x80)?;
+                zigzag >>= 7;
+            }
+            return self.buffer.write_u8(zigzag as u8);
+        }
         self.write_type(DataType::Int32)?;
         match self.byte_order {
             ByteOrder::Big => self.buffer.write_i32::<BigEndian>(value),
@@ -78,6 +214,40 @@ impl Serializer {
         }
     }
 
+    /// Serializes an i64 value.
+    pub fn serialize_int64(&mut self, value: i64) -> std::io::Result<()> {
+        self.write_type(DataType::Int64)?;
+        match self.byte_order {
+            ByteOrder::Big => self.buffer.write_i64::<BigEndian>(value),
+            ByteOrder::Little => self.buffer.write_i64::<LittleEndian>(value),
+        }
+    }
+
+    /// Serializes a NaiveDateTime as milliseconds since the Unix epoch.
+    pub fn serialize_datetime(&mut self, value: &NaiveDateTime) -> std::io::Result<()> {
+        self.write_type(DataType::DateTime)?;
+        let millis = value.and_utc().timestamp_millis();
+        match self.byte_order {
+            ByteOrder::Big => self.buffer.write_i64::<BigEndian>(millis),
+            ByteOrder::Little => self.buffer.write_i64::<LittleEndian>(millis),
+        }
+    }
+
+    /// Serializes an enum as its one-byte discriminant.
+    pub fn serialize_enum(&mut self, discriminant: u8) -> std::io::Result<()> {
+        self.write_type(DataType::Enum)?;
+        self.buffer.write_u8(discriminant)
+    }
+
+    /// Serializes an enum by label, failing with `InvalidInput` if `registry` doesn't know the label.
+    #[allow(dead_code)]
+    pub fn serialize_enum_label(&mut self, registry: &EnumRegistry, label: &str) -> std::io::Result<()> {
+        let discriminant = registry.discriminant(label).ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("Unknown {} '{}'", registry.name, label))
+        })?;
+        self.serialize_enum(discriminant)
+    }
+
     /// Serializes a boolean value.
     pub fn serialize_bool(&mut self, value: bool) -> std::io::Result<()> {
         self.write_type(DataType::Bool)?;
@@ -86,11 +256,16 @@ impl Serializer {
 
     /// Serializes a string value.
     pub fn serialize_string(&mut self, value: &str) -> std::io::Result<()> {
-        self.write_type(DataType::String)?;
-        self.serialize_int32(value.len() as i32)?;
+        self.write_header(DataType::String, value.len())?;
         self.buffer.write_all(value.as_bytes())
     }
 
+    /// Serializes raw bytes, length-prefixed like a string.
+    pub fn serialize_bytes(&mut self, value: &[u8]) -> std::io::Result<()> {
+        self.write_header(DataType::Bytes, value.len())?;
+        self.buffer.write_all(value)
+    }
+
     /// Serializes a f32 value.
     pub fn serialize_float(&mut self, value: f32) -> std::io::Result<()> {
         self.write_type(DataType::Float)?;
@@ -100,25 +275,209 @@ impl Serializer {
         }
     }
 
+    /// Serializes a f64 value.
+    pub fn serialize_double(&mut self, value: f64) -> std::io::Result<()> {
+        self.write_type(DataType::Double)?;
+        match self.byte_order {
+            ByteOrder::Big => self.buffer.write_f64::<BigEndian>(value),
+            ByteOrder::Little => self.buffer.write_f64::<LittleEndian>(value),
+        }
+    }
+
+    /// Serializes an absent value. Only the type tag is written.
+    pub fn serialize_null(&mut self) -> std::io::Result<()> {
+        self.write_type(DataType::Null)
+    }
+
     /// Serializes an array of serializable items.
     pub fn serialize_array<T: Serialize>(&mut self, array: &[T]) -> std::io::Result<()> {
-        self.write_type(DataType::Array)?;
-        self.serialize_int32(array.len() as i32)?;
-        for item in array {
+        self.serialize_iter(array.iter())
+    }
+
+    /// Serializes the items of an iterator as an array, without collecting them first.
+    ///
+    /// The length prefix is taken from `len()`, so it is written before any item is produced.
+    pub fn serialize_iter<T: Serialize, I: ExactSizeIterator<Item = T>>(&mut self, iter: I) -> std::io::Result<()> {
+        self.write_header(DataType::Array, iter.len())?;
+        for item in iter {
             item.serialize(self)?;
         }
         Ok(())
-    }    
+    }
 
     /// Serializes a map of serializable keys and values.
     pub fn serialize_map<K: Serialize, V: Serialize>(&mut self, map: &HashMap<K, V>) -> std::io::Result<()> {
         map.serialize(self)
     }
 
-    /// Returns the serialized buffer.
+    /// Serializes a map of already decoded Values, writing each value with its own type tag.
+    ///
+    /// Equivalent to `serialize_map::<String, Value>`; used for responses whose fields have mixed types.
+    pub fn serialize_value_map(&mut self, map: &HashMap<String, Value>) -> std::io::Result<()> {
+        self.serialize_map(map)
+    }
+
+    /// Serializes a single decoded Value with its own type tag.
+    ///
+    /// Same as `value.serialize(self)`, without needing the `Serialize` trait in scope.
+    #[allow(dead_code)]
+    pub fn serialize_value(&mut self, value: &Value) -> std::io::Result<()> {
+        value.serialize(self)
+    }
+
+    /// Clears the buffer so the Serializer can be reused, keeping its capacity, byte order and encoding mode.
+    ///
+    /// Any header written by `new_versioned` is cleared as well.
+    #[allow(dead_code)]
+    pub fn reset(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Returns the byte order numbers are written in.
+    #[allow(dead_code)]
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// Returns the bytes serialized so far without consuming the Serializer.
+    #[allow(dead_code)]
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Returns ownership of the serialized buffer, including any buffer passed to `with_buffer`.
     pub fn get_buffer(self) -> Vec<u8> {
         self.buffer
     }
+
+    /// Returns the serialized buffer followed by a CRC32 of its contents.
+    ///
+    /// The checksum is written in the serializer's byte order; decode with `Deserializer::new_checked`.
+    #[allow(dead_code)]
+    pub fn finish_with_checksum(mut self) -> Vec<u8> {
+        let checksum = crc32(&self.buffer);
+        match self.byte_order {
+            ByteOrder::Big => self.buffer.extend_from_slice(&checksum.to_be_bytes()),
+            ByteOrder::Little => self.buffer.extend_from_slice(&checksum.to_le_bytes()),
+        }
+        self.buffer
+    }
+}
+
+/// Computes the CRC32 (IEEE) checksum of the data.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Errors produced while deserializing a buffer.
+#[derive(Debug)]
+pub enum SerializationError {
+    /// The type tag doesn't correspond to any DataType.
+    UnknownType(u8),
+    /// A value of a different type was found where another was required.
+    UnexpectedType { expected: DataType, found: DataType },
+    /// The buffer ended in the middle of a value.
+    UnexpectedEof,
+    /// A string's bytes are not valid UTF-8.
+    ///
+    /// `position` is the buffer offset where the string's bytes start and `valid_up_to` the number
+    /// of bytes from there that are valid, so the first invalid byte is at `position + valid_up_to`.
+    InvalidUtf8 { position: usize, valid_up_to: usize },
+    /// A length prefix is negative or larger than the bytes left in the buffer.
+    LengthTooLarge { declared: i64, available: usize },
+    /// Arrays or maps are nested deeper than the deserializer allows.
+    DepthExceeded(usize),
+    /// A varint is longer than the maximum encoded length of an i32.
+    VarintTooLong,
+    /// An enum discriminant that the [`EnumRegistry`] used to decode it doesn't define.
+    UnknownEnum { name: &'static str, discriminant: u8 },
+    /// A DateTime's epoch millis are outside the representable range.
+    TimestampOutOfRange(i64),
+    /// A versioned buffer doesn't start with the protocol magic.
+    InvalidMagic,
+    /// The trailing CRC32 doesn't match the buffer's contents.
+    ChecksumMismatch { expected: u32, actual: u32 },
+    /// An envelope was written in a different byte order than it is being read in.
+    ByteOrderMismatch { expected: ByteOrder, found: ByteOrder },
+    /// An envelope's byte order flag is neither `b'B'` nor `b'L'`.
+    UnknownByteOrder(u8),
+    /// A JSON value has no equivalent in the wire format.
+    #[cfg(feature = "json")]
+    InvalidJson(String),
+    /// Any other I/O error from the underlying reader.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SerializationError::UnknownType(tag) => write!(f, "Unknown data type tag {}", tag),
+            SerializationError::UnexpectedType { expected, found } => write!(f, "Expected {:?}, found {:?}", expected, found),
+            SerializationError::UnexpectedEof => write!(f, "Unexpected end of buffer"),
+            SerializationError::InvalidUtf8 { position, valid_up_to } => write!(
+                f,
+                "Invalid UTF-8 in string starting at byte {}: first invalid byte at {} (valid up to {})",
+                position,
+                position + valid_up_to,
+                valid_up_to
+            ),
+            SerializationError::LengthTooLarge { declared, available } => {
+                write!(f, "Declared length {} exceeds remaining {} bytes", declared, available)
+            }
+            SerializationError::DepthExceeded(max_depth) => write!(f, "Nesting exceeds maximum depth of {}", max_depth),
+            SerializationError::VarintTooLong => write!(f, "Varint is too long"),
+            SerializationError::UnknownEnum { name, discriminant } => write!(f, "Unknown {} discriminant {}", name, discriminant),
+            SerializationError::TimestampOutOfRange(millis) => write!(f, "Timestamp {} is out of range", millis),
+            SerializationError::InvalidMagic => write!(f, "Invalid protocol magic"),
+            SerializationError::ChecksumMismatch { expected, actual } => {
+                write!(f, "Checksum mismatch: expected {:08x}, got {:08x}", expected, actual)
+            }
+            SerializationError::ByteOrderMismatch { expected, found } => {
+                write!(f, "Byte order mismatch: expected {} endian, found {} endian", expected, found)
+            }
+            SerializationError::UnknownByteOrder(flag) => write!(f, "Unknown byte order flag {:#04x}", flag),
+            #[cfg(feature = "json")]
+            SerializationError::InvalidJson(message) => write!(f, "Invalid JSON value: {}", message),
+            SerializationError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SerializationError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SerializationError {
+    fn from(e: std::io::Error) -> Self {
+        if e.kind() == std::io::ErrorKind::UnexpectedEof {
+            SerializationError::UnexpectedEof
+        } else {
+            SerializationError::Io(e)
+        }
+    }
+}
+
+impl From<SerializationError> for std::io::Error {
+    fn from(e: SerializationError) -> Self {
+        match e {
+            SerializationError::Io(e) => e,
+            SerializationError::UnexpectedEof => std::io::Error::new(std::io::ErrorKind::UnexpectedEof, e),
+            e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+        }
+    }
 }
 
 /// Trait for types that can be serialized.
@@ -133,12 +492,30 @@ impl Serialize for i32 {
     }
 }
 
+impl Serialize for i64 {
+    fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        serializer.serialize_int64(*self)
+    }
+}
+
+impl Serialize for NaiveDateTime {
+    fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        serializer.serialize_datetime(self)
+    }
+}
+
 impl Serialize for f32 {
     fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
         serializer.serialize_float(*self)
     }
 }
 
+impl Serialize for f64 {
+    fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        serializer.serialize_double(*self)
+    }
+}
+
 impl Serialize for String {
     fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
         serializer.serialize_string(self)
@@ -157,14 +534,28 @@ impl Serialize for bool {
     }
 }
 
+impl<T: Serialize> Serialize for &T {
+    fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        (**self).serialize(serializer)
+    }
+}
+
+impl<T: Serialize> Serialize for Option<T> {
+    fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        match self {
+            Some(value) => value.serialize(serializer),
+            None => serializer.serialize_null(),
+        }
+    }
+}
+
 impl<K, V> Serialize for HashMap<K, V>
 where
     K: Serialize,
     V: Serialize,
 {
     fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
-        serializer.write_type(DataType::Map)?;
-        serializer.serialize_int32(self.len() as i32)?;
+        serializer.write_header(DataType::Map, self.len())?;
         for (key, value) in self {
             key.serialize(serializer)?;
             value.serialize(serializer)?;
@@ -173,87 +564,346 @@ where
     }
 }
 
+/// Trait for types that can be deserialized, the counterpart of [`Serialize`].
+///
+/// Each implementation reads the type tag and fails with `UnexpectedType` if it doesn't match.
+pub trait Deserialize: Sized {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, SerializationError>;
+}
+
+impl Deserialize for i32 {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, SerializationError> {
+        match deserializer.read_type()? {
+            DataType::Int32 => deserializer.deserialize_int32(),
+            DataType::VarInt32 => deserializer.deserialize_varint32(),
+            found => Err(SerializationError::UnexpectedType { expected: DataType::Int32, found }),
+        }
+    }
+}
+
+impl Deserialize for f32 {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, SerializationError> {
+        deserializer.expect_type(DataType::Float)?;
+        deserializer.deserialize_float()
+    }
+}
+
+impl Deserialize for bool {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, SerializationError> {
+        deserializer.expect_type(DataType::Bool)?;
+        deserializer.deserialize_bool()
+    }
+}
+
+impl Deserialize for String {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, SerializationError> {
+        deserializer.expect_type(DataType::String)?;
+        deserializer.deserialize_string()
+    }
+}
+
+impl<T: Deserialize> Deserialize for Vec<T> {
+    fn deserialize(deserializer: &mut Deserializer) -> Result<Self, SerializationError> {
+        deserializer.expect_type(DataType::Array)?;
+        deserializer.enter_nested()?;
+        let array = deserializer.deserialize_array(T::deserialize);
+        deserializer.depth -= 1;
+        array
+    }
+}
+
 /// Handles the deserialization of data from a byte buffer.
 pub struct Deserializer<'a> {
     cursor: Cursor<&'a [u8]>,
     byte_order: ByteOrder,
+    /// Maximum nesting of arrays and maps accepted by `deserialize_next`
+    max_depth: usize,
+    /// Current nesting of arrays and maps
+    depth: usize,
 }
 
+/// Default maximum nesting depth for arrays and maps.
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+
 impl<'a> Deserializer<'a> {
     /// Creates a new Deserializer with the given buffer and byte order.
     pub fn new(buffer: &'a [u8], byte_order: ByteOrder) -> Self {
+        Deserializer::with_limits(buffer, byte_order, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Creates a new Deserializer that rejects arrays and maps nested deeper than `max_depth`.
+    ///
+    /// This bounds recursion in `deserialize_next`, so a malicious packet can't overflow the stack.
+    pub fn with_limits(buffer: &'a [u8], byte_order: ByteOrder, max_depth: usize) -> Self {
         Deserializer {
             cursor: Cursor::new(buffer),
             byte_order,
+            max_depth,
+            depth: 0,
+        }
+    }
+
+    /// Creates a new Deserializer over a buffer produced by `Serializer::new_versioned`.
+    ///
+    /// Validates the magic and returns the deserializer positioned after the header, along with the version.
+    #[allow(dead_code)]
+    pub fn new_versioned(buffer: &'a [u8], byte_order: ByteOrder) -> Result<(Self, u8), SerializationError> {
+        let header_len = MAGIC.len() + 1;
+        if buffer.len() < header_len || buffer[..MAGIC.len()] != MAGIC {
+            return Err(SerializationError::InvalidMagic);
+        }
+        let version = buffer[MAGIC.len()];
+        Ok((Deserializer::new(&buffer[header_len..], byte_order), version))
+    }
+
+    /// Creates a new Deserializer over a buffer produced by `Serializer::finish_with_checksum`.
+    ///
+    /// The trailing checksum is verified and stripped before decoding.
+    #[allow(dead_code)]
+    pub fn new_checked(buffer: &'a [u8], byte_order: ByteOrder) -> Result<Self, SerializationError> {
+        let payload = Deserializer::verify_checksum(buffer, byte_order)?;
+        Ok(Deserializer::new(payload, byte_order))
+    }
+
+    /// Verifies the trailing CRC32 of a buffer and returns the payload without it.
+    ///
+    /// A mismatch is reported as [`SerializationError::ChecksumMismatch`].
+    pub fn verify_checksum(buffer: &[u8], byte_order: ByteOrder) -> Result<&[u8], SerializationError> {
+        if buffer.len() < 4 {
+            return Err(SerializationError::UnexpectedEof);
+        }
+        let (payload, trailer) = buffer.split_at(buffer.len() - 4);
+        let trailer = [trailer[0], trailer[1], trailer[2], trailer[3]];
+        let expected = match byte_order {
+            ByteOrder::Big => u32::from_be_bytes(trailer),
+            ByteOrder::Little => u32::from_le_bytes(trailer),
+        };
+        let actual = crc32(payload);
+        if expected != actual {
+            return Err(SerializationError::ChecksumMismatch { expected, actual });
+        }
+        Ok(payload)
+    }
+
+    /// Enters a nested array or map, failing if the maximum depth is exceeded.
+    fn enter_nested(&mut self) -> Result<(), SerializationError> {
+        if self.depth >= self.max_depth {
+            return Err(SerializationError::DepthExceeded(self.max_depth));
         }
+        self.depth += 1;
+        Ok(())
     }
 
     /// Reads the data type from the buffer.
-    fn read_type(&mut self) -> std::io::Result<DataType> {
+    fn read_type(&mut self) -> Result<DataType, SerializationError> {
         let type_byte = self.cursor.read_u8()?;
-        DataType::from_u8(type_byte)
-            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid data type"))
+        DataType::from_tag(type_byte).ok_or(SerializationError::UnknownType(type_byte))
+    }
+
+    /// Reads the next type tag and fails unless it is `expected`.
+    fn expect_type(&mut self, expected: DataType) -> Result<(), SerializationError> {
+        let found = self.read_type()?;
+        if found != expected {
+            return Err(SerializationError::UnexpectedType { expected, found });
+        }
+        Ok(())
+    }
+
+    /// Returns the byte order numbers are read in.
+    #[allow(dead_code)]
+    pub fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// Returns the current read position in the buffer.
+    #[allow(dead_code)]
+    pub fn position(&self) -> u64 {
+        self.cursor.position()
+    }
+
+    /// Returns the number of bytes left to read.
+    pub fn remaining(&self) -> usize {
+        self.cursor.get_ref().len().saturating_sub(self.cursor.position() as usize)
+    }
+
+    /// Reads a length prefix, written as a tagged Int32, and validates it against the remaining bytes.
+    fn read_length(&mut self) -> Result<usize, SerializationError> {
+        match self.read_type()? {
+            DataType::Int32 => {
+                let declared_len = self.deserialize_int32()?;
+                self.check_length(declared_len)
+            }
+            DataType::VarInt32 => {
+                let declared_len = self.deserialize_varint32()?;
+                self.check_length(declared_len)
+            }
+            other => Err(SerializationError::UnexpectedType { expected: DataType::Int32, found: other }),
+        }
+    }
+
+    /// Validates a declared length against the bytes left in the buffer.
+    ///
+    /// Every element takes at least one byte, so a length larger than the remaining
+    /// bytes can only come from a malformed packet and is rejected before allocating.
+    fn check_length(&self, len: i32) -> Result<usize, SerializationError> {
+        if len < 0 || len as usize > self.remaining() {
+            return Err(SerializationError::LengthTooLarge { declared: len as i64, available: self.remaining() });
+        }
+        Ok(len as usize)
+    }
+
+    /// Returns the type of the next value without advancing the cursor.
+    #[allow(dead_code)]
+    pub fn peek_type(&self) -> Result<DataType, SerializationError> {
+        let position = self.cursor.position() as usize;
+        let type_byte = *self.cursor.get_ref().get(position).ok_or(SerializationError::UnexpectedEof)?;
+        DataType::from_tag(type_byte).ok_or(SerializationError::UnknownType(type_byte))
+    }
+
+    /// Deserializes the next value, or returns `Ok(None)` if the cursor is exactly at the end of the buffer.
+    ///
+    /// Unlike looping until `deserialize_next` fails, a value cut off part-way is still an error,
+    /// so corrupt data can't be mistaken for the end of the stream.
+    pub fn try_next(&mut self) -> Result<Option<Value>, SerializationError> {
+        if self.remaining() == 0 {
+            return Ok(None);
+        }
+        self.deserialize_next().map(Some)
     }
 
     /// Deserializes the next value from the buffer.
-    pub fn deserialize_next(&mut self) -> std::io::Result<Value> {
+    pub fn deserialize_next(&mut self) -> Result<Value, SerializationError> {
         let data_type = self.read_type()?;
         match data_type {
             DataType::Int32 => Ok(Value::Int32(self.deserialize_int32()?)),
+            DataType::VarInt32 => Ok(Value::Int32(self.deserialize_varint32()?)),
+            DataType::Int64 => Ok(Value::Int64(self.deserialize_int64()?)),
+            DataType::DateTime => Ok(Value::DateTime(self.deserialize_datetime()?)),
+            DataType::Enum => Ok(Value::Enum(self.deserialize_enum()?)),
             DataType::Bool => Ok(Value::Bool(self.deserialize_bool()?)),
             DataType::String => Ok(Value::String(self.deserialize_string()?)),
             DataType::Float => Ok(Value::Float(self.deserialize_float()?)),
+            DataType::Double => Ok(Value::Double(self.deserialize_double()?)),
+            DataType::Null => Ok(Value::Null),
+            DataType::Bytes => Ok(Value::Bytes(self.deserialize_bytes()?)),
             DataType::Array => {
-                let array = self.deserialize_array(|d| d.deserialize_next())?;
-                Ok(Value::Array(array))
+                self.enter_nested()?;
+                let array = self.deserialize_array(|d| d.deserialize_next());
+                self.depth -= 1;
+                Ok(Value::Array(array?))
             }
             DataType::Map => {
-                let map = self.deserialize_map(
-                    |d| d.deserialize_next().and_then(|v| v.into_string()),
-                    |d| d.deserialize_next(),
-                )?;
-                Ok(Value::Map(map))
+                self.enter_nested()?;
+                let entries = self.deserialize_entries();
+                self.depth -= 1;
+                let entries = entries?;
+                // Maps keyed entirely by strings decode as Value::Map, anything else keeps its keys as Values
+                if entries.iter().all(|(key, _)| matches!(key, Value::String(_))) {
+                    let map = entries.into_iter()
+                        .map(|(key, value)| key.into_string().map(|key| (key, value)))
+                        .collect::<Result<HashMap<_, _>, _>>()?;
+                    Ok(Value::Map(map))
+                } else {
+                    Ok(Value::KeyedMap(entries))
+                }
             }
         }
     }
 
     /// Deserializes an i32 value.
-    pub fn deserialize_int32(&mut self) -> std::io::Result<i32> {
+    pub fn deserialize_int32(&mut self) -> Result<i32, SerializationError> {
+        match self.byte_order {
+            ByteOrder::Big => Ok(self.cursor.read_i32::<BigEndian>()?),
+            ByteOrder::Little => Ok(self.cursor.read_i32::<LittleEndian>()?),
+        }
+    }
+
+    /// Deserializes an i32 value written as a zigzag LEB128 varint.
+    pub fn deserialize_varint32(&mut self) -> Result<i32, SerializationError> {
+        let mut zigzag = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = self.cursor.read_u8()?;
+            zigzag |= ((byte & 0x7F) as u32) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(((zigzag >> 1) as i32) ^ -((zigzag & 1) as i32));
+            }
+        }
+        Err(SerializationError::VarintTooLong)
+    }
+
+    /// Deserializes an i64 value.
+    pub fn deserialize_int64(&mut self) -> Result<i64, SerializationError> {
         match self.byte_order {
-            ByteOrder::Big => self.cursor.read_i32::<BigEndian>(),
-            ByteOrder::Little => self.cursor.read_i32::<LittleEndian>(),
+            ByteOrder::Big => Ok(self.cursor.read_i64::<BigEndian>()?),
+            ByteOrder::Little => Ok(self.cursor.read_i64::<LittleEndian>()?),
         }
     }
 
+    /// Deserializes a NaiveDateTime stored as milliseconds since the Unix epoch.
+    pub fn deserialize_datetime(&mut self) -> Result<NaiveDateTime, SerializationError> {
+        let millis = self.deserialize_int64()?;
+        DateTime::from_timestamp_millis(millis)
+            .map(|datetime| datetime.naive_utc())
+            .ok_or(SerializationError::TimestampOutOfRange(millis))
+    }
+
+    /// Deserializes an enum's one-byte discriminant.
+    pub fn deserialize_enum(&mut self) -> Result<u8, SerializationError> {
+        Ok(self.cursor.read_u8()?)
+    }
+
+    /// Deserializes a tagged enum and looks up its label, failing if `registry` doesn't define the discriminant.
+    #[allow(dead_code)]
+    pub fn deserialize_enum_label(&mut self, registry: &EnumRegistry) -> Result<&'static str, SerializationError> {
+        self.expect_type(DataType::Enum)?;
+        let discriminant = self.deserialize_enum()?;
+        registry.label(discriminant)
+    }
+
     /// Deserializes a boolean value.
-    pub fn deserialize_bool(&mut self) -> std::io::Result<bool> {
+    pub fn deserialize_bool(&mut self) -> Result<bool, SerializationError> {
         Ok(self.cursor.read_u8()? != 0)
     }
 
     /// Deserializes a string value.
-    pub fn deserialize_string(&mut self) -> std::io::Result<String> {
-        self.cursor.set_position(self.cursor.position() + 1);
-        let len = self.deserialize_int32()? as usize;
+    pub fn deserialize_string(&mut self) -> Result<String, SerializationError> {
+        let len = self.read_length()?;
+        let position = self.cursor.position() as usize;
+        let mut buffer = vec![0u8; len];
+        self.cursor.read_exact(&mut buffer)?;
+        String::from_utf8(buffer).map_err(|e| SerializationError::InvalidUtf8 { position, valid_up_to: e.utf8_error().valid_up_to() })
+    }
+
+    /// Deserializes raw bytes. Unlike strings, no UTF-8 validation is performed.
+    pub fn deserialize_bytes(&mut self) -> Result<Vec<u8>, SerializationError> {
+        let len = self.read_length()?;
         let mut buffer = vec![0u8; len];
         self.cursor.read_exact(&mut buffer)?;
-        String::from_utf8(buffer).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        Ok(buffer)
     }
 
     /// Deserializes a f32 value.
-    pub fn deserialize_float(&mut self) -> std::io::Result<f32> {
+    pub fn deserialize_float(&mut self) -> Result<f32, SerializationError> {
+        match self.byte_order {
+            ByteOrder::Big => Ok(self.cursor.read_f32::<BigEndian>()?),
+            ByteOrder::Little => Ok(self.cursor.read_f32::<LittleEndian>()?),
+        }
+    }
+
+    /// Deserializes a f64 value.
+    pub fn deserialize_double(&mut self) -> Result<f64, SerializationError> {
         match self.byte_order {
-            ByteOrder::Big => self.cursor.read_f32::<BigEndian>(),
-            ByteOrder::Little => self.cursor.read_f32::<LittleEndian>(),
+            ByteOrder::Big => Ok(self.cursor.read_f64::<BigEndian>()?),
+            ByteOrder::Little => Ok(self.cursor.read_f64::<LittleEndian>()?),
         }
     }
 
     /// Deserializes an array of items.
-    pub fn deserialize_array<T, F>(&mut self, deserialize_item: F) -> std::io::Result<Vec<T>>
+    pub fn deserialize_array<T, F>(&mut self, deserialize_item: F) -> Result<Vec<T>, SerializationError>
     where
-        F: Fn(&mut Self) -> std::io::Result<T>,
+        F: Fn(&mut Self) -> Result<T, SerializationError>,
     {   
-        self.cursor.set_position(self.cursor.position() + 1);
-        let len = self.deserialize_int32()? as usize;
+        let len = self.read_length()?;
         let mut array = Vec::with_capacity(len);
         for _ in 0..len {
             array.push(deserialize_item(self)?);
@@ -261,19 +911,51 @@ impl<'a> Deserializer<'a> {
         Ok(array)
     }
 
+    /// Reads a tagged array of Int32s, failing with `UnexpectedType` at the first element of another type.
+    pub fn deserialize_int32_array(&mut self) -> Result<Vec<i32>, SerializationError> {
+        Vec::<i32>::deserialize(self)
+    }
+
+    /// Reads a tagged array of Strings, failing with `UnexpectedType` at the first element of another type.
+    pub fn deserialize_string_array(&mut self) -> Result<Vec<String>, SerializationError> {
+        Vec::<String>::deserialize(self)
+    }
+
+    /// Reads a tagged array of Floats, failing with `UnexpectedType` at the first element of another type.
+    pub fn deserialize_float_array(&mut self) -> Result<Vec<f32>, SerializationError> {
+        Vec::<f32>::deserialize(self)
+    }
+
+    /// Reads a tagged array of Bools, failing with `UnexpectedType` at the first element of another type.
+    pub fn deserialize_bool_array(&mut self) -> Result<Vec<bool>, SerializationError> {
+        Vec::<bool>::deserialize(self)
+    }
+
+    /// Deserializes the entries of a map as key-value pairs in the order they were written.
+    fn deserialize_entries(&mut self) -> Result<Vec<(Value, Value)>, SerializationError> {
+        let len = self.read_length()?;
+        let mut entries = Vec::with_capacity(len);
+        for _ in 0..len {
+            let key = self.deserialize_next()?;
+            let value = self.deserialize_next()?;
+            entries.push((key, value));
+        }
+        Ok(entries)
+    }
+
     /// Deserializes a map of key-value pairs.
+    #[allow(dead_code)]
     pub fn deserialize_map<K, V, FK, FV>(
         &mut self,
         deserialize_key: FK,
         deserialize_value: FV,
-    ) -> std::io::Result<HashMap<K, V>>
+    ) -> Result<HashMap<K, V>, SerializationError>
     where
         K: std::hash::Hash + Eq,
-        FK: Fn(&mut Self) -> std::io::Result<K>,
-        FV: Fn(&mut Self) -> std::io::Result<V>,
+        FK: Fn(&mut Self) -> Result<K, SerializationError>,
+        FV: Fn(&mut Self) -> Result<V, SerializationError>,
     {
-        self.cursor.set_position(self.cursor.position() + 1);
-        let len = self.deserialize_int32()? as usize;
+        let len = self.read_length()?;
         let mut map = HashMap::with_capacity(len);
         for _ in 0..len {
             let key = deserialize_key(self)?;
@@ -284,43 +966,279 @@ impl<'a> Deserializer<'a> {
     }
 }
 
-/// Represents a deserialized value.
-#[derive(Debug)]
-pub enum Value {
-    Int32(i32),
-    Bool(bool),
-    String(String),
-    Float(f32),
-    Array(Vec<Value>),
-    Map(HashMap<String, Value>),
+/// Fixed-size header written before a request's payload map.
+///
+/// The header is raw bytes without type tags: a flag byte naming the byte order (`b'B'` or `b'L'`),
+/// then, in that byte order, an 8-byte request_id followed by one byte each for the invocation
+/// semantic and the action. The flag lets a receiver reject a peer with a different byte order
+/// instead of reading garbled numbers.
+/// The invocation semantic is kept as a raw byte so that unknown values can be reported;
+/// see [`InvocationSemantic`] for the defined ones.
+/// A receiver can read it with [`Envelope::deserialize`] to route and deduplicate a request
+/// before decoding the payload that follows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope {
+    pub request_id: u64,
+    pub invocation_semantic: u8,
+    pub action: u8,
 }
 
-impl fmt::Display for Value {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Int32(v) => write!(f, "{}", v),
-            Value::Bool(v) => write!(f, "{}", v),
-            Value::String(v) => write!(f, "{}", v),
-            Value::Float(v) => write!(f, "{}", v),
-            Value::Array(v) => {
-                write!(f, "[")?;
-                for (i, item) in v.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, ", ")?;
-                    }
-                    write!(f, "{}", item)?;
-                }
-                write!(f, "]")
-            },
-            Value::Map(m) => {
-                write!(f, "{{")?;
-                for (i, (key, value)) in m.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, ", ")?;
-                    }
-                    write!(f, "{}: {}", key, value)?;
-                }
-                write!(f, "}}")
+impl Envelope {
+    /// Encoded size of an envelope in bytes.
+    pub const LEN: usize = 11;
+
+    /// Writes the envelope to the serializer's buffer.
+    pub fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        serializer.buffer.write_u8(serializer.byte_order.flag())?;
+        match serializer.byte_order {
+            ByteOrder::Big => serializer.buffer.write_u64::<BigEndian>(self.request_id)?,
+            ByteOrder::Little => serializer.buffer.write_u64::<LittleEndian>(self.request_id)?,
+        }
+        serializer.buffer.write_u8(self.invocation_semantic)?;
+        serializer.buffer.write_u8(self.action)
+    }
+
+    /// Reads an envelope, leaving the deserializer positioned at the payload.
+    ///
+    /// Fails with `ByteOrderMismatch` if the envelope was written in the other byte order.
+    pub fn deserialize(deserializer: &mut Deserializer) -> Result<Envelope, SerializationError> {
+        let flag = deserializer.cursor.read_u8()?;
+        let found = ByteOrder::from_flag(flag).ok_or(SerializationError::UnknownByteOrder(flag))?;
+        if found != deserializer.byte_order {
+            return Err(SerializationError::ByteOrderMismatch { expected: deserializer.byte_order, found });
+        }
+        let request_id = match deserializer.byte_order {
+            ByteOrder::Big => deserializer.cursor.read_u64::<BigEndian>()?,
+            ByteOrder::Little => deserializer.cursor.read_u64::<LittleEndian>()?,
+        };
+        Ok(Envelope {
+            request_id,
+            invocation_semantic: deserializer.cursor.read_u8()?,
+            action: deserializer.cursor.read_u8()?,
+        })
+    }
+}
+
+/// The labels of an enum sent as [`DataType::Enum`], e.g. response status codes.
+///
+/// Only the one-byte discriminant goes on the wire; both ends share a registry to turn it back
+/// into a label, and a discriminant the registry doesn't define is rejected instead of passed on.
+#[derive(Debug, Clone, Copy)]
+pub struct EnumRegistry {
+    /// What the enum is, used in error messages, e.g. `"status"`.
+    pub name: &'static str,
+    labels: &'static [(u8, &'static str)],
+}
+
+#[allow(dead_code)]
+impl EnumRegistry {
+    /// Creates a registry from `(discriminant, label)` pairs.
+    pub const fn new(name: &'static str, labels: &'static [(u8, &'static str)]) -> Self {
+        EnumRegistry { name, labels }
+    }
+
+    /// Returns the label for a discriminant, or `UnknownEnum` if it isn't defined.
+    pub fn label(&self, discriminant: u8) -> Result<&'static str, SerializationError> {
+        self.labels
+            .iter()
+            .find(|(value, _)| *value == discriminant)
+            .map(|(_, label)| *label)
+            .ok_or(SerializationError::UnknownEnum { name: self.name, discriminant })
+    }
+
+    /// Returns the discriminant for a label, if it is defined.
+    pub fn discriminant(&self, label: &str) -> Option<u8> {
+        self.labels.iter().find(|(_, known)| *known == label).map(|(value, _)| *value)
+    }
+}
+
+/// How the server treats retransmissions of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvocationSemantic {
+    /// Executed once; retransmissions are answered from the server's dedup store.
+    AtMostOnce = 0,
+    /// Executed again every time it arrives.
+    AtLeastOnce = 1,
+}
+
+impl FromStr for InvocationSemantic {
+    type Err = String;
+
+    /// Parses an invocation semantic from its config name, `"at-most-once"` or `"at-least-once"`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "at-most-once" => Ok(InvocationSemantic::AtMostOnce),
+            "at-least-once" => Ok(InvocationSemantic::AtLeastOnce),
+            _ => Err(format!("Unknown invocation semantic '{}', expected \"at-most-once\" or \"at-least-once\"", s)),
+        }
+    }
+}
+
+impl TryFrom<u8> for InvocationSemantic {
+    type Error = u8;
+
+    /// Converts the byte carried in an [`Envelope`], returning it back if it is not a known semantic.
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(InvocationSemantic::AtMostOnce),
+            1 => Ok(InvocationSemantic::AtLeastOnce),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<InvocationSemantic> for u8 {
+    fn from(semantic: InvocationSemantic) -> u8 {
+        semantic as u8
+    }
+}
+
+/// Represents a deserialized value.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Int32(i32),
+    Int64(i64),
+    Bool(bool),
+    String(String),
+    Float(f32),
+    Double(f64),
+    Array(Vec<Value>),
+    Map(HashMap<String, Value>),
+    /// A map with at least one non-string key, e.g. a map keyed by flight ID.
+    KeyedMap(Vec<(Value, Value)>),
+    Null,
+    Bytes(Vec<u8>),
+    DateTime(NaiveDateTime),
+    /// An enum discriminant; see [`EnumRegistry`] for mapping it to a label.
+    Enum(u8),
+}
+
+/// Values are equal when they have the same variant and contents.
+///
+/// `Float` and `Double` compare by their bit patterns, so a value always equals its own
+/// round-trip through the wire format: `NaN` equals an identical `NaN`, while `0.0` and `-0.0`
+/// are different. `KeyedMap` entries are compared in order.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Int32(a), Value::Int32(b)) => a == b,
+            (Value::Int64(a), Value::Int64(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            (Value::Double(a), Value::Double(b)) => a.to_bits() == b.to_bits(),
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (Value::Map(a), Value::Map(b)) => a == b,
+            (Value::KeyedMap(a), Value::KeyedMap(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            (Value::Bytes(a), Value::Bytes(b)) => a == b,
+            (Value::DateTime(a), Value::DateTime(b)) => a == b,
+            (Value::Enum(a), Value::Enum(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// Bitwise float comparison makes equality reflexive, so `Value` is also `Eq`.
+impl Eq for Value {}
+
+macro_rules! impl_value_from {
+    ($($source:ty => $variant:ident),* $(,)?) => {
+        $(impl From<$source> for Value {
+            fn from(value: $source) -> Self {
+                Value::$variant(value.into())
+            }
+        })*
+    };
+}
+
+impl_value_from! {
+    i32 => Int32,
+    i64 => Int64,
+    bool => Bool,
+    f32 => Float,
+    f64 => Double,
+    String => String,
+    &str => String,
+    Vec<Value> => Array,
+}
+
+impl Serialize for Value {
+    fn serialize(&self, serializer: &mut Serializer) -> std::io::Result<()> {
+        match self {
+            Value::Int32(v) => serializer.serialize_int32(*v),
+            Value::Int64(v) => serializer.serialize_int64(*v),
+            Value::Bool(v) => serializer.serialize_bool(*v),
+            Value::String(v) => serializer.serialize_string(v),
+            Value::Float(v) => serializer.serialize_float(*v),
+            Value::Double(v) => serializer.serialize_double(*v),
+            Value::Array(v) => serializer.serialize_array(v),
+            Value::Map(v) => serializer.serialize_map(v),
+            Value::KeyedMap(entries) => {
+                serializer.write_header(DataType::Map, entries.len())?;
+                for (key, value) in entries {
+                    key.serialize(serializer)?;
+                    value.serialize(serializer)?;
+                }
+                Ok(())
+            }
+            Value::Null => serializer.serialize_null(),
+            Value::Bytes(v) => serializer.serialize_bytes(v),
+            Value::DateTime(v) => serializer.serialize_datetime(v),
+            Value::Enum(v) => serializer.serialize_enum(*v),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int32(v) => write!(f, "{}", v),
+            Value::Int64(v) => write!(f, "{}", v),
+            Value::Bool(v) => write!(f, "{}", v),
+            Value::String(v) => write!(f, "{}", v),
+            Value::Float(v) => write!(f, "{}", v),
+            // `{}` prints the shortest representation that parses back to the same f64
+            Value::Double(v) => write!(f, "{}", v),
+            Value::Array(v) => {
+                write!(f, "[")?;
+                for (i, item) in v.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Value::Map(m) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in m.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            },
+            Value::KeyedMap(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            },
+            Value::Null => write!(f, "null"),
+            Value::DateTime(v) => write!(f, "{}", v),
+            Value::Enum(v) => write!(f, "enum({})", v),
+            Value::Bytes(v) => {
+                write!(f, "0x")?;
+                for byte in v {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
             },
         }
     }
@@ -328,6 +1246,24 @@ impl fmt::Display for Value {
 
 #[allow(dead_code)]
 impl Value {
+    /// Returns the DataType this value is serialized as.
+    pub fn data_type(&self) -> DataType {
+        match self {
+            Value::Int32(_) => DataType::Int32,
+            Value::Int64(_) => DataType::Int64,
+            Value::Bool(_) => DataType::Bool,
+            Value::String(_) => DataType::String,
+            Value::Float(_) => DataType::Float,
+            Value::Double(_) => DataType::Double,
+            Value::Array(_) => DataType::Array,
+            Value::Map(_) | Value::KeyedMap(_) => DataType::Map,
+            Value::Null => DataType::Null,
+            Value::Bytes(_) => DataType::Bytes,
+            Value::DateTime(_) => DataType::DateTime,
+            Value::Enum(_) => DataType::Enum,
+        }
+    }
+
     /// Returns the value as an i32 if it is an Int32, otherwise None.
     pub fn as_i32(&self) -> Option<i32> {
         match self {
@@ -336,6 +1272,14 @@ impl Value {
         }
     }
 
+    /// Returns the value as an i64 if it is an Int64, otherwise None.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Value::Int64(v) => Some(*v),
+            _ => None,
+        }
+    }
+
     /// Returns the value as a bool if it is a Bool, otherwise None.
     pub fn as_bool(&self) -> Option<bool> {
         match self {
@@ -360,6 +1304,14 @@ impl Value {
         }
     }
 
+    /// Returns the value as an f64 if it is a Double, otherwise None.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Double(v) => Some(*v),
+            _ => None,
+        }
+    }
+
     /// Returns a reference to the Vec<Value> if it is an Array, otherwise None.
     pub fn as_array(&self) -> Option<&Vec<Value>> {
         match self {
@@ -376,12 +1328,927 @@ impl Value {
         }
     }
 
+    /// Walks nested maps by key, returning None if a key is missing or a value along the path isn't a Map.
+    pub fn get_path(&self, path: &[&str]) -> Option<&Value> {
+        path.iter().try_fold(self, |value, key| value.as_map()?.get(*key))
+    }
+
+    /// Returns the i-th element if it is an Array, otherwise None.
+    pub fn index(&self, i: usize) -> Option<&Value> {
+        self.as_array()?.get(i)
+    }
+
+    /// Returns the entries if it is a KeyedMap, otherwise None.
+    pub fn as_keyed_map(&self) -> Option<&Vec<(Value, Value)>> {
+        match self {
+            Value::KeyedMap(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns a reference to the bytes if it is Bytes, otherwise None.
+    pub fn as_bytes(&self) -> Option<&Vec<u8>> {
+        match self {
+            Value::Bytes(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the value as a NaiveDateTime if it is a DateTime, otherwise None.
+    pub fn as_datetime(&self) -> Option<NaiveDateTime> {
+        match self {
+            Value::DateTime(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns the discriminant if the value is an Enum, otherwise None.
+    pub fn as_enum(&self) -> Option<u8> {
+        match self {
+            Value::Enum(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the value is Null.
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
     /// Converts the Value into a String if it is a String, otherwise returns an error.
-    fn into_string(self) -> std::io::Result<String> {
+    fn into_string(self) -> Result<String, SerializationError> {
         if let Value::String(s) = self {
             Ok(s)
         } else {
-            Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "Value is not a String"))
+            Err(SerializationError::UnexpectedType { expected: DataType::String, found: self.data_type() })
+        }
+    }
+}
+
+/// JSON interop for debugging and logging, enabled with the `json` feature.
+///
+/// `to_json` maps `Int32`/`Int64`/`Float`/`Double` to numbers, `Map` to an object, `Array` to an
+/// array and `Null` to null. Some values don't fit JSON cleanly:
+/// - non-finite `Float`/`Double` values (NaN, infinities) become `null`;
+/// - `Float` is widened to f64, so e.g. `0.1f32` prints as `0.10000000149011612`;
+/// - `KeyedMap` keys are converted to strings with their `Display` form;
+/// - `Bytes` become an array of numbers and `DateTime` a `%Y-%m-%d %H:%M:%S%.f` string;
+/// - `Enum` becomes its discriminant as a number.
+///
+/// `from_json` picks the narrowest type for numbers (`Int32`, then `Int64`, then `Double`) and
+/// rejects unsigned integers above `i64::MAX`, so `to_json` followed by `from_json` is lossy for
+/// `Float`, `Bytes`, `DateTime`, `Enum` and `KeyedMap`.
+#[cfg(feature = "json")]
+impl Value {
+    /// Converts the Value into a JSON value.
+    pub fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value as Json;
+        match self {
+            Value::Int32(v) => Json::from(*v),
+            Value::Int64(v) => Json::from(*v),
+            Value::Bool(v) => Json::from(*v),
+            Value::String(v) => Json::from(v.clone()),
+            Value::Float(v) => serde_json::Number::from_f64(*v as f64).map_or(Json::Null, Json::Number),
+            Value::Double(v) => serde_json::Number::from_f64(*v).map_or(Json::Null, Json::Number),
+            Value::Array(v) => Json::Array(v.iter().map(Value::to_json).collect()),
+            Value::Map(m) => Json::Object(m.iter().map(|(key, value)| (key.clone(), value.to_json())).collect()),
+            Value::KeyedMap(entries) => Json::Object(entries.iter().map(|(key, value)| (key.to_string(), value.to_json())).collect()),
+            Value::Null => Json::Null,
+            Value::Bytes(v) => Json::Array(v.iter().map(|&byte| Json::from(byte)).collect()),
+            Value::DateTime(v) => Json::from(v.format("%Y-%m-%d %H:%M:%S%.f").to_string()),
+            Value::Enum(v) => Json::from(*v),
+        }
+    }
+
+    /// Converts a JSON value into a Value.
+    pub fn from_json(json: &serde_json::Value) -> Result<Value, SerializationError> {
+        use serde_json::Value as Json;
+        match json {
+            Json::Null => Ok(Value::Null),
+            Json::Bool(v) => Ok(Value::Bool(*v)),
+            Json::Number(n) => {
+                if let Some(v) = n.as_i64() {
+                    Ok(i32::try_from(v).map_or(Value::Int64(v), Value::Int32))
+                } else if n.is_u64() {
+                    Err(SerializationError::InvalidJson(format!("integer {} is out of range", n)))
+                } else {
+                    n.as_f64()
+                        .map(Value::Double)
+                        .ok_or_else(|| SerializationError::InvalidJson(format!("number {} is not representable", n)))
+                }
+            }
+            Json::String(v) => Ok(Value::String(v.clone())),
+            Json::Array(v) => v.iter().map(Value::from_json).collect::<Result<Vec<_>, _>>().map(Value::Array),
+            Json::Object(m) => m
+                .iter()
+                .map(|(key, value)| Value::from_json(value).map(|value| (key.clone(), value)))
+                .collect::<Result<HashMap<_, _>, _>>()
+                .map(Value::Map),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// Serializes `value` in `byte_order` and decodes it back, checking nothing is left over.
+    fn round_trip(value: &Value, byte_order: ByteOrder) -> Value {
+        let mut serializer = Serializer::new(byte_order);
+        serializer.serialize_value(value).unwrap();
+        let buffer = serializer.get_buffer();
+        let mut deserializer = Deserializer::new(&buffer, byte_order);
+        let decoded = deserializer.deserialize_next().unwrap();
+        assert_eq!(deserializer.remaining(), 0, "trailing bytes after {:?}", value);
+        decoded
+    }
+
+    fn departure() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 9, 1).unwrap().and_hms_opt(10, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn with_buffer_reuses_the_callers_allocation() {
+        let buffer = Vec::with_capacity(256);
+        let allocation = buffer.as_ptr();
+        let mut serializer = Serializer::with_buffer(buffer, ByteOrder::Little);
+        serializer.serialize_string("New York").unwrap();
+        serializer.serialize_array(&[1, 2, 3]).unwrap();
+        serializer.serialize_double(299.99).unwrap();
+
+        let buffer = serializer.get_buffer();
+        assert!(!buffer.is_empty());
+        assert_eq!(buffer.capacity(), 256);
+        assert_eq!(buffer.as_ptr(), allocation);
+    }
+
+    #[test]
+    fn every_value_type_round_trips_in_both_byte_orders() {
+        let mut map = HashMap::new();
+        map.insert("status".to_string(), Value::from("200"));
+        map.insert("seats_available".to_string(), Value::Int32(42));
+        let values = vec![
+            Value::Int32(-7),
+            Value::Int32(i32::MAX),
+            Value::Int64(i64::MIN),
+            Value::Bool(true),
+            Value::from("London→Paris"),
+            Value::from(""),
+            Value::Float(199.5),
+            Value::Double(0.1),
+            Value::Array(vec![Value::Int32(1), Value::from("two"), Value::Null]),
+            Value::Map(map),
+            Value::KeyedMap(vec![(Value::Int32(1), Value::from("New York")), (Value::Int32(2), Value::from("London"))]),
+            Value::Null,
+            Value::Bytes(vec![0, 255, 10]),
+            Value::DateTime(departure()),
+            Value::Enum(3),
+        ];
+        for byte_order in [ByteOrder::Little, ByteOrder::Big] {
+            for value in &values {
+                assert_eq!(&round_trip(value, byte_order), value, "{} endian", byte_order);
+            }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn peek_type_does_not_consume_the_value() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_string("London").unwrap();
+        serializer.serialize_int32(7).unwrap();
+        let buffer = serializer.get_buffer();
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+
+        assert_eq!(deserializer.peek_type().unwrap(), DataType::String);
+        assert_eq!(deserializer.peek_type().unwrap(), DataType::String);
+        assert_eq!(deserializer.position(), 0);
+        assert_eq!(deserializer.deserialize_next().unwrap(), Value::String("London".to_string()));
+        assert_eq!(deserializer.peek_type().unwrap(), DataType::Int32);
+        assert_eq!(deserializer.deserialize_next().unwrap(), Value::Int32(7));
+        assert!(matches!(deserializer.peek_type(), Err(SerializationError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn peek_type_rejects_an_unknown_tag() {
+        let deserializer = Deserializer::new(&[0xee], ByteOrder::Little);
+        assert!(matches!(deserializer.peek_type(), Err(SerializationError::UnknownType(0xee))));
+    }
+
+    #[test]
+    fn big_endian_writes_the_most_significant_byte_first() {
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_int32(0x0102_0304).unwrap();
+        serializer.serialize_int64(-2).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer[..5], [1, 1, 2, 3, 4]);
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Big);
+        assert_eq!(deserializer.deserialize_next().unwrap(), Value::Int32(0x0102_0304));
+        assert_eq!(deserializer.deserialize_next().unwrap(), Value::Int64(-2));
+        // The same bytes read as little-endian come out as a different number
+        assert_eq!(Deserializer::new(&buffer, ByteOrder::Little).deserialize_next().unwrap(), Value::Int32(0x0403_0201));
+    }
+
+    #[test]
+    fn envelope_in_the_other_byte_order_is_reported_as_a_mismatch() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        assert_eq!(serializer.byte_order(), ByteOrder::Little);
+        let envelope = Envelope { request_id: 1, invocation_semantic: 0, action: 3 };
+        envelope.serialize(&mut serializer).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer.len(), Envelope::LEN);
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Big);
+        assert_eq!(deserializer.byte_order(), ByteOrder::Big);
+        assert!(matches!(
+            Envelope::deserialize(&mut deserializer),
+            Err(SerializationError::ByteOrderMismatch { expected: ByteOrder::Big, found: ByteOrder::Little })
+        ));
+        assert_eq!(Envelope::deserialize(&mut Deserializer::new(&buffer, ByteOrder::Little)).unwrap(), envelope);
+    }
+
+    #[test]
+    fn envelope_with_an_unknown_byte_order_flag_is_rejected() {
+        let mut buffer = vec![0u8; Envelope::LEN];
+        buffer[0] = b'X';
+        assert!(matches!(
+            Envelope::deserialize(&mut Deserializer::new(&buffer, ByteOrder::Little)),
+            Err(SerializationError::UnknownByteOrder(b'X'))
+        ));
+    }
+
+    #[test]
+    fn every_data_type_round_trips_through_its_tag() {
+        let all = [
+            DataType::Int32, DataType::Bool, DataType::String, DataType::Float, DataType::Array,
+            DataType::Map, DataType::Int64, DataType::Double, DataType::Null, DataType::Bytes,
+            DataType::VarInt32, DataType::DateTime, DataType::Enum,
+        ];
+        for data_type in all {
+            assert_eq!(DataType::from_tag(data_type.tag()), Some(data_type));
+        }
+        let mut tags: Vec<u8> = all.iter().map(|data_type| data_type.tag()).collect();
+        tags.sort_unstable();
+        tags.dedup();
+        assert_eq!(tags.len(), all.len(), "tags must be distinct");
+        assert_eq!(DataType::from_tag(0), None);
+        assert_eq!(DataType::from_tag(14), None);
+    }
+
+    #[test]
+    fn deserialize_string_reads_a_string_after_its_tag() {
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_string("Tokyo").unwrap();
+        let buffer = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Big);
+        deserializer.expect_type(DataType::String).unwrap();
+        assert_eq!(deserializer.deserialize_string().unwrap(), "Tokyo");
+        assert_eq!(deserializer.remaining(), 0);
+    }
+
+    #[test]
+    fn length_prefix_with_the_wrong_tag_is_rejected() {
+        let buffer = [DataType::String.tag(), DataType::Bool.tag(), 1, b'x'];
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        assert!(matches!(
+            deserializer.deserialize_next(),
+            Err(SerializationError::UnexpectedType { expected: DataType::Int32, found: DataType::Bool })
+        ));
+    }
+
+    #[test]
+    fn decoded_values_re_serialize_to_the_same_bytes() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_array(&[Value::Int32(1), Value::from("Paris"), Value::Array(vec![Value::Bool(false), Value::Null])]).unwrap();
+        // A single entry, so the HashMap's iteration order can't change the bytes
+        serializer.serialize_map(&HashMap::from([("status".to_string(), Value::from("200"))])).unwrap();
+        serializer.serialize_datetime(&departure()).unwrap();
+        let original = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&original, ByteOrder::Little);
+        let mut proxy = Serializer::new(ByteOrder::Little);
+        while let Some(value) = deserializer.try_next().unwrap() {
+            value.serialize(&mut proxy).unwrap();
+        }
+        assert_eq!(proxy.get_buffer(), original);
+    }
+
+    /// `levels` arrays, each holding only the next one, around a Null
+    fn nested_arrays(levels: usize) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        for _ in 0..levels {
+            buffer.push(DataType::Array.tag());
+            buffer.push(DataType::Int32.tag());
+            buffer.extend_from_slice(&1i32.to_le_bytes());
+        }
+        buffer.push(DataType::Null.tag());
+        buffer
+    }
+
+    #[test]
+    fn thousand_nested_arrays_fail_with_depth_exceeded() {
+        let buffer = nested_arrays(1000);
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        assert!(matches!(deserializer.deserialize_next(), Err(SerializationError::DepthExceeded(DEFAULT_MAX_DEPTH))));
+    }
+
+    #[test]
+    fn nesting_up_to_max_depth_is_accepted() {
+        let buffer = nested_arrays(3);
+        assert!(Deserializer::with_limits(&buffer, ByteOrder::Little, 3).deserialize_next().is_ok());
+        assert!(matches!(
+            Deserializer::with_limits(&buffer, ByteOrder::Little, 2).deserialize_next(),
+            Err(SerializationError::DepthExceeded(2))
+        ));
+        // The typed decoders count depth as well
+        let mut deserializer = Deserializer::with_limits(&buffer, ByteOrder::Little, 2);
+        assert!(matches!(Vec::<Vec<Vec<i32>>>::deserialize(&mut deserializer), Err(SerializationError::DepthExceeded(2))));
+    }
+
+    #[test]
+    fn varint_encoding_shrinks_a_flight_ids_response() {
+        let mut response = HashMap::new();
+        response.insert("status".to_string(), Value::from("200"));
+        response.insert("flight_ids".to_string(), Value::Array((1..=5).map(Value::Int32).collect()));
+
+        let mut fixed = Serializer::new(ByteOrder::Little);
+        fixed.serialize_value_map(&response).unwrap();
+        let fixed = fixed.get_buffer();
+        let mut varint = Serializer::new_varint(ByteOrder::Little);
+        varint.serialize_value_map(&response).unwrap();
+        let varint = varint.get_buffer();
+
+        // Every length prefix and flight ID drops from 5 bytes to 2
+        assert_eq!((fixed.len(), varint.len()), (74, 44));
+        // A plain Deserializer reads both, and small flight IDs still come back as Int32
+        for buffer in [&fixed, &varint] {
+            assert_eq!(Deserializer::new(buffer, ByteOrder::Little).deserialize_next().unwrap(), Value::Map(response.clone()));
+        }
+    }
+
+    #[test]
+    fn varints_round_trip_at_the_edges_of_i32() {
+        for value in [0, -1, 63, -64, 64, i32::MAX, i32::MIN] {
+            let mut serializer = Serializer::new_varint(ByteOrder::Big);
+            serializer.serialize_int32(value).unwrap();
+            let buffer = serializer.get_buffer();
+            assert_eq!(Deserializer::new(&buffer, ByteOrder::Big).deserialize_next().unwrap(), Value::Int32(value));
+        }
+    }
+
+    #[test]
+    fn position_and_remaining_track_a_multi_value_buffer() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_int32(7).unwrap();
+        serializer.serialize_bool(true).unwrap();
+        serializer.serialize_string("Oslo").unwrap();
+        let buffer = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        assert_eq!((deserializer.position(), deserializer.remaining()), (0, 17));
+        let mut seen = Vec::new();
+        while deserializer.remaining() > 0 {
+            let position = deserializer.position();
+            seen.push((position, deserializer.deserialize_next().unwrap()));
+        }
+        assert_eq!(seen, vec![(0, Value::Int32(7)), (5, Value::Bool(true)), (7, Value::from("Oslo"))]);
+        assert_eq!((deserializer.position(), deserializer.remaining()), (17, 0));
+    }
+
+    #[test]
+    fn map_keyed_by_flight_id_round_trips() {
+        let flights = HashMap::from([(1, "New York".to_string()), (2, "London".to_string()), (3, "Tokyo".to_string())]);
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_map(&flights).unwrap();
+        serializer.serialize_map(&flights).unwrap();
+        let buffer = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        let decoded = deserializer.deserialize_next().unwrap();
+        let entries: HashMap<i32, String> = decoded.as_keyed_map().unwrap()
+            .iter()
+            .map(|(key, value)| (key.as_i32().unwrap(), value.as_string().unwrap().clone()))
+            .collect();
+        assert_eq!(entries, flights);
+
+        deserializer.expect_type(DataType::Map).unwrap();
+        assert_eq!(deserializer.deserialize_map(i32::deserialize, String::deserialize).unwrap(), flights);
+    }
+
+    #[test]
+    fn typed_deserialize_reads_each_primitive() {
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_int32(-5).unwrap();
+        serializer.serialize_float(2.5).unwrap();
+        serializer.serialize_bool(true).unwrap();
+        serializer.serialize_string("Rome").unwrap();
+        serializer.serialize_array(&["a", "b"]).unwrap();
+        serializer.serialize_array(&[1, 2, 3]).unwrap();
+        let buffer = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Big);
+        assert_eq!(i32::deserialize(&mut deserializer).unwrap(), -5);
+        assert_eq!(f32::deserialize(&mut deserializer).unwrap(), 2.5);
+        assert!(bool::deserialize(&mut deserializer).unwrap());
+        assert_eq!(String::deserialize(&mut deserializer).unwrap(), "Rome");
+        assert_eq!(Vec::<String>::deserialize(&mut deserializer).unwrap(), ["a", "b"]);
+        assert_eq!(Vec::<i32>::deserialize(&mut deserializer).unwrap(), [1, 2, 3]);
+        assert_eq!(deserializer.remaining(), 0);
+    }
+
+    #[test]
+    fn typed_deserialize_accepts_varints_as_i32() {
+        let mut serializer = Serializer::new_varint(ByteOrder::Little);
+        serializer.serialize_int32(-300).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(i32::deserialize(&mut Deserializer::new(&buffer, ByteOrder::Little)).unwrap(), -300);
+    }
+
+    #[test]
+    fn typed_deserialize_rejects_another_type() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_string("7").unwrap();
+        let buffer = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        assert!(matches!(
+            i32::deserialize(&mut deserializer),
+            Err(SerializationError::UnexpectedType { expected: DataType::Int32, found: DataType::String })
+        ));
+        for result in [
+            f32::deserialize(&mut Deserializer::new(&buffer, ByteOrder::Little)).map(|_| ()),
+            bool::deserialize(&mut Deserializer::new(&buffer, ByteOrder::Little)).map(|_| ()),
+            Vec::<String>::deserialize(&mut Deserializer::new(&buffer, ByteOrder::Little)).map(|_| ()),
+        ] {
+            assert!(matches!(result, Err(SerializationError::UnexpectedType { found: DataType::String, .. })));
+        }
+    }
+
+    #[test]
+    fn floats_compare_by_their_bits() {
+        assert_eq!(Value::Float(f32::NAN), Value::Float(f32::NAN));
+        assert_eq!(Value::Double(f64::NAN), Value::Double(f64::NAN));
+        assert_ne!(Value::Double(0.0), Value::Double(-0.0));
+        assert_ne!(Value::Float(1.0), Value::Double(1.0));
+        assert_ne!(Value::Int32(1), Value::Int64(1));
+
+        // A NaN survives the wire format and still equals itself
+        assert_eq!(round_trip(&Value::Double(f64::NAN), ByteOrder::Little), Value::Double(f64::NAN));
+    }
+
+    #[test]
+    fn keyed_maps_compare_in_order() {
+        let one = (Value::Int32(1), Value::from("New York"));
+        let two = (Value::Int32(2), Value::from("London"));
+        assert_eq!(Value::KeyedMap(vec![one.clone(), two.clone()]), Value::KeyedMap(vec![one.clone(), two.clone()]));
+        assert_ne!(Value::KeyedMap(vec![one.clone(), two.clone()]), Value::KeyedMap(vec![two, one]));
+    }
+
+    #[test]
+    fn get_path_walks_three_levels_of_maps() {
+        let seats = Value::Map(HashMap::from([("available".to_string(), Value::Int32(42))]));
+        let flight = Value::Map(HashMap::from([("seats".to_string(), seats)]));
+        let update = Value::Map(HashMap::from([("flight".to_string(), flight)]));
+
+        assert_eq!(update.get_path(&["flight", "seats", "available"]), Some(&Value::Int32(42)));
+        assert_eq!(update.get_path(&[]), Some(&update));
+        assert_eq!(update.get_path(&["flight", "fare"]), None);
+        // Walking past a leaf returns None instead of panicking
+        assert_eq!(update.get_path(&["flight", "seats", "available", "more"]), None);
+    }
+
+    #[test]
+    fn index_returns_none_out_of_range_or_for_non_arrays() {
+        let flight_ids = Value::Array(vec![Value::Int32(1), Value::Int32(3)]);
+        assert_eq!(flight_ids.index(1), Some(&Value::Int32(3)));
+        assert_eq!(flight_ids.index(2), None);
+        assert_eq!(Value::from("1,3").index(0), None);
+    }
+
+    #[test]
+    fn typed_array_helpers_read_homogeneous_arrays() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_array(&[3, 1, 2]).unwrap();
+        serializer.serialize_array(&["Oslo", "Rome"]).unwrap();
+        serializer.serialize_array(&[1.5f32, -0.25]).unwrap();
+        serializer.serialize_array(&[true, false]).unwrap();
+        serializer.serialize_array::<i32>(&[]).unwrap();
+        let buffer = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        assert_eq!(deserializer.deserialize_int32_array().unwrap(), [3, 1, 2]);
+        assert_eq!(deserializer.deserialize_string_array().unwrap(), ["Oslo", "Rome"]);
+        assert_eq!(deserializer.deserialize_float_array().unwrap(), [1.5, -0.25]);
+        assert_eq!(deserializer.deserialize_bool_array().unwrap(), [true, false]);
+        assert_eq!(deserializer.deserialize_int32_array().unwrap(), Vec::<i32>::new());
+        assert_eq!(deserializer.remaining(), 0);
+    }
+
+    #[test]
+    fn typed_array_helper_fails_at_a_mismatch_in_the_middle() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_array(&[Value::Int32(1), Value::from("2"), Value::Int32(3)]).unwrap();
+        let buffer = serializer.get_buffer();
+
+        assert!(matches!(
+            Deserializer::new(&buffer, ByteOrder::Little).deserialize_int32_array(),
+            Err(SerializationError::UnexpectedType { expected: DataType::Int32, found: DataType::String })
+        ));
+        assert!(matches!(
+            Deserializer::new(&buffer, ByteOrder::Little).deserialize_string_array(),
+            Err(SerializationError::UnexpectedType { expected: DataType::String, found: DataType::Int32 })
+        ));
+    }
+
+    #[test]
+    fn mixed_value_map_decodes_to_equal_values() {
+        let response = HashMap::from([
+            ("status".to_string(), Value::from("200")),
+            ("airfare".to_string(), Value::Float(299.99)),
+            ("seats_available".to_string(), Value::Int32(42)),
+        ]);
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_value_map(&response).unwrap();
+        let buffer = serializer.get_buffer();
+
+        let decoded = Deserializer::new(&buffer, ByteOrder::Big).deserialize_next().unwrap();
+        assert_eq!(decoded.get_path(&["airfare"]), Some(&Value::Float(299.99)));
+        assert_eq!(decoded, Value::Map(response));
+    }
+
+    /// Claims to hold more items than fit in an i32 length prefix without producing any
+    struct HugeIter;
+
+    impl Iterator for HugeIter {
+        type Item = i32;
+
+        fn next(&mut self) -> Option<i32> {
+            None
+        }
+
+        fn size_hint(&self) -> (usize, Option<usize>) {
+            let len = i32::MAX as usize + 1;
+            (len, Some(len))
+        }
+    }
+
+    impl ExactSizeIterator for HugeIter {}
+
+    #[test]
+    fn length_beyond_i32_is_rejected_before_writing() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        let error = serializer.serialize_iter(HugeIter).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(serializer.buffer().is_empty());
+
+        let error = serializer.write_header(DataType::String, usize::MAX).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(serializer.buffer().is_empty());
+        serializer.write_header(DataType::String, i32::MAX as usize).unwrap();
+    }
+
+    #[test]
+    fn envelope_and_payload_round_trip() {
+        for byte_order in [ByteOrder::Little, ByteOrder::Big] {
+            let envelope = Envelope { request_id: u64::MAX - 1, invocation_semantic: InvocationSemantic::AtLeastOnce.into(), action: 12 };
+            let payload = HashMap::from([("legs".to_string(), "1:2,3:1".to_string())]);
+            let mut serializer = Serializer::new(byte_order);
+            envelope.serialize(&mut serializer).unwrap();
+            serializer.serialize_map(&payload).unwrap();
+            let buffer = serializer.get_buffer();
+
+            let mut deserializer = Deserializer::new(&buffer, byte_order);
+            let decoded = Envelope::deserialize(&mut deserializer).unwrap();
+            assert_eq!(decoded, envelope);
+            assert_eq!(InvocationSemantic::try_from(decoded.invocation_semantic), Ok(InvocationSemantic::AtLeastOnce));
+            assert_eq!(deserializer.position(), Envelope::LEN as u64);
+            assert_eq!(deserializer.deserialize_next().unwrap(), Value::Map(HashMap::from([("legs".to_string(), Value::from("1:2,3:1"))])));
+        }
+    }
+
+    #[test]
+    fn truncated_envelope_is_an_unexpected_eof() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        Envelope { request_id: 7, invocation_semantic: 0, action: 1 }.serialize(&mut serializer).unwrap();
+        let buffer = serializer.get_buffer();
+        assert!(matches!(
+            Envelope::deserialize(&mut Deserializer::new(&buffer[..Envelope::LEN - 1], ByteOrder::Little)),
+            Err(SerializationError::UnexpectedEof)
+        ));
+    }
+
+    #[test]
+    fn invalid_utf8_reports_where_the_bad_byte_is() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_int32(1).unwrap();
+        // 0xC3 starts a two-byte sequence, but 0x28 is not a continuation byte
+        serializer.serialize_bytes(&[b'a', 0xC3, 0x28, b'b']).unwrap();
+        let mut buffer = serializer.get_buffer();
+        buffer[5] = DataType::String.tag();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        deserializer.deserialize_next().unwrap();
+        let error = deserializer.deserialize_next().unwrap_err();
+        assert!(matches!(error, SerializationError::InvalidUtf8 { position: 11, valid_up_to: 1 }), "{:?}", error);
+        assert_eq!(error.to_string(), "Invalid UTF-8 in string starting at byte 11: first invalid byte at 12 (valid up to 1)");
+    }
+
+    #[test]
+    fn try_next_tells_a_clean_end_from_a_truncated_value() {
+        assert!(Deserializer::new(&[], ByteOrder::Little).try_next().unwrap().is_none());
+
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_int32(1).unwrap();
+        serializer.serialize_string("London").unwrap();
+        let buffer = serializer.get_buffer();
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        assert_eq!(deserializer.try_next().unwrap(), Some(Value::Int32(1)));
+        assert_eq!(deserializer.try_next().unwrap(), Some(Value::from("London")));
+        assert!(deserializer.try_next().unwrap().is_none());
+
+        // The same buffer cut off inside the string is an error, not the end of the stream
+        let mut deserializer = Deserializer::new(&buffer[..buffer.len() - 1], ByteOrder::Little);
+        assert_eq!(deserializer.try_next().unwrap(), Some(Value::Int32(1)));
+        assert!(deserializer.try_next().is_err());
+    }
+
+    #[test]
+    fn serialize_iter_writes_the_same_bytes_as_an_array() {
+        let mut from_iter = Serializer::new(ByteOrder::Little);
+        from_iter.serialize_iter((0..5).map(|flight_id| flight_id * 10)).unwrap();
+        let mut from_slice = Serializer::new(ByteOrder::Little);
+        from_slice.serialize_array(&[0, 10, 20, 30, 40]).unwrap();
+        assert_eq!(from_iter.buffer(), from_slice.buffer());
+
+        let buffer = from_iter.get_buffer();
+        assert_eq!(Deserializer::new(&buffer, ByteOrder::Little).deserialize_int32_array().unwrap(), [0, 10, 20, 30, 40]);
+    }
+
+    const STATUS: EnumRegistry = EnumRegistry::new("status", &[(0, "200"), (1, "400"), (2, "404"), (3, "500")]);
+
+    #[test]
+    fn status_enum_round_trips_as_one_byte() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_enum_label(&STATUS, "404").unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer, [DataType::Enum.tag(), 2]);
+
+        assert_eq!(Deserializer::new(&buffer, ByteOrder::Little).deserialize_enum_label(&STATUS).unwrap(), "404");
+        assert_eq!(Deserializer::new(&buffer, ByteOrder::Little).deserialize_next().unwrap(), Value::Enum(2));
+    }
+
+    #[test]
+    fn unknown_status_is_rejected_on_both_ends() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        let error = serializer.serialize_enum_label(&STATUS, "418").unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(serializer.buffer().is_empty());
+
+        serializer.serialize_enum(9).unwrap();
+        let buffer = serializer.get_buffer();
+        assert!(matches!(
+            Deserializer::new(&buffer, ByteOrder::Little).deserialize_enum_label(&STATUS),
+            Err(SerializationError::UnknownEnum { name: "status", discriminant: 9 })
+        ));
+    }
+
+    #[test]
+    fn serialize_value_writes_a_map_that_decodes_back() {
+        let update = Value::Map(HashMap::from([
+            ("flight_id".to_string(), Value::Int32(1)),
+            ("seats_available".to_string(), Value::Int32(41)),
+            ("history".to_string(), Value::Array(vec![Value::Int32(42), Value::Int32(41)])),
+        ]));
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_value(&update).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(Deserializer::new(&buffer, ByteOrder::Little).deserialize_next().unwrap(), update);
+    }
+
+    #[test]
+    fn checksummed_buffer_round_trips_and_detects_a_flipped_bit() {
+        // CRC32 (IEEE) check value
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        for byte_order in [ByteOrder::Little, ByteOrder::Big] {
+            let mut serializer = Serializer::new(byte_order);
+            serializer.serialize_string("Singapore").unwrap();
+            let mut framed = serializer.finish_with_checksum();
+            let mut deserializer = Deserializer::new_checked(&framed, byte_order).unwrap();
+            assert_eq!(deserializer.deserialize_next().unwrap(), Value::String("Singapore".to_string()));
+
+            framed[3] ^= 0x01;
+            assert!(matches!(Deserializer::new_checked(&framed, byte_order), Err(SerializationError::ChecksumMismatch { .. })));
+        }
+        assert!(matches!(Deserializer::new_checked(&[1, 2], ByteOrder::Little), Err(SerializationError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn versioned_buffer_starts_with_the_magic_and_version() {
+        let mut serializer = Serializer::new_versioned(ByteOrder::Little, 3);
+        serializer.serialize_int32(7).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(&buffer[..3], b"FS\x03");
+
+        let (mut deserializer, version) = Deserializer::new_versioned(&buffer, ByteOrder::Little).unwrap();
+        assert_eq!(version, 3);
+        assert_eq!(deserializer.deserialize_next().unwrap(), Value::Int32(7));
+
+        assert!(matches!(Deserializer::new_versioned(b"XS\x03", ByteOrder::Little), Err(SerializationError::InvalidMagic)));
+        assert!(matches!(Deserializer::new_versioned(b"FS", ByteOrder::Little), Err(SerializationError::InvalidMagic)));
+    }
+
+    #[test]
+    fn datetime_keeps_milliseconds_and_rejects_out_of_range_timestamps() {
+        let departure = NaiveDate::from_ymd_opt(2024, 8, 30).unwrap().and_hms_milli_opt(10, 0, 0, 250).unwrap();
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_datetime(&departure).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer[1..], departure.and_utc().timestamp_millis().to_be_bytes());
+        assert_eq!(Deserializer::new(&buffer, ByteOrder::Big).deserialize_next().unwrap(), Value::DateTime(departure));
+
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_int64(i64::MAX).unwrap();
+        let mut buffer = serializer.get_buffer();
+        buffer[0] = DataType::DateTime.tag();
+        assert!(matches!(
+            Deserializer::new(&buffer, ByteOrder::Little).deserialize_next(),
+            Err(SerializationError::TimestampOutOfRange(i64::MAX))
+        ));
+    }
+
+    #[test]
+    fn errors_name_what_went_wrong_and_map_onto_io_error_kinds() {
+        let unknown = Deserializer::new(&[0xee], ByteOrder::Little).deserialize_next().unwrap_err();
+        assert!(matches!(unknown, SerializationError::UnknownType(0xee)));
+        assert_eq!(unknown.to_string(), "Unknown data type tag 238");
+        assert_eq!(std::io::Error::from(unknown).kind(), std::io::ErrorKind::InvalidData);
+
+        let eof = Deserializer::new(&[DataType::Int32.tag(), 1], ByteOrder::Little).deserialize_next().unwrap_err();
+        assert!(matches!(eof, SerializationError::UnexpectedEof));
+        assert_eq!(std::io::Error::from(eof).kind(), std::io::ErrorKind::UnexpectedEof);
+
+        // Other I/O errors are kept as they are
+        let io = SerializationError::from(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "closed"));
+        assert!(std::error::Error::source(&io).is_some());
+        assert_eq!(std::io::Error::from(io).kind(), std::io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn reset_clears_the_buffer_but_keeps_capacity_and_settings() {
+        let mut serializer = Serializer::with_capacity(ByteOrder::Big, 64);
+        serializer.serialize_int32(1).unwrap();
+        serializer.reset();
+        assert!(serializer.buffer().is_empty());
+        assert_eq!(serializer.byte_order(), ByteOrder::Big);
+
+        serializer.serialize_int32(2).unwrap();
+        let buffer = serializer.get_buffer();
+        assert!(buffer.capacity() >= 64);
+        assert_eq!(buffer, [DataType::Int32.tag(), 0, 0, 0, 2]);
+    }
+
+    /// Compares 10k serialize cycles on one reused Serializer against a fresh one per cycle.
+    ///
+    /// Timing depends on the machine, so this only runs on request:
+    /// `cargo test --release reused_serializer -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn reused_serializer_avoids_an_allocation_per_cycle() {
+        const CYCLES: usize = 10_000;
+        let mut response = HashMap::new();
+        response.insert("status".to_string(), Value::from("200"));
+        response.insert("request_id".to_string(), Value::from("42"));
+        response.insert("departure_time".to_string(), Value::DateTime(departure()));
+        response.insert("airfare".to_string(), Value::Int64(45000));
+        response.insert("seats_available".to_string(), Value::Int32(42));
+
+        let start = std::time::Instant::now();
+        let mut fresh_bytes = 0;
+        for _ in 0..CYCLES {
+            let mut serializer = Serializer::new(ByteOrder::Little);
+            serializer.serialize_value_map(&response).unwrap();
+            fresh_bytes += serializer.get_buffer().len();
+        }
+        let fresh = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let mut reused_bytes = 0;
+        let mut serializer = Serializer::with_capacity(ByteOrder::Little, 256);
+        let allocation = serializer.buffer().as_ptr();
+        let mut reallocations = 0;
+        for _ in 0..CYCLES {
+            serializer.reset();
+            serializer.serialize_value_map(&response).unwrap();
+            reused_bytes += serializer.buffer().len();
+            if serializer.buffer().as_ptr() != allocation {
+                reallocations += 1;
+            }
+        }
+        let reused = start.elapsed();
+
+        println!("{} cycles: fresh {:?}, reused {:?}", CYCLES, fresh, reused);
+        assert_eq!(fresh_bytes, reused_bytes);
+        // The reused buffer never grows past its initial capacity, so it is never reallocated
+        assert_eq!(reallocations, 0);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json_round_trip_keeps_plain_values_and_flags_lossy_ones() {
+        let mut map = HashMap::new();
+        map.insert("flight_ids".to_string(), Value::Array(vec![Value::Int32(1), Value::Int32(2)]));
+        map.insert("total_seats".to_string(), Value::Int64(5_000_000_000));
+        map.insert("status".to_string(), Value::from("200"));
+        map.insert("refundable".to_string(), Value::Bool(false));
+        map.insert("note".to_string(), Value::Null);
+        let value = Value::Map(map);
+        assert_eq!(Value::from_json(&value.to_json()).unwrap(), value);
+
+        assert_eq!(Value::Double(f64::NAN).to_json(), serde_json::Value::Null);
+        assert_eq!(Value::DateTime(departure()).to_json(), serde_json::json!("2024-09-01 10:00:00"));
+        assert!(matches!(Value::from_json(&serde_json::json!(u64::MAX)), Err(SerializationError::InvalidJson(_))));
+    }
+
+    #[test]
+    fn byte_order_parses_from_its_config_name() {
+        assert_eq!("big".parse::<ByteOrder>(), Ok(ByteOrder::Big));
+        assert_eq!("little".parse::<ByteOrder>(), Ok(ByteOrder::Little));
+        assert_eq!("Big".parse::<ByteOrder>(), Err("Invalid byte order 'Big', expected \"little\" or \"big\"".to_string()));
+        assert_eq!(ByteOrder::Big.to_string().parse::<ByteOrder>(), Ok(ByteOrder::Big));
+    }
+
+    #[test]
+    fn int64_is_written_as_eight_bytes_in_the_chosen_byte_order() {
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_int64(0x0102_0304_0506_0708).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer, [DataType::Int64.tag(), 1, 2, 3, 4, 5, 6, 7, 8]);
+
+        for value in [i64::MIN, -1, 5_000_000_000, i64::MAX] {
+            assert_eq!(round_trip(&Value::Int64(value), ByteOrder::Little), Value::Int64(value));
+        }
+    }
+
+    #[test]
+    fn double_keeps_full_precision_where_float_would_round() {
+        let fare = 299.99_f64;
+        assert_eq!(round_trip(&Value::Double(fare), ByteOrder::Big), Value::Double(fare));
+        assert_ne!(fare as f32 as f64, fare);
+
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_double(fare).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer.len(), 9);
+        assert_eq!(buffer[1..], fare.to_le_bytes());
+    }
+
+    #[test]
+    fn none_is_written_as_a_bare_null_tag() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        None::<i32>.serialize(&mut serializer).unwrap();
+        Some(7).serialize(&mut serializer).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer[0], DataType::Null.tag());
+
+        let mut deserializer = Deserializer::new(&buffer, ByteOrder::Little);
+        assert_eq!(deserializer.deserialize_next().unwrap(), Value::Null);
+        assert_eq!(deserializer.deserialize_next().unwrap(), Value::Int32(7));
+        assert!(Value::Null.as_i32().is_none());
+    }
+
+    #[test]
+    fn bytes_that_are_not_utf8_round_trip_unchanged() {
+        let blob = vec![0xff, 0xfe, 0x00, 0x80];
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_bytes(&blob).unwrap();
+        let buffer = serializer.get_buffer();
+        assert_eq!(buffer[0], DataType::Bytes.tag());
+        assert_eq!(Deserializer::new(&buffer, ByteOrder::Big).deserialize_next().unwrap(), Value::Bytes(blob.clone()));
+
+        // The same bytes are rejected as a string
+        let mut serializer = Serializer::new(ByteOrder::Big);
+        serializer.serialize_bytes(&blob).unwrap();
+        let mut buffer = serializer.get_buffer();
+        buffer[0] = DataType::String.tag();
+        assert!(matches!(Deserializer::new(&buffer, ByteOrder::Big).deserialize_next(), Err(SerializationError::InvalidUtf8 { .. })));
+    }
+
+    #[test]
+    fn string_length_beyond_the_buffer_is_rejected_before_allocating() {
+        let mut serializer = Serializer::new(ByteOrder::Little);
+        serializer.serialize_string("abc").unwrap();
+        let mut buffer = serializer.get_buffer();
+        buffer[2..6].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(matches!(
+            Deserializer::new(&buffer, ByteOrder::Little).deserialize_next(),
+            Err(SerializationError::LengthTooLarge { declared, available: 3 }) if declared == i32::MAX as i64
+        ));
+
+        buffer[2..6].copy_from_slice(&(-1i32).to_le_bytes());
+        assert!(matches!(Deserializer::new(&buffer, ByteOrder::Little).deserialize_next(), Err(SerializationError::LengthTooLarge { declared: -1, .. })));
+    }
+}
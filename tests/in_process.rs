@@ -0,0 +1,111 @@
+//! Runs a `Server` on an ephemeral loopback port in a background thread and drives it with
+//! `FlightClient`s through the library target, so the full UDP path runs under `cargo test`.
+
+use std::io;
+use std::process::{Command, Output};
+use std::sync::atomic::Ordering;
+use std::thread;
+use chrono::NaiveDateTime;
+
+use server::config::Config;
+use server::controller::{Flight, Response};
+use server::flight_client::FlightClient;
+use server::flight_server::Server;
+
+/// Binds a server on an ephemeral loopback port with one 10-seat flight, id 1
+fn server_with_flight() -> Server {
+    let mut config = Config::default();
+    config.server.address = "127.0.0.1:0".to_string();
+    let server = Server::bind(config).unwrap();
+    server.controller.add_flight(Flight {
+        flight_id: 1,
+        source: "Singapore".to_string(),
+        destination: "Tokyo".to_string(),
+        departure_time: NaiveDateTime::parse_from_str("2024-10-01 08:00:00", "%Y-%m-%d %H:%M:%S").unwrap(),
+        airfare: 45000,
+        seats_available: 10,
+        capacity: 10,
+        overbook_limit: 0,
+    }).unwrap();
+    server
+}
+
+fn client_for(server: &Server) -> FlightClient {
+    let mut config = Config::default();
+    config.server.address = server.local_addr().unwrap().to_string();
+    let mut client = FlightClient::new(config).unwrap();
+    client.set_verbose(false);
+    client
+}
+
+#[test]
+fn monitoring_client_sees_another_clients_reservation() {
+    let server = server_with_flight();
+    let mut watcher = client_for(&server);
+    let mut buyer = client_for(&server);
+
+    let update = thread::scope(|scope| {
+        scope.spawn(|| server.run().unwrap());
+        let mut calls = || -> io::Result<_> {
+            assert!(matches!(watcher.monitor_flight(1, 30)?, Response::MonitoringStarted(Ok(()))));
+            assert!(matches!(buyer.reserve_seats(1, 4)?, Response::Reservation(Ok(0))));
+            watcher.receive_update()
+        };
+        // Shut the server down even if a call failed, otherwise the scope waits on it forever
+        let update = calls();
+        server.shutdown_handle().store(true, Ordering::SeqCst);
+        update
+    })
+    .unwrap();
+
+    assert_eq!(update["action"], "5".into());
+    assert_eq!(update["seats_available"], "6".into());
+}
+
+#[test]
+fn scripted_client_prints_json_and_exits_with_the_outcome() {
+    let server = server_with_flight();
+    let address = server.local_addr().unwrap().to_string();
+    let client = |args: &[&str]| -> Output {
+        Command::new(env!("CARGO_BIN_EXE_client")).args(args).env("FLIGHT_SERVER_ADDRESS", &address).output().unwrap()
+    };
+
+    let (details, rejected, malformed) = thread::scope(|scope| {
+        scope.spawn(|| server.run().unwrap());
+        let outputs = (
+            client(&["details", "--flight-id", "1"]),
+            client(&["reserve", "--flight-id", "1", "--seats", "11"]),
+            client(&["reserve", "--flight-id"]),
+        );
+        server.shutdown_handle().store(true, Ordering::SeqCst);
+        outputs
+    });
+
+    assert_eq!(details.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&details.stdout).trim(),
+        r#"{"ok":true,"departure_time":"2024-10-01 08:00:00","airfare":45000,"seats_available":10}"#
+    );
+    assert_eq!(rejected.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&rejected.stdout).starts_with(r#"{"ok":false,"error":"#));
+    // Bad arguments fail before contacting the server
+    assert_eq!(malformed.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&malformed.stderr).starts_with("Missing value for --flight-id"));
+}
+
+#[test]
+fn server_on_an_ephemeral_port_reports_where_it_listens() {
+    let server = server_with_flight();
+    let addr = server.local_addr().unwrap();
+    assert!(addr.ip().is_loopback() && addr.port() != 0);
+
+    let client = client_for(&server);
+    let pinged = thread::scope(|scope| {
+        scope.spawn(|| server.run().unwrap());
+        let pinged = client.ping();
+        server.shutdown_handle().store(true, Ordering::SeqCst);
+        pinged
+    });
+    assert!(pinged.is_ok());
+    assert_eq!(client.server_addr(), addr);
+}